@@ -12,6 +12,21 @@ mod m20240718_184207_content_downloads;
 mod m20240721_161119_libraries;
 mod m20240721_171819_add_root_libraries_to_player_connections;
 mod m20240723_172208_add_sort_key_to_items;
+mod m20240727_101512_add_outputs_to_contents;
+mod m20240728_094033_add_variants_to_content_downloads;
+mod m20240729_201544_shared_transcodes;
+mod m20240730_183012_clips;
+mod m20240731_104218_resume_positions;
+mod m20240801_093512_watch_progress;
+mod m20240802_141107_layout_overrides;
+mod m20240803_101944_short_links;
+mod m20240804_152230_sync_tombstones;
+mod m20240805_190417_search;
+mod m20240806_083127_subscriptions;
+mod m20240807_094512_prefetch;
+mod m20240808_101530_sort_orders;
+mod m20240809_120512_playback_sessions;
+mod m20240810_091204_sync_locks;
 pub struct Migrator;
 
 #[async_trait::async_trait]
@@ -27,6 +42,21 @@ impl MigratorTrait for Migrator {
             Box::new(m20240721_161119_libraries::Migration),
             Box::new(m20240721_171819_add_root_libraries_to_player_connections::Migration),
             Box::new(m20240723_172208_add_sort_key_to_items::Migration),
+            Box::new(m20240727_101512_add_outputs_to_contents::Migration),
+            Box::new(m20240728_094033_add_variants_to_content_downloads::Migration),
+            Box::new(m20240729_201544_shared_transcodes::Migration),
+            Box::new(m20240730_183012_clips::Migration),
+            Box::new(m20240731_104218_resume_positions::Migration),
+            Box::new(m20240801_093512_watch_progress::Migration),
+            Box::new(m20240802_141107_layout_overrides::Migration),
+            Box::new(m20240803_101944_short_links::Migration),
+            Box::new(m20240804_152230_sync_tombstones::Migration),
+            Box::new(m20240805_190417_search::Migration),
+            Box::new(m20240806_083127_subscriptions::Migration),
+            Box::new(m20240807_094512_prefetch::Migration),
+            Box::new(m20240808_101530_sort_orders::Migration),
+            Box::new(m20240809_120512_playback_sessions::Migration),
+            Box::new(m20240810_091204_sync_locks::Migration),
         ]
     }
 }
@@ -0,0 +1,123 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const MOVE_OUTPUTS_UP: &str = r"
+UPDATE content_downloads AS d SET outputs = c.outputs
+FROM contents AS c
+WHERE c.outputs IS NOT NULL
+    AND d.id = (
+        SELECT latest.id FROM content_downloads AS latest
+        WHERE latest.player_connection_id = c.player_connection_id
+            AND latest.content_id = c.content_id
+        ORDER BY latest.updated_at DESC
+        LIMIT 1
+    );
+";
+
+const MOVE_OUTPUTS_DOWN: &str = r"
+UPDATE contents AS c SET outputs = (
+    SELECT d.outputs FROM content_downloads AS d
+    WHERE d.player_connection_id = c.player_connection_id
+        AND d.content_id = c.content_id
+        AND d.variant_id IS NULL
+        AND d.outputs IS NOT NULL
+    ORDER BY d.updated_at DESC
+    LIMIT 1
+);
+";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ContentDownloads::Table)
+                    .add_column_if_not_exists(string_null(ContentDownloads::VariantId))
+                    .add_column_if_not_exists(json_null(ContentDownloads::Variant))
+                    .add_column_if_not_exists(json_null(ContentDownloads::Outputs))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-content_downloads-variant")
+                    .table(ContentDownloads::Table)
+                    .col(ContentDownloads::PlayerConnectionId)
+                    .col(ContentDownloads::ContentId)
+                    .col(ContentDownloads::VariantId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Outputs are tracked per variant now, the ones exported so far belong to
+        // the content's latest download, which sits in the content dir.
+        manager
+            .get_connection()
+            .execute_unprepared(MOVE_OUTPUTS_UP)
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Contents::Table)
+                    .drop_column(Contents::Outputs)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Contents::Table)
+                    .add_column_if_not_exists(json_null(Contents::Outputs))
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .get_connection()
+            .execute_unprepared(MOVE_OUTPUTS_DOWN)
+            .await?;
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-content_downloads-variant")
+                    .table(ContentDownloads::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ContentDownloads::Table)
+                    .drop_column(ContentDownloads::VariantId)
+                    .drop_column(ContentDownloads::Variant)
+                    .drop_column(ContentDownloads::Outputs)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ContentDownloads {
+    Table,
+    PlayerConnectionId,
+    ContentId,
+    VariantId,
+    Variant,
+    Outputs,
+}
+
+#[derive(DeriveIden)]
+enum Contents {
+    Table,
+    Outputs,
+}
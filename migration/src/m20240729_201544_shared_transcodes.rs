@@ -0,0 +1,89 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                table_auto(SharedTranscodes::Table)
+                    .col(string(SharedTranscodes::Key).primary_key())
+                    .col(string(SharedTranscodes::MediaProviderId))
+                    .col(string(SharedTranscodes::ContentId))
+                    .col(string(SharedTranscodes::VariantId))
+                    .col(
+                        ColumnDef::new(SharedTranscodes::Status)
+                            .custom(Alias::new("status_name"))
+                            .not_null()
+                            .to_owned(),
+                    )
+                    .col(json_null(SharedTranscodes::Outputs))
+                    .col(integer(SharedTranscodes::RefCount).default(0))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ContentDownloads::Table)
+                    .add_column_if_not_exists(string_null(ContentDownloads::SharedKey))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-content_downloads-shared_key")
+                    .table(ContentDownloads::Table)
+                    .col(ContentDownloads::SharedKey)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-content_downloads-shared_key")
+                    .table(ContentDownloads::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ContentDownloads::Table)
+                    .drop_column(ContentDownloads::SharedKey)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(SharedTranscodes::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum SharedTranscodes {
+    Table,
+    Key,
+    MediaProviderId,
+    ContentId,
+    VariantId,
+    Status,
+    Outputs,
+    RefCount,
+}
+
+#[derive(DeriveIden)]
+enum ContentDownloads {
+    Table,
+    SharedKey,
+}
@@ -0,0 +1,84 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                table_auto(Clips::Table)
+                    .col(
+                        uuid(Clips::Id)
+                            .extra("DEFAULT gen_random_uuid()")
+                            .primary_key(),
+                    )
+                    .col(integer(Clips::PlayerConnectionId))
+                    .col(string(Clips::ContentId))
+                    .col(uuid(Clips::ContentDownloadId))
+                    .col(string(Clips::Name))
+                    .col(big_integer(Clips::StartMs))
+                    .col(big_integer(Clips::EndMs))
+                    .col(
+                        ColumnDef::new(Clips::Status)
+                            .custom(Alias::new("status_name"))
+                            .not_null()
+                            .to_owned(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-clips-contents")
+                            .from(Clips::Table, (Clips::PlayerConnectionId, Clips::ContentId))
+                            .to(
+                                Contents::Table,
+                                (Contents::PlayerConnectionId, Contents::ContentId),
+                            )
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-clips-content_downloads")
+                            .from(Clips::Table, Clips::ContentDownloadId)
+                            .to(ContentDownloads::Table, ContentDownloads::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Clips::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Clips {
+    Table,
+    Id,
+    PlayerConnectionId,
+    ContentId,
+    ContentDownloadId,
+    Name,
+    StartMs,
+    EndMs,
+    Status,
+}
+
+#[derive(DeriveIden)]
+enum Contents {
+    Table,
+    PlayerConnectionId,
+    ContentId,
+}
+
+#[derive(DeriveIden)]
+enum ContentDownloads {
+    Table,
+    Id,
+}
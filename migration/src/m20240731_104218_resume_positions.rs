@@ -0,0 +1,63 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PlayerConnections::Table)
+                    .add_column_if_not_exists(
+                        uuid_uniq(PlayerConnections::StreamToken)
+                            .extra("DEFAULT gen_random_uuid()"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Contents::Table)
+                    .add_column_if_not_exists(big_integer_null(Contents::ResumePositionMs))
+                    .add_column_if_not_exists(timestamp_null(Contents::ResumeUpdatedAt))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Contents::Table)
+                    .drop_column(Contents::ResumePositionMs)
+                    .drop_column(Contents::ResumeUpdatedAt)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PlayerConnections::Table)
+                    .drop_column(PlayerConnections::StreamToken)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum PlayerConnections {
+    Table,
+    StreamToken,
+}
+
+#[derive(DeriveIden)]
+enum Contents {
+    Table,
+    ResumePositionMs,
+    ResumeUpdatedAt,
+}
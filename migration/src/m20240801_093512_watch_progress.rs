@@ -0,0 +1,38 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Contents::Table)
+                    .add_column_if_not_exists(big_integer_null(Contents::DurationMs))
+                    .add_column_if_not_exists(timestamp_null(Contents::PlayedAt))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Contents::Table)
+                    .drop_column(Contents::DurationMs)
+                    .drop_column(Contents::PlayedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Contents {
+    Table,
+    DurationMs,
+    PlayedAt,
+}
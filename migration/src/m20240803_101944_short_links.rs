@@ -0,0 +1,59 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                table_auto(ShortLinks::Table)
+                    .col(
+                        uuid(ShortLinks::Id)
+                            .extra("DEFAULT gen_random_uuid()")
+                            .primary_key(),
+                    )
+                    .col(string_uniq(ShortLinks::Code))
+                    .col(integer(ShortLinks::UserId))
+                    .col(string(ShortLinks::Target))
+                    .col(timestamp_null(ShortLinks::ExpiresAt))
+                    .col(big_integer(ShortLinks::Uses).default(0))
+                    .col(timestamp_null(ShortLinks::LastUsedAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-short_links-users")
+                            .from(ShortLinks::Table, ShortLinks::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ShortLinks::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ShortLinks {
+    Table,
+    Id,
+    Code,
+    UserId,
+    Target,
+    ExpiresAt,
+    Uses,
+    LastUsedAt,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+}
@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Names weigh more than series names, which weigh more than descriptions. The
+// `simple` config doesn't stem, titles are in all sorts of languages.
+const CONTENTS_UP: &str = r"
+ALTER TABLE contents ADD COLUMN IF NOT EXISTS search tsvector GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', coalesce(cached_data->>'name', '')), 'A') ||
+    setweight(to_tsvector('simple', coalesce(cached_data->>'series_name', '')), 'B') ||
+    setweight(to_tsvector('simple', coalesce(cached_data->>'description', '')), 'C')
+) STORED;
+CREATE INDEX IF NOT EXISTS idx_contents_search ON contents USING GIN (search);
+";
+
+const LIBRARIES_UP: &str = r"
+ALTER TABLE libraries ADD COLUMN IF NOT EXISTS search tsvector GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', coalesce(cached_data->>'name', '')), 'A') ||
+    setweight(to_tsvector('simple', coalesce(cached_data->>'description', '')), 'C')
+) STORED;
+CREATE INDEX IF NOT EXISTS idx_libraries_search ON libraries USING GIN (search);
+";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared(CONTENTS_UP).await?;
+        db.execute_unprepared(LIBRARIES_UP).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "DROP INDEX IF EXISTS idx_libraries_search; ALTER TABLE libraries DROP COLUMN IF EXISTS search;",
+        )
+        .await?;
+        db.execute_unprepared(
+            "DROP INDEX IF EXISTS idx_contents_search; ALTER TABLE contents DROP COLUMN IF EXISTS search;",
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                table_auto(Subscriptions::Table)
+                    .col(pk_auto(Subscriptions::Id))
+                    .col(integer(Subscriptions::PlayerConnectionId))
+                    .col(string(Subscriptions::LibraryId))
+                    .col(string(Subscriptions::Name))
+                    .col(string_null(Subscriptions::Profile))
+                    .col(string_null(Subscriptions::AudioLanguage))
+                    .col(string_null(Subscriptions::SubtitleLanguage))
+                    .col(timestamp_null(Subscriptions::LastCheckedAt))
+                    .col(big_integer(Subscriptions::Downloads).default(0))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-subscriptions-player_connections")
+                            .from(Subscriptions::Table, Subscriptions::PlayerConnectionId)
+                            .to(PlayerConnections::Table, PlayerConnections::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx-subscriptions-connection-library")
+                            .col(Subscriptions::PlayerConnectionId)
+                            .col(Subscriptions::LibraryId)
+                            .unique(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Subscriptions::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Subscriptions {
+    Table,
+    Id,
+    PlayerConnectionId,
+    LibraryId,
+    Name,
+    Profile,
+    AudioLanguage,
+    SubtitleLanguage,
+    LastCheckedAt,
+    Downloads,
+}
+
+#[derive(DeriveIden)]
+enum PlayerConnections {
+    Table,
+    Id,
+}
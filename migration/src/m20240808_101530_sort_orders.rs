@@ -0,0 +1,90 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Every order ends with the id so keyset pages never skip ties. Existing rows
+// are filled from their cached data, the next sync brings the provider's sort
+// names and dates. The episode `sort_key` mirrors `Content::sort_key` in the
+// players crate and has to stay in sync with it, specials are only placed
+// where they aired by the next sync.
+const CONTENTS_UP: &str = r"
+ALTER TABLE contents
+    ADD COLUMN IF NOT EXISTS sort_name text NOT NULL DEFAULT '',
+    ADD COLUMN IF NOT EXISTS production_year integer NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS date_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN IF NOT EXISTS runtime_ms bigint NOT NULL DEFAULT 0;
+UPDATE contents SET
+    sort_name = lower(coalesce(cached_data->>'sort_name', cached_data->>'name', '')),
+    production_year = coalesce((cached_data->>'production_year')::integer, 0),
+    date_added = least(created_at, coalesce((cached_data->>'date_added')::timestamp, created_at)),
+    runtime_ms = coalesce((cached_data->>'runtime_ms')::bigint, duration_ms, 0),
+    sort_key = CASE WHEN cached_data->'kind'->>'type' = 'Episode' THEN
+        greatest(coalesce((cached_data->'kind'->>'season')::bigint, 0), 0) * 10000000
+            + least(greatest((cached_data->'kind'->>'episode')::bigint, 0), 9999) * 1000 + 500
+        ELSE 0 END
+WHERE cached_data IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_contents_order_episode
+    ON contents (player_connection_id, parent_id, sort_key, sort_name, content_id);
+CREATE INDEX IF NOT EXISTS idx_contents_order_name
+    ON contents (player_connection_id, parent_id, sort_name, content_id);
+CREATE INDEX IF NOT EXISTS idx_contents_order_year
+    ON contents (player_connection_id, parent_id, production_year, sort_name, content_id);
+CREATE INDEX IF NOT EXISTS idx_contents_order_date_added
+    ON contents (player_connection_id, parent_id, date_added, content_id);
+CREATE INDEX IF NOT EXISTS idx_contents_order_runtime
+    ON contents (player_connection_id, parent_id, runtime_ms, content_id);
+";
+
+// Seasons sort by their number, everything else by name.
+const LIBRARIES_UP: &str = r"
+ALTER TABLE libraries ADD COLUMN IF NOT EXISTS sort_name text NOT NULL DEFAULT '';
+UPDATE libraries SET
+    sort_name = lower(coalesce(cached_data->>'sort_name', cached_data->>'name', '')),
+    sort_key = CASE WHEN cached_data->'kind'->>'type' = 'Season' THEN
+        greatest((cached_data->'kind'->>'season')::bigint, 0)
+        ELSE 0 END
+WHERE cached_data IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_libraries_order
+    ON libraries (player_connection_id, parent_id, sort_key, sort_name, library_id);
+";
+
+const USERS_UP: &str =
+    "ALTER TABLE users ADD COLUMN IF NOT EXISTS library_sort text NOT NULL DEFAULT 'episode';";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared(CONTENTS_UP).await?;
+        db.execute_unprepared(LIBRARIES_UP).await?;
+        db.execute_unprepared(USERS_UP).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared("ALTER TABLE users DROP COLUMN IF EXISTS library_sort;")
+            .await?;
+        db.execute_unprepared(
+            "DROP INDEX IF EXISTS idx_libraries_order; ALTER TABLE libraries DROP COLUMN IF EXISTS sort_name;",
+        )
+        .await?;
+        db.execute_unprepared(
+            r"
+DROP INDEX IF EXISTS idx_contents_order_episode;
+DROP INDEX IF EXISTS idx_contents_order_name;
+DROP INDEX IF EXISTS idx_contents_order_year;
+DROP INDEX IF EXISTS idx_contents_order_date_added;
+DROP INDEX IF EXISTS idx_contents_order_runtime;
+ALTER TABLE contents
+    DROP COLUMN IF EXISTS sort_name,
+    DROP COLUMN IF EXISTS production_year,
+    DROP COLUMN IF EXISTS date_added,
+    DROP COLUMN IF EXISTS runtime_ms;
+",
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
 use self::types::{BaseItemKind, ResponseProfile, SubtitleProfile, TranscodingProfile};
 use crate::types::{
-    Content, ContentKind, Item, Library, LibraryKind, M3U8Playlist, MediaStream, TranscodeJob,
+    Airs, Chapter, Content, ContentKind, DirectFile, Item, ItemsPage, ItemsQuery, Library,
+    LibraryKind, M3U8Playlist, MediaSource, MediaStream, Metadata, Person, PlaybackEvent, Shelf,
+    SkipKind, SkipRange, TranscodeJob, UserData, VrLayout,
 };
 use chrono::Utc;
 use progenitor::generate_api;
@@ -12,6 +14,17 @@ use uuid::Uuid;
 
 generate_api!("schemas/jellyfin-openapi-stable-models-only.json");
 
+/// Items per request when fetching a whole library, big libraries time out
+/// when fetched at once.
+const PAGE_SIZE: usize = 200;
+
+/// Fields asked for whenever items are fetched with their metadata.
+const ITEM_FIELDS: &str = "ParentId,DateCreated,MediaSources,MediaStreams,BasicSyncInfo,Genres,Tags,Studios,SeriesStudio,People,Chapters,ChildCount,MediaSourceCount,Overview,Path,SortName,SpecialEpisodeNumbers";
+
+/// Item types worth finding through search, people, genres and the like aren't.
+const SEARCH_ITEM_TYPES: &str =
+    "Movie,Episode,Series,Season,BoxSet,Folder,CollectionFolder,Video,MusicVideo";
+
 #[derive(Clone)]
 pub struct JellyfinConfig {
     pub base_url: String,
@@ -582,47 +595,92 @@ impl JellyfinUser {
                 .await
                 .map(|views| views.into_iter().map(Item::Library).collect()),
             Some(lib) => {
-                let url = format!("{}/Users/{}/Items", self.client.base_url, self.id);
-                let query: &[(&str, &str)] = &[
-                    ("SortBy", "IsFolder,SortName,ProductionYear"),
-                    ("SortOrder", "Ascending"),
-                    // ("IncludeItemTypes", "Movie,Episode".into()),
-                    // ("Recursive", "true".into()),
-                    ("ParentId", &lib.id),
-                    ("Fields", "ParentId,DateCreated,MediaSources,MediaStreams,BasicSyncInfo,Genres,Tags,Studios,SeriesStudio,People,Chapters,ChildCount,MediaSourceCount,Overview"),
-                    ("ImageTypeLimit", "1"),
-                    ("EnableImageTypes", "Primary,Backdrop"),
-                    ("StartIndex", "0"),
-                    ("IsMissing", "false")
-                ];
-                let response: types::BaseItemDtoQueryResult = self
-                    .client
-                    .client
-                    .get(&url)
-                    .query(query)
-                    .header(
-                        "X-Emby-Authorization",
-                        emby_authorization(Some(&self.token)),
-                    )
-                    .send()
-                    .await?
-                    .error_for_status()?
-                    .json()
-                    .await?;
-                Ok(response
-                    .items
-                    .unwrap_or_default()
-                    .into_iter()
-                    .map(|item| item.into())
-                    .collect())
+                let mut items = vec![];
+                loop {
+                    let page = self
+                        .items_page(&lib, items.len(), PAGE_SIZE, ItemsQuery::default())
+                        .await?;
+                    let fetched = page.items.len();
+                    items.extend(page.items);
+                    if fetched < PAGE_SIZE || page.total.is_some_and(|total| items.len() >= total) {
+                        break;
+                    }
+                }
+                Ok(items)
             }
         }
     }
 
+    /// Up to `limit` items of a library, starting at `start_index`.
+    pub async fn items_page(
+        &self,
+        lib: &Library,
+        start_index: usize,
+        limit: usize,
+        filter: ItemsQuery,
+    ) -> Result<ItemsPage, reqwest::Error> {
+        let url = format!("{}/Users/{}/Items", self.client.base_url, self.id);
+        let start_index = start_index.to_string();
+        let limit = limit.to_string();
+        let mut query: Vec<(&str, &str)> = vec![
+            ("SortBy", "IsFolder,SortName,ProductionYear"),
+            ("SortOrder", "Ascending"),
+            // ("IncludeItemTypes", "Movie,Episode".into()),
+            // ("Recursive", "true".into()),
+            ("ParentId", &lib.id),
+            ("StartIndex", &start_index),
+            ("Limit", &limit),
+            ("EnableTotalRecordCount", "true"),
+            ("IsMissing", "false"),
+        ];
+        if filter.ids_only {
+            query.extend([
+                ("Fields", "ParentId"),
+                ("EnableImages", "false"),
+                ("EnableUserData", "false"),
+            ]);
+        } else {
+            query.extend([
+                ("Fields", ITEM_FIELDS),
+                ("ImageTypeLimit", "1"),
+                ("EnableImageTypes", "Primary,Backdrop"),
+            ]);
+        }
+        let changed_since = filter.changed_since.map(|since| since.to_rfc3339());
+        if let Some(changed_since) = &changed_since {
+            query.push(("MinDateLastSaved", changed_since));
+        }
+        let response: types::BaseItemDtoQueryResult = self
+            .client
+            .client
+            .get(&url)
+            .query(&query)
+            .header(
+                "X-Emby-Authorization",
+                emby_authorization(Some(&self.token)),
+            )
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(ItemsPage {
+            items: response
+                .items
+                .unwrap_or_default()
+                .into_iter()
+                .map(|item| item.into())
+                .collect(),
+            total: response
+                .total_record_count
+                .and_then(|total| usize::try_from(total).ok()),
+        })
+    }
+
     pub async fn item(&self, id: &str) -> Result<Item, reqwest::Error> {
         let url = format!("{}/Users/{}/Items/{}", self.client.base_url, self.id, id);
         let query: &[(&str, &str)] = &[
-            ("Fields", "ParentId,DateCreated,MediaSources,MediaStreams,BasicSyncInfo,Genres,Tags,Studios,SeriesStudio,People,Chapters,ChildCount,MediaSourceCount,Overview"),
+            ("Fields", ITEM_FIELDS),
             ("ImageTypeLimit", "1"),
             ("EnableImageTypes", "Primary,Backdrop"),
         ];
@@ -643,11 +701,338 @@ impl JellyfinUser {
         Ok(response.into())
     }
 
+    /// Up to `limit` contents of a shelf, in the server's order.
+    pub async fn shelf(&self, shelf: Shelf, limit: usize) -> Result<Vec<Content>, reqwest::Error> {
+        let limit = limit.to_string();
+        let mut query: Vec<(&str, &str)> = vec![
+            ("Limit", &limit),
+            ("Fields", ITEM_FIELDS),
+            ("ImageTypeLimit", "1"),
+            ("EnableImageTypes", "Primary,Backdrop"),
+        ];
+        let url = match shelf {
+            Shelf::Resume => {
+                query.push(("MediaTypes", "Video"));
+                format!("{}/Users/{}/Items/Resume", self.client.base_url, self.id)
+            }
+            Shelf::NextUp => {
+                query.push(("userId", &self.id));
+                format!("{}/Shows/NextUp", self.client.base_url)
+            }
+            Shelf::Latest => {
+                // Episodes rather than the shows they were added to.
+                query.extend([
+                    ("GroupItems", "false"),
+                    ("IncludeItemTypes", "Movie,Episode,Video,MusicVideo"),
+                ]);
+                format!("{}/Users/{}/Items/Latest", self.client.base_url, self.id)
+            }
+        };
+        let response = self
+            .client
+            .client
+            .get(&url)
+            .query(&query)
+            .header(
+                "X-Emby-Authorization",
+                emby_authorization(Some(&self.token)),
+            )
+            .send()
+            .await?
+            .error_for_status()?;
+        // Latest is the only one answering with a plain list.
+        let items = match shelf {
+            Shelf::Latest => response.json::<Vec<BaseItemDto>>().await?,
+            Shelf::Resume | Shelf::NextUp => response
+                .json::<types::BaseItemDtoQueryResult>()
+                .await?
+                .items
+                .unwrap_or_default(),
+        };
+        Ok(items
+            .into_iter()
+            .filter_map(|item| match Item::from(item) {
+                Item::Content(content) => Some(content),
+                Item::Library(_) => None,
+            })
+            .collect())
+    }
+
+    /// Up to `limit` items matching `term`, best matches first. Uses the server's
+    /// search hints, then fetches the hits with their metadata.
+    pub async fn search(&self, term: &str, limit: usize) -> Result<Vec<Item>, reqwest::Error> {
+        let url = format!("{}/Search/Hints", self.client.base_url);
+        let limit = limit.to_string();
+        let query: &[(&str, &str)] = &[
+            ("searchTerm", term),
+            ("userId", &self.id),
+            ("limit", &limit),
+            ("includeItemTypes", SEARCH_ITEM_TYPES),
+        ];
+        let response: types::SearchHintResult = self
+            .client
+            .client
+            .get(&url)
+            .query(query)
+            .header(
+                "X-Emby-Authorization",
+                emby_authorization(Some(&self.token)),
+            )
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let ids: Vec<String> = response
+            .search_hints
+            .into_iter()
+            .filter_map(|hint| hint.id.or(hint.item_id))
+            .map(|id| id.to_string())
+            .collect();
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let url = format!("{}/Users/{}/Items", self.client.base_url, self.id);
+        let joined = ids.join(",");
+        let query: &[(&str, &str)] = &[
+            ("Ids", &joined),
+            ("Fields", ITEM_FIELDS),
+            ("ImageTypeLimit", "1"),
+            ("EnableImageTypes", "Primary,Backdrop"),
+        ];
+        let response: types::BaseItemDtoQueryResult = self
+            .client
+            .client
+            .get(&url)
+            .query(query)
+            .header(
+                "X-Emby-Authorization",
+                emby_authorization(Some(&self.token)),
+            )
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let mut items: Vec<Item> = response
+            .items
+            .unwrap_or_default()
+            .into_iter()
+            .map(|item| item.into())
+            .collect();
+        // Items come back in library order, put them back in order of relevance.
+        items.sort_by_key(|item| {
+            let id = match item {
+                Item::Library(library) => &library.id,
+                Item::Content(content) => &content.id,
+            };
+            ids.iter().position(|hit| hit == id)
+        });
+        Ok(items)
+    }
+
+    /// Intro, credits and recap ranges of an item. Uses media segments (Jellyfin 10.10+)
+    /// and falls back to guessing from chapter names on older servers.
+    pub async fn skip_ranges(&self, id: &str) -> Result<Vec<SkipRange>, eyre::Error> {
+        let url = format!("{}/MediaSegments/{}", self.client.base_url, id);
+        let response = self
+            .client
+            .client
+            .get(&url)
+            .header(
+                "X-Emby-Authorization",
+                emby_authorization(Some(&self.token)),
+            )
+            .send()
+            .await?;
+        if response.status() != StatusCode::NOT_FOUND {
+            let segments: MediaSegments = response.error_for_status()?.json().await?;
+            let ranges: Vec<SkipRange> = segments
+                .items
+                .into_iter()
+                .filter_map(|segment| {
+                    let kind = match segment.type_.as_str() {
+                        "Intro" => SkipKind::Intro,
+                        "Outro" => SkipKind::Outro,
+                        "Recap" => SkipKind::Recap,
+                        "Preview" => SkipKind::Preview,
+                        "Commercial" => SkipKind::Commercial,
+                        _ => return None,
+                    };
+                    Some(SkipRange {
+                        kind,
+                        start_ms: ticks_to_ms(segment.start_ticks),
+                        end_ms: ticks_to_ms(segment.end_ticks),
+                    })
+                })
+                .collect();
+            if !ranges.is_empty() {
+                return Ok(ranges);
+            }
+        }
+
+        let url = format!("{}/Users/{}/Items/{}", self.client.base_url, self.id, id);
+        let item: types::BaseItemDto = self
+            .client
+            .client
+            .get(&url)
+            .query(&[("Fields", "Chapters")])
+            .header(
+                "X-Emby-Authorization",
+                emby_authorization(Some(&self.token)),
+            )
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let chapters = item.chapters.unwrap_or_default();
+        let end_ticks = item.run_time_ticks.unwrap_or_default();
+        Ok(chapters
+            .iter()
+            .enumerate()
+            .filter_map(|(i, chapter)| {
+                let kind = chapter_skip_kind(chapter.name.as_deref()?)?;
+                let start = chapter.start_position_ticks.unwrap_or_default();
+                let end = chapters
+                    .get(i + 1)
+                    .and_then(|next| next.start_position_ticks)
+                    .unwrap_or(end_ticks);
+                (end > start).then(|| SkipRange {
+                    kind,
+                    start_ms: ticks_to_ms(start),
+                    end_ms: ticks_to_ms(end),
+                })
+            })
+            .collect())
+    }
+
+    /// Reports playback through a `/Sessions/Playing` call, positions are in the
+    /// content's own time.
+    pub async fn report_playback(
+        &self,
+        id: &str,
+        event: PlaybackEvent,
+        position_ms: u64,
+    ) -> Result<(), eyre::Error> {
+        let path = match event {
+            PlaybackEvent::Start => "/Sessions/Playing",
+            PlaybackEvent::Progress => "/Sessions/Playing/Progress",
+            PlaybackEvent::Stopped => "/Sessions/Playing/Stopped",
+        };
+        self.client
+            .client
+            .post(format!("{}{path}", self.client.base_url))
+            .json(&serde_json::json!({
+                "ItemId": id,
+                "PositionTicks": ms_to_ticks(position_ms),
+                "CanSeek": true,
+                "IsPaused": false,
+                "PlayMethod": "DirectStream",
+            }))
+            .header(
+                "X-Emby-Authorization",
+                emby_authorization(Some(&self.token)),
+            )
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Raw bytes of an image at a server relative path, like an item's `icon_url`.
+    pub async fn image(&self, path: &str) -> Result<Vec<u8>, eyre::Error> {
+        if !path.starts_with('/') {
+            eyre::bail!("Image path must be relative to the server: {path}");
+        }
+        let url = format!("{}{path}", self.client.base_url);
+        let bytes = self
+            .client
+            .client
+            .get(&url)
+            .header(
+                "X-Emby-Authorization",
+                emby_authorization(Some(&self.token)),
+            )
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        Ok(bytes.to_vec())
+    }
+
+    pub async fn user_data(&self, id: &str) -> Result<UserData, eyre::Error> {
+        let url = format!("{}/Users/{}/Items/{}", self.client.base_url, self.id, id);
+        let item: types::BaseItemDto = self
+            .client
+            .client
+            .get(&url)
+            .header(
+                "X-Emby-Authorization",
+                emby_authorization(Some(&self.token)),
+            )
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(item
+            .user_data
+            .map(|data| UserData {
+                favorite: data.is_favorite.unwrap_or_default(),
+                played: data.played.unwrap_or_default(),
+                position_ms: ticks_to_ms(data.playback_position_ticks.unwrap_or_default()),
+            })
+            .unwrap_or_default())
+    }
+
+    pub async fn set_favorite(&self, id: &str, favorite: bool) -> Result<(), eyre::Error> {
+        let url = format!(
+            "{}/Users/{}/FavoriteItems/{}",
+            self.client.base_url, self.id, id
+        );
+        let request = if favorite {
+            self.client.client.post(&url)
+        } else {
+            self.client.client.delete(&url)
+        };
+        request
+            .header(
+                "X-Emby-Authorization",
+                emby_authorization(Some(&self.token)),
+            )
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    pub async fn mark_played(&self, id: &str) -> Result<(), eyre::Error> {
+        let url = format!(
+            "{}/Users/{}/PlayedItems/{}",
+            self.client.base_url, self.id, id
+        );
+        self.client
+            .client
+            .post(&url)
+            .header(
+                "X-Emby-Authorization",
+                emby_authorization(Some(&self.token)),
+            )
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
     pub async fn transcode(
         &self,
         content: &Content,
         profile: serde_json::Value,
         preferred_media_streams: &[MediaStream],
+        media_source_id: Option<&str>,
     ) -> Result<TranscodeJob, eyre::Error> {
         let audio_index = preferred_media_streams
             .iter()
@@ -674,9 +1059,11 @@ impl JellyfinUser {
             .unwrap()
             .simple()
             .encode_lower(&mut buffer);
+        // Jellyfin uses the item id as the id of its primary media source.
+        let media_source_id = media_source_id.unwrap_or(media_source_id_from_uuid);
         let query = PlaybackQuery::new(
             &self.id,
-            media_source_id_from_uuid,
+            media_source_id,
             audio_index.copied(),
             subtitle_index.copied(),
         );
@@ -702,6 +1089,34 @@ impl JellyfinUser {
             .await?;
         tracing::info!(name: "jellyfin_resp", ?response);
 
+        // Direct play means the profile accepts the container and codecs as they are,
+        // so the original file can be fetched without Jellyfin spending any CPU on it.
+        // Direct stream only copies the codecs into another container, which is what
+        // the HLS path below ends up doing anyway.
+        if let Some(source) = response
+            .media_sources
+            .iter()
+            .find(|source| source.supports_direct_play.unwrap_or_default())
+        {
+            let container = source
+                .container
+                .as_deref()
+                .and_then(|c| c.split(',').next())
+                .unwrap_or("mkv");
+            return Ok(TranscodeJob::Direct(DirectFile {
+                url: format!(
+                    "{}/Videos/{}/stream.{}?static=true&mediaSourceId={}&api_key={}",
+                    self.client.base_url,
+                    &content.id,
+                    container,
+                    source.id.as_deref().unwrap_or(media_source_id),
+                    self.token
+                ),
+                container: container.to_string(),
+                size: source.size.and_then(|size| size.try_into().ok()),
+            }));
+        }
+
         let path = response
             .media_sources
             .into_iter()
@@ -749,6 +1164,12 @@ impl JellyfinUser {
                     "{}/videos/{}/{}",
                     self.client.base_url, &content.id, variant.uri
                 );
+                if let Some(map) = variant.map.as_mut() {
+                    map.uri = format!(
+                        "{}/videos/{}/{}",
+                        self.client.base_url, &content.id, map.uri
+                    );
+                }
             });
             media.insert(name, media_playlist);
         }
@@ -760,6 +1181,45 @@ impl JellyfinUser {
     }
 }
 
+#[derive(serde::Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct MediaSegments {
+    #[serde(default)]
+    items: Vec<MediaSegment>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct MediaSegment {
+    #[serde(rename = "Type")]
+    type_: String,
+    start_ticks: i64,
+    end_ticks: i64,
+}
+
+/// Jellyfin counts time in 100ns ticks.
+fn ticks_to_ms(ticks: i64) -> u64 {
+    u64::try_from(ticks / 10_000).unwrap_or_default()
+}
+
+fn ms_to_ticks(ms: u64) -> i64 {
+    i64::try_from(ms).unwrap_or(i64::MAX / 10_000) * 10_000
+}
+
+/// Skip kind for chapters named by intro detection plugins or the release itself.
+fn chapter_skip_kind(name: &str) -> Option<SkipKind> {
+    let name = name.trim().to_lowercase();
+    match name.as_str() {
+        "intro" | "opening" | "op" | "opening credits" | "theme song" => Some(SkipKind::Intro),
+        "outro" | "ending" | "ed" | "credits" | "end credits" | "closing credits" => {
+            Some(SkipKind::Outro)
+        }
+        "recap" | "previously" | "previously on" => Some(SkipKind::Recap),
+        "preview" | "next episode" | "next time" => Some(SkipKind::Preview),
+        _ => None,
+    }
+}
+
 #[derive(Serialize, Debug, Clone)]
 struct PlaybackQuery {
     #[serde(rename = "UserId")]
@@ -806,13 +1266,47 @@ impl From<BaseItemDto> for Item {
     }
 }
 
+/// A rating out of 10 in tenths, `None` when it's out of range.
+fn rating_tenths(rating: f32) -> Option<u8> {
+    let tenths = (rating * 10.0).round();
+    // Only kept when it fits, NaN doesn't.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    (0.0..=100.0).contains(&tenths).then_some(tenths as u8)
+}
+
+fn metadata(item: &BaseItemDto) -> Metadata {
+    Metadata {
+        sort_name: item.sort_name.clone().filter(|name| !name.is_empty()),
+        date_added: item.date_created,
+        runtime_ms: item.run_time_ticks.map(ticks_to_ms),
+        production_year: item.production_year,
+        genres: item.genres.clone().unwrap_or_default(),
+        tags: item.tags.clone().unwrap_or_default(),
+        official_rating: item.official_rating.clone(),
+        community_rating_tenths: item.community_rating.and_then(rating_tenths),
+        people: item
+            .people
+            .iter()
+            .flatten()
+            .filter_map(|person| {
+                Some(Person {
+                    name: person.name.clone()?,
+                    role: person.role.clone().filter(|role| !role.is_empty()),
+                    kind: person.type_.as_ref().map(ToString::to_string),
+                })
+            })
+            .collect(),
+    }
+}
+
 impl From<BaseItemDto> for Library {
     fn from(item: BaseItemDto) -> Self {
+        let metadata = metadata(&item);
         Library {
             id: item.id.expect("No id in ViewDto").to_string(),
             parent_id: item.parent_id.map(|id| id.to_string()),
             name: item.name.expect("No name in ViewDto"),
-            description: None,
+            description: item.overview,
             icon_url: Some(match item.type_.unwrap() {
                 BaseItemKind::Season | BaseItemKind::CollectionFolder | BaseItemKind::Folder => {
                     format!(
@@ -836,12 +1330,37 @@ impl From<BaseItemDto> for Library {
                     name: Some(x.to_string()),
                 },
             },
+            series_name: item.series_name,
+            metadata,
         }
     }
 }
 
+fn media_stream(stream: types::MediaStream) -> Option<MediaStream> {
+    match &stream.type_.expect("no media type") {
+        types::MediaStreamType::Video => Some(MediaStream::Video {
+            index: stream.index.expect("no index"),
+            codec: stream.codec.expect("no codec"),
+        }),
+        types::MediaStreamType::Audio => Some(MediaStream::Audio {
+            index: stream.index.expect("no index"),
+            codec: stream.codec.expect("no codec"),
+            language: stream.language,
+            name: stream.title,
+        }),
+        types::MediaStreamType::Subtitle => Some(MediaStream::Subtitle {
+            index: stream.index.expect("no index"),
+            codec: stream.codec.expect("no codec"),
+            language: stream.language,
+            name: stream.title,
+        }),
+        _ => None,
+    }
+}
+
 impl From<BaseItemDto> for Content {
     fn from(item: BaseItemDto) -> Self {
+        let metadata = metadata(&item);
         let id = item.id.expect("No id in BaseItemDto").to_string();
         let name = item.name.expect("No name in BaseItemDto");
         let description = item.overview;
@@ -851,34 +1370,61 @@ impl From<BaseItemDto> for Content {
             }
             _ => format!("/Items/{id}/Images/Primary?maxHeight=300&maxWidth=300&quality=90",),
         });
+        let format_3d = item.video3_d_format.map(|format| format.to_string());
+        let tags: Vec<&str> = item.tags.iter().flatten().map(String::as_str).collect();
+        let layout = VrLayout::detect(format_3d.as_deref(), &tags, item.path.as_deref());
+        let chapters = item
+            .chapters
+            .iter()
+            .flatten()
+            .map(|chapter| Chapter {
+                name: chapter.name.clone(),
+                start_ms: chapter
+                    .start_position_ticks
+                    .map(ticks_to_ms)
+                    .unwrap_or_default(),
+            })
+            .collect();
         Self {
             id,
             parent_id: item.parent_id.map(|id| id.to_string()),
             name,
             description,
             icon_url,
+            series_name: item.series_name,
+            season_name: item.season_name,
+            // Missing from listings that only ask for ids.
             media_streams: item
                 .media_streams
-                .expect("no media streams")
+                .unwrap_or_default()
                 .into_iter()
-                .filter_map(|stream| match &stream.type_.expect("no media type") {
-                    types::MediaStreamType::Video => Some(MediaStream::Video {
-                        index: stream.index.expect("no index"),
-                        codec: stream.codec.expect("no codec"),
-                    }),
-                    types::MediaStreamType::Audio => Some(MediaStream::Audio {
-                        index: stream.index.expect("no index"),
-                        codec: stream.codec.expect("no codec"),
-                        language: stream.language,
-                        name: stream.title,
-                    }),
-                    types::MediaStreamType::Subtitle => Some(MediaStream::Subtitle {
-                        index: stream.index.expect("no index"),
-                        codec: stream.codec.expect("no codec"),
-                        language: stream.language,
-                        name: stream.title,
-                    }),
-                    _ => None,
+                .filter_map(media_stream)
+                .collect(),
+            media_sources: item
+                .media_sources
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|source| {
+                    let resolution = source.media_streams.as_ref().and_then(|streams| {
+                        streams
+                            .iter()
+                            .filter(|s| matches!(s.type_, Some(types::MediaStreamType::Video)))
+                            .find_map(|s| Some(format!("{}x{}", s.width?, s.height?)))
+                    });
+                    Some(MediaSource {
+                        id: source.id?,
+                        name: source.name,
+                        container: source.container,
+                        size: source.size.and_then(|size| size.try_into().ok()),
+                        bitrate: source.bitrate.and_then(|rate| rate.try_into().ok()),
+                        resolution,
+                        media_streams: source
+                            .media_streams
+                            .unwrap_or_default()
+                            .into_iter()
+                            .filter_map(media_stream)
+                            .collect(),
+                    })
                 })
                 .collect(),
             kind: match item.type_.expect("no type") {
@@ -891,6 +1437,85 @@ impl From<BaseItemDto> for Content {
                     name: Some(x.to_string()),
                 },
             },
+            layout,
+            chapters,
+            airs: match (
+                item.airs_before_season_number,
+                item.airs_after_season_number,
+            ) {
+                (Some(season), _) => Some(Airs::Before {
+                    season,
+                    episode: item.airs_before_episode_number,
+                }),
+                (None, Some(season)) => Some(Airs::After { season }),
+                (None, None) => None,
+            },
+            metadata,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_item_metadata() {
+        let item: BaseItemDto = serde_json::from_value(serde_json::json!({
+            "Id": "8f5a9d3c2b1e4f6a9c7d0e1f2a3b4c5d",
+            "Name": "The Long Way Round",
+            "SortName": "long way round",
+            "DateCreated": "2024-08-07T10:12:03.4521877Z",
+            "RunTimeTicks": 13_200_000_000_i64,
+            "ProductionYear": 2024,
+            "Genres": ["Drama", "Sci-Fi"],
+            "Tags": ["vr"],
+            "OfficialRating": "TV-14",
+            "CommunityRating": 7.26,
+            "People": [
+                {"Name": "Ada Moon", "Role": "Captain", "Type": "Actor"},
+                {"Name": "Lin Reyes", "Role": "", "Type": "Director"},
+                {"Role": "Nameless"}
+            ]
+        }))
+        .unwrap();
+        let metadata = metadata(&item);
+        assert_eq!(metadata.sort_name.as_deref(), Some("long way round"));
+        assert_eq!(
+            metadata.date_added.map(|date| date.timestamp()),
+            Some(1_723_025_523)
+        );
+        assert_eq!(metadata.runtime_ms, Some(1_320_000));
+        assert_eq!(metadata.production_year, Some(2024));
+        assert_eq!(metadata.genres, ["Drama", "Sci-Fi"]);
+        assert_eq!(metadata.tags, ["vr"]);
+        assert_eq!(metadata.official_rating.as_deref(), Some("TV-14"));
+        assert_eq!(metadata.community_rating_tenths, Some(73));
+        assert_eq!(
+            metadata.people,
+            [
+                Person {
+                    name: "Ada Moon".to_string(),
+                    role: Some("Captain".to_string()),
+                    kind: Some("Actor".to_string()),
+                },
+                Person {
+                    name: "Lin Reyes".to_string(),
+                    role: None,
+                    kind: Some("Director".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_out_missing_metadata() {
+        let item: BaseItemDto = serde_json::from_value(serde_json::json!({
+            "Id": "8f5a9d3c2b1e4f6a9c7d0e1f2a3b4c5d",
+            "SortName": "",
+            "CommunityRating": 11.5
+        }))
+        .unwrap();
+        assert_eq!(metadata(&item), Metadata::default());
+    }
+}
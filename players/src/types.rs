@@ -1,8 +1,6 @@
-use std::{
-    collections::HashMap,
-    hash::{Hash, Hasher},
-};
+use std::collections::HashMap;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -13,6 +11,11 @@ pub struct Library {
     pub description: Option<String>,
     pub icon_url: Option<String>,
     pub kind: LibraryKind,
+    /// Show a season belongs to.
+    #[serde(default)]
+    pub series_name: Option<String>,
+    #[serde(flatten, default)]
+    pub metadata: Metadata,
 }
 
 impl Library {
@@ -25,6 +28,8 @@ impl Library {
             description: None,
             icon_url: None,
             kind: LibraryKind::Collection,
+            series_name: None,
+            metadata: Metadata::default(),
         }
     }
 }
@@ -36,8 +41,200 @@ pub struct Content {
     pub name: String,
     pub description: Option<String>,
     pub icon_url: Option<String>,
+    /// Show an episode belongs to.
+    #[serde(default)]
+    pub series_name: Option<String>,
+    /// Season an episode belongs to, like `Season 1` or `Specials`.
+    #[serde(default)]
+    pub season_name: Option<String>,
     pub media_streams: Vec<MediaStream>,
     pub kind: ContentKind,
+    /// Versions of the item (4K/1080p cuts, extras, ...), empty when the provider
+    /// doesn't report them.
+    #[serde(default)]
+    pub media_sources: Vec<MediaSource>,
+    /// Layout detected from the provider's metadata.
+    #[serde(default)]
+    pub layout: VrLayout,
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
+    /// Where a special aired among the regular episodes, when the provider knows.
+    #[serde(default)]
+    pub airs: Option<Airs>,
+    #[serde(flatten, default)]
+    pub metadata: Metadata,
+}
+
+/// Placement of a special (season 0 episode) in a show's airing order.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum Airs {
+    /// Before an episode of a season, or before the whole season.
+    Before { season: i32, episode: Option<i32> },
+    /// After the last episode of a season.
+    After { season: i32 },
+}
+
+/// Descriptive details of contents and libraries, whatever the provider knows.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// Name to sort by, like `matrix, the`.
+    #[serde(default)]
+    pub sort_name: Option<String>,
+    /// When the provider first saw the item.
+    #[serde(default)]
+    pub date_added: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub runtime_ms: Option<u64>,
+    #[serde(default)]
+    pub production_year: Option<i32>,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Age rating, like `TV-14` or `PG-13`.
+    #[serde(default)]
+    pub official_rating: Option<String>,
+    /// Out of 10, in tenths, so `73` is 7.3.
+    #[serde(default)]
+    pub community_rating_tenths: Option<u8>,
+    #[serde(default)]
+    pub people: Vec<Person>,
+}
+
+/// Someone who worked on a content, like an actor or director.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Person {
+    pub name: String,
+    /// Character played, for actors.
+    pub role: Option<String>,
+    /// `Actor`, `Director`, `Writer`, ...
+    pub kind: Option<String>,
+}
+
+/// A named point in a content.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub name: Option<String>,
+    pub start_ms: u64,
+}
+
+/// How a video maps onto the viewer's field of view.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Projection {
+    #[default]
+    Flat,
+    /// 180° equirectangular.
+    Dome,
+    /// 360° equirectangular.
+    Sphere,
+    Fisheye,
+}
+
+/// How the two eyes are packed into a frame.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Stereo {
+    #[default]
+    Mono,
+    /// Side by side, left eye first.
+    Sbs,
+    /// Top and bottom, left eye on top.
+    Tb,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VrLayout {
+    pub projection: Projection,
+    pub stereo: Stereo,
+}
+
+impl VrLayout {
+    /// Guesses the layout from the provider's 3D format (e.g. `HalfSideBySide`),
+    /// tags and the file name following the usual `_180_sbs`, `_LR` or `_360_TB`
+    /// conventions. Only the file stem of `path` counts, folders like `/vr/` say
+    /// nothing about a single file. A bare `180` or `360` is only taken for the
+    /// projection next to another VR hint, titles have numbers too.
+    #[must_use]
+    pub fn detect(format_3d: Option<&str>, tags: &[&str], path: Option<&str>) -> Self {
+        let mut layout = Self::default();
+        match format_3d {
+            Some("HalfSideBySide" | "FullSideBySide") => layout.stereo = Stereo::Sbs,
+            Some("HalfTopAndBottom" | "FullTopAndBottom") => layout.stereo = Stereo::Tb,
+            _ => {}
+        }
+        let stem = path.map(|path| {
+            let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+            name.rsplit_once('.').map_or(name, |(stem, _)| stem)
+        });
+        let mut vr = false;
+        let mut degrees = None;
+        for token in tags
+            .iter()
+            .copied()
+            .chain(stem)
+            .flat_map(|hint| hint.split(|c: char| !c.is_ascii_alphanumeric()))
+            .map(str::to_ascii_lowercase)
+        {
+            match token.as_str() {
+                "sbs" | "lr" | "3dh" => layout.stereo = Stereo::Sbs,
+                "tb" | "ou" | "bt" | "3dv" => layout.stereo = Stereo::Tb,
+                "180" => degrees = Some(Projection::Dome),
+                "360" => degrees = Some(Projection::Sphere),
+                "vr180" => layout.projection = Projection::Dome,
+                "vr360" => layout.projection = Projection::Sphere,
+                "fisheye" | "fisheye190" | "mkx200" | "mkx220" | "rf52" | "vrca220" => {
+                    layout.projection = Projection::Fisheye;
+                }
+                "vr" => vr = true,
+                _ => {}
+            }
+        }
+        if let Some(degrees) = degrees {
+            if layout.projection == Projection::Flat && (vr || layout.stereo != Stereo::Mono) {
+                layout.projection = degrees;
+            }
+        }
+        // Plain "VR" releases are almost always 180° side by side.
+        if vr && layout.projection == Projection::Flat {
+            layout.projection = Projection::Dome;
+            if layout.stereo == Stereo::Mono {
+                layout.stereo = Stereo::Sbs;
+            }
+        }
+        layout
+    }
+
+    /// Short description for badges, e.g. `180° SBS`.
+    #[must_use]
+    pub fn label(&self) -> String {
+        let projection = match self.projection {
+            Projection::Flat => "Flat",
+            Projection::Dome => "180°",
+            Projection::Sphere => "360°",
+            Projection::Fisheye => "Fisheye",
+        };
+        match self.stereo {
+            Stereo::Mono => projection.to_string(),
+            Stereo::Sbs => format!("{projection} SBS"),
+            Stereo::Tb => format!("{projection} TB"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MediaSource {
+    pub id: String,
+    pub name: Option<String>,
+    pub container: Option<String>,
+    pub size: Option<u64>,
+    pub bitrate: Option<u64>,
+    /// `WIDTHxHEIGHT` of the first video stream.
+    pub resolution: Option<String>,
+    /// Streams of this version, their indexes don't carry over to other ones.
+    #[serde(default)]
+    pub media_streams: Vec<MediaStream>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -86,39 +283,352 @@ pub enum Item {
     Content(Content),
 }
 
+/// A slice of a library's items.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ItemsPage {
+    pub items: Vec<Item>,
+    /// Items in the whole library, when the provider reports it.
+    pub total: Option<usize>,
+}
+
+/// Contents a provider suggests to a user.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Shelf {
+    /// Started but not finished.
+    Resume,
+    /// The episode after the last one watched, per show.
+    NextUp,
+    /// Recently added.
+    Latest,
+}
+
+impl Shelf {
+    pub const ALL: [Self; 3] = [Self::Resume, Self::NextUp, Self::Latest];
+}
+
+/// Narrows down a page of a library's items, for syncing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ItemsQuery {
+    /// Only items saved since then, where the provider can tell.
+    pub changed_since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Just enough of each item to tell which ones still exist, without metadata.
+    pub ids_only: bool,
+}
+
+/// A stretch of a content viewers usually want to skip.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SkipRange {
+    pub kind: SkipKind,
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum SkipKind {
+    Intro,
+    Outro,
+    Recap,
+    Preview,
+    Commercial,
+}
+
+impl std::str::FromStr for SkipKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "intro" => Ok(Self::Intro),
+            "outro" => Ok(Self::Outro),
+            "recap" => Ok(Self::Recap),
+            "preview" => Ok(Self::Preview),
+            "commercial" => Ok(Self::Commercial),
+            _ => Err(format!("Unknown skip kind: {s}")),
+        }
+    }
+}
+
+impl std::fmt::Display for SkipKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Intro => "intro",
+            Self::Outro => "outro",
+            Self::Recap => "recap",
+            Self::Preview => "preview",
+            Self::Commercial => "commercial",
+        })
+    }
+}
+
+/// What the provider knows about a user's relation to a content.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserData {
+    pub favorite: bool,
+    pub played: bool,
+    pub position_ms: u64,
+}
+
+/// Playback state reported back to the provider, so it knows what was watched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackEvent {
+    Start,
+    Progress,
+    Stopped,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct M3U8Playlist {
     pub main: m3u8_rs::MasterPlaylist,
     pub media: HashMap<String, m3u8_rs::MediaPlaylist>,
 }
 
+/// The original file, for sources the profile can already play as-is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectFile {
+    /// Fully qualified URL, including whatever auth the provider needs.
+    pub url: String,
+    pub container: String,
+    pub size: Option<u64>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TranscodeJob {
     M3U8(M3U8Playlist),
+    Direct(DirectFile),
 }
 
+/// Room for the episodes of a season in [`Content::sort_key`].
+const SEASON_SLOTS: i64 = 10_000_000;
+/// Room for an episode and the specials airing before it.
+const EPISODE_SLOTS: i64 = 1000;
+
 impl Content {
-    /// Generate a sort key for the content.
+    /// Streams of the media source `media_source_id`, those of the default one
+    /// when it's `None` or the provider didn't list the source's streams.
+    #[must_use]
+    pub fn media_streams_of(&self, media_source_id: Option<&str>) -> &[MediaStream] {
+        media_source_id
+            .and_then(|id| self.media_sources.iter().find(|source| source.id == id))
+            .filter(|source| !source.media_streams.is_empty())
+            .map_or(&self.media_streams, |source| &source.media_streams)
+    }
+
+    /// Position in the show's airing order, 0 for anything but episodes.
+    ///
+    /// Specials go right before the episode or season they aired before, or
+    /// after the season they aired after. Those the provider didn't place stay
+    /// in season 0, like episodes numbered without seasons.
+    ///
+    /// The `sort_orders` migration backfills cached rows the same way, keep it
+    /// in sync.
+    #[must_use]
     pub fn sort_key(&self) -> i64 {
-        let mut hasher = std::hash::DefaultHasher::new();
-
-        // self.parent_id.hash(&mut hasher);
-        match self.kind {
-            ContentKind::Episode { season, episode } => {
-                let season = season.unwrap_or(0);
-                return (season * 10000 + episode).try_into().unwrap();
-                //.hash(&mut hasher);
-            }
-            ContentKind::Movie | ContentKind::Other { .. } => {
-                self.parent_id.hash(&mut hasher);
-                self.name.hash(&mut hasher);
-            }
+        let ContentKind::Episode { season, episode } = self.kind else {
+            return 0;
         };
-
-        let finish = hasher.finish();
-        match finish.try_into() {
-            Ok(sort_key) => sort_key,
-            Err(_) => 0i64.wrapping_sub_unsigned(finish),
+        let episode = i64::from(episode.clamp(0, 9999));
+        let slot = |season: i32, position: i64, offset: i64| {
+            i64::from(season.max(0)) * SEASON_SLOTS + position * EPISODE_SLOTS + offset
+        };
+        // Specials keep their own order among those sharing a placement.
+        let special = episode.min(EPISODE_SLOTS / 2 - 1);
+        match self.airs {
+            Some(Airs::Before {
+                season,
+                episode: before,
+            }) => slot(
+                season,
+                before.map_or(0, |before| i64::from(before.clamp(0, 9999))),
+                special,
+            ),
+            Some(Airs::After { season }) => slot(season + 1, 0, special) - EPISODE_SLOTS / 2,
+            None => slot(season.unwrap_or(0), episode, EPISODE_SLOTS / 2),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_vr_layouts() {
+        let detect = |format: Option<&str>, tags: &[&str], path: &str| {
+            let layout = VrLayout::detect(format, tags, Some(path));
+            (layout.projection, layout.stereo)
+        };
+        assert_eq!(
+            detect(None, &[], "/media/Scene_180_sbs.mp4"),
+            (Projection::Dome, Stereo::Sbs)
+        );
+        assert_eq!(
+            detect(None, &[], "D:\\media\\Scene_360_TB.mkv"),
+            (Projection::Sphere, Stereo::Tb)
+        );
+        assert_eq!(
+            detect(None, &[], "/media/Scene_MKX200_LR.mp4"),
+            (Projection::Fisheye, Stereo::Sbs)
+        );
+        assert_eq!(
+            detect(None, &["VR"], "/media/Scene.mp4"),
+            (Projection::Dome, Stereo::Sbs)
+        );
+        assert_eq!(
+            detect(None, &["VR"], "/media/Scene_360.mp4"),
+            (Projection::Sphere, Stereo::Mono)
+        );
+        assert_eq!(
+            detect(Some("HalfSideBySide"), &[], "/movies/Avatar (2009).mkv"),
+            (Projection::Flat, Stereo::Sbs)
+        );
+        assert_eq!(
+            detect(None, &[], "/movies/Tablet.mkv"),
+            (Projection::Flat, Stereo::Mono)
+        );
+    }
+
+    #[test]
+    fn ignores_folders_and_numbers_in_titles() {
+        let detect = |path: &str| {
+            let layout = VrLayout::detect(None, &[], Some(path));
+            (layout.projection, layout.stereo)
+        };
+        assert_eq!(
+            detect("/media/vr/Behind the Scenes.mp4"),
+            (Projection::Flat, Stereo::Mono)
+        );
+        assert_eq!(
+            detect("/media/vr.sbs/Behind the Scenes.mp4"),
+            (Projection::Flat, Stereo::Mono)
+        );
+        assert_eq!(
+            detect("/movies/Xbox 360 Story.mkv"),
+            (Projection::Flat, Stereo::Mono)
+        );
+        assert_eq!(
+            detect("/movies/Top 180 Jumps.mkv"),
+            (Projection::Flat, Stereo::Mono)
+        );
+    }
+
+    #[test]
+    fn reads_streams_of_the_chosen_media_source() {
+        let audio = |index: i32, language: &str| MediaStream::Audio {
+            index,
+            codec: "aac".to_string(),
+            language: Some(language.to_string()),
+            name: None,
+        };
+        let source = |id: &str, media_streams: Vec<MediaStream>| MediaSource {
+            id: id.to_string(),
+            name: None,
+            container: None,
+            size: None,
+            bitrate: None,
+            resolution: None,
+            media_streams,
+        };
+        let content = Content {
+            id: "movie".to_string(),
+            parent_id: None,
+            name: String::new(),
+            description: None,
+            icon_url: None,
+            series_name: None,
+            season_name: None,
+            media_streams: vec![audio(1, "eng")],
+            kind: ContentKind::Movie,
+            media_sources: vec![
+                source("movie", vec![audio(1, "eng")]),
+                source("directors-cut", vec![audio(1, "jpn"), audio(2, "eng")]),
+                source("unlisted", vec![]),
+            ],
+            layout: VrLayout::default(),
+            chapters: vec![],
+            airs: None,
+            metadata: Metadata::default(),
+        };
+
+        assert_eq!(content.media_streams_of(None), [audio(1, "eng")]);
+        assert_eq!(
+            content.media_streams_of(Some("directors-cut")),
+            [audio(1, "jpn"), audio(2, "eng")]
+        );
+        assert_eq!(
+            content.media_streams_of(Some("unlisted")),
+            [audio(1, "eng")]
+        );
+        assert_eq!(content.media_streams_of(Some("gone")), [audio(1, "eng")]);
+    }
+
+    #[test]
+    fn sorts_specials_where_they_aired() {
+        let episode = |season: Option<i32>, episode: i32, airs: Option<Airs>| {
+            Content {
+                id: String::new(),
+                parent_id: None,
+                name: String::new(),
+                description: None,
+                icon_url: None,
+                series_name: None,
+                season_name: None,
+                media_streams: vec![],
+                kind: ContentKind::Episode { season, episode },
+                media_sources: vec![],
+                layout: VrLayout::default(),
+                chapters: vec![],
+                airs,
+                metadata: Metadata::default(),
+            }
+            .sort_key()
+        };
+        let mut order = [
+            ("s2e1", episode(Some(2), 1, None)),
+            ("s1e10", episode(Some(1), 10, None)),
+            ("s1e2", episode(Some(1), 2, None)),
+            (
+                "special after s1",
+                episode(Some(0), 1, Some(Airs::After { season: 1 })),
+            ),
+            (
+                "special before s1e2",
+                episode(
+                    Some(0),
+                    2,
+                    Some(Airs::Before {
+                        season: 1,
+                        episode: Some(2),
+                    }),
+                ),
+            ),
+            (
+                "special before s2",
+                episode(
+                    Some(0),
+                    3,
+                    Some(Airs::Before {
+                        season: 2,
+                        episode: None,
+                    }),
+                ),
+            ),
+            ("unplaced special", episode(Some(0), 4, None)),
+            ("s1e1", episode(Some(1), 1, None)),
+        ];
+        order.sort_by_key(|(_, key)| *key);
+        assert_eq!(
+            order.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+            [
+                "unplaced special",
+                "s1e1",
+                "special before s1e2",
+                "s1e2",
+                "s1e10",
+                "special after s1",
+                "special before s2",
+                "s2e1",
+            ]
+        );
+    }
+}
@@ -114,6 +114,9 @@ impl Hooks for App {
         Ok(vec![
             Box::new(initializers::view_engine::ViewEngineInitializer),
             Box::new(initializers::media_provider::MediaProviderInitializer),
+            Box::new(initializers::library_sync::LibrarySyncInitializer),
+            Box::new(initializers::playback_sessions::PlaybackSessionsInitializer),
+            Box::new(initializers::image_cache::ImageCacheInitializer),
             Box::new(initializers::layers::LayersInitializer),
         ])
     }
@@ -126,7 +129,13 @@ impl Hooks for App {
             ))
             .add_route(controllers::user::routes())
             .add_route(controllers::dashboard::routes())
+            .add_route(controllers::heresphere::routes())
+            .add_route(controllers::deovr::routes())
             .add_route(controllers::playlist::routes())
+            .add_route(controllers::short_links::routes())
+            .add_route(controllers::subscriptions::routes())
+            .add_route(controllers::webhooks::routes())
+            .add_route(controllers::images::routes())
     }
 
     fn connect_workers<'a>(p: &'a mut Processor, ctx: &'a AppContext) {
@@ -135,6 +144,8 @@ impl Hooks for App {
 
     fn register_tasks(tasks: &mut Tasks) {
         tasks.register(tasks::seed::SeedData);
+        tasks.register(tasks::shared_gc::SharedGc);
+        tasks.register(tasks::library_sync::LibrarySync);
     }
 
     async fn truncate(db: &DatabaseConnection) -> Result<()> {
@@ -0,0 +1,167 @@
+//! Clips are playlists covering part of a finished HLS download. Whole segments are
+//! referenced in place, only the first and last one get trimmed into the clip's dir.
+
+use std::path::Path;
+
+use eyre::{bail, Context};
+use m3u8_rs::{MediaPlaylist, MediaPlaylistType, MediaSegment};
+
+use super::remux;
+
+/// Segments closer than this to the requested edge aren't worth trimming.
+const TRIM_TOLERANCE_SECS: f64 = 0.05;
+
+/// Parses `[[hh:]mm:]ss[.fff]` into milliseconds.
+#[must_use]
+pub fn parse_timestamp(timestamp: &str) -> Option<u64> {
+    let mut total = 0.0_f64;
+    for part in timestamp.trim().split(':') {
+        let value: f64 = part.parse().ok()?;
+        if !value.is_finite() || value < 0.0 {
+            return None;
+        }
+        total = total * 60.0 + value;
+    }
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    Some((total * 1000.0).round() as u64)
+}
+
+/// Writes the playlists of a clip covering `start..end` seconds of the download in
+/// `download_dir` to `clip_dir`. `uri_prefix` leads from `clip_dir` to `download_dir`.
+/// Returns the clip's duration.
+///
+/// # Errors
+///
+/// When the download's playlists can't be read, the range is outside of the
+/// content or the clip can't be written.
+pub fn build(
+    download_dir: &Path,
+    clip_dir: &Path,
+    uri_prefix: &str,
+    start: f64,
+    end: f64,
+) -> eyre::Result<f64> {
+    let master = std::fs::read(download_dir.join("main.m3u8")).wrap_err("reading main.m3u8")?;
+    let mut master = m3u8_rs::parse_master_playlist_res(&master)
+        .map_err(|e| eyre::eyre!("Failed to parse master playlist: {e}"))?;
+    master.variants.retain(|variant| !variant.is_i_frame);
+    if master.variants.is_empty() {
+        bail!("Master playlist has no variants");
+    }
+    std::fs::create_dir_all(clip_dir).wrap_err("creating clip dir")?;
+
+    let mut duration = None;
+    for variant in &master.variants {
+        let media = std::fs::read(download_dir.join(&variant.uri))
+            .wrap_err_with(|| format!("reading {}", variant.uri))?;
+        let media = m3u8_rs::parse_media_playlist_res(&media)
+            .map_err(|e| eyre::eyre!("Failed to parse media playlist: {e}"))?;
+        let name = variant.uri.trim_end_matches(".m3u8");
+        let clip = clip_media(download_dir, clip_dir, name, uri_prefix, &media, start, end)?;
+        duration.get_or_insert(clip.segments.iter().map(|s| f64::from(s.duration)).sum());
+
+        let mut out = vec![];
+        clip.write_to(&mut out)?;
+        std::fs::write(clip_dir.join(&variant.uri), out)
+            .wrap_err_with(|| format!("writing {}", variant.uri))?;
+    }
+
+    let mut out = vec![];
+    master.write_to(&mut out)?;
+    std::fs::write(clip_dir.join("main.m3u8"), out).wrap_err("writing main.m3u8")?;
+    Ok(duration.unwrap_or_default())
+}
+
+fn clip_media(
+    download_dir: &Path,
+    clip_dir: &Path,
+    name: &str,
+    uri_prefix: &str,
+    media: &MediaPlaylist,
+    start: f64,
+    end: f64,
+) -> eyre::Result<MediaPlaylist> {
+    let mut segments: Vec<MediaSegment> = vec![];
+    let mut map = None;
+    let mut position = 0.0_f64;
+    for segment in &media.segments {
+        let (from, duration) = (position, f64::from(segment.duration));
+        position += duration;
+        if segment.map.is_some() {
+            map.clone_from(&segment.map);
+        }
+        if from + duration <= start || from >= end {
+            continue;
+        }
+
+        let mut clipped = segment.clone();
+        clipped.uri = format!("{uri_prefix}/{}", segment.uri);
+        clipped.program_date_time = None;
+        clipped.daterange = None;
+        if segments.is_empty() {
+            clipped.discontinuity = false;
+            clipped.map.clone_from(&map);
+        }
+        if let Some(map) = clipped.map.as_mut() {
+            map.uri = format!("{uri_prefix}/{}", map.uri);
+        }
+
+        let trim_from = (start - from > TRIM_TOLERANCE_SECS).then_some(start - from);
+        let trim_to = (from + duration - end > TRIM_TOLERANCE_SECS).then_some(end - from);
+        if trim_from.is_some() || trim_to.is_some() {
+            let data = std::fs::read(download_dir.join(&segment.uri))
+                .wrap_err_with(|| format!("reading segment {}", segment.uri))?;
+            // Only MPEG-TS can be cut, fMP4 segments are kept whole.
+            if data.first() == Some(&0x47) {
+                if let Some(trimmed) = remux::trim_ts(&data, trim_from, trim_to)
+                    .wrap_err_with(|| format!("trimming segment {}", segment.uri))?
+                {
+                    let uri = format!("{name}/{}.ts", segments.len());
+                    std::fs::create_dir_all(clip_dir.join(name))?;
+                    std::fs::write(clip_dir.join(&uri), &trimmed.data)
+                        .wrap_err_with(|| format!("writing {uri}"))?;
+                    let kept_until = trimmed.end_secs.unwrap_or(duration);
+                    #[allow(clippy::cast_possible_truncation)]
+                    {
+                        clipped.duration = (kept_until - trimmed.start_secs) as f32;
+                    }
+                    clipped.uri = uri;
+                }
+            }
+        }
+        segments.push(clipped);
+    }
+    if segments.is_empty() {
+        bail!("The clip is outside of the content");
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let target_duration = segments
+        .iter()
+        .map(|s| s.duration.ceil() as u64)
+        .max()
+        .unwrap_or_default();
+    Ok(MediaPlaylist {
+        version: media.version,
+        target_duration,
+        segments,
+        end_list: true,
+        playlist_type: Some(MediaPlaylistType::Vod),
+        independent_segments: media.independent_segments,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_timestamp("90"), Some(90_000));
+        assert_eq!(parse_timestamp("1:30.5"), Some(90_500));
+        assert_eq!(parse_timestamp("1:02:03"), Some(3_723_000));
+        assert_eq!(parse_timestamp("-3"), None);
+        assert_eq!(parse_timestamp("1:xx"), None);
+    }
+}
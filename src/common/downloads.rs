@@ -0,0 +1,143 @@
+//! Starting downloads of contents, for the transcode form and subscriptions alike.
+
+use loco_rs::{model::ModelError, Error, Result};
+use players::types::{Content, MediaStream};
+use sea_orm::DatabaseConnection;
+
+use crate::{
+    common::variants::Variant,
+    initializers::media_provider::ConnectedMediaProvider,
+    models::{
+        _entities::{contents, player_connections, shared_transcodes},
+        shared_transcodes::Lease,
+    },
+    workers::downloader::DownloadWorkerArgs,
+};
+
+/// What to download of a content. Stream indexes below `0` leave the choice to
+/// the provider, like an empty `media_source_id` or `profile` do.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    pub audio_index: i32,
+    pub subtitle_index: i32,
+    pub media_source_id: Option<String>,
+    pub profile: Option<String>,
+}
+
+/// Index of the first audio or subtitle stream of `language`, `-1` to leave it
+/// to the provider.
+#[must_use]
+pub fn stream_index(content: &Content, language: Option<&str>, audio: bool) -> i32 {
+    let Some(language) = language else {
+        return -1;
+    };
+    content
+        .media_streams
+        .iter()
+        .find_map(|stream| match stream {
+            MediaStream::Audio {
+                index,
+                language: Some(l),
+                ..
+            } if audio && l.eq_ignore_ascii_case(language) => Some(*index),
+            MediaStream::Subtitle {
+                index,
+                language: Some(l),
+                ..
+            } if !audio && l.eq_ignore_ascii_case(language) => Some(*index),
+            _ => None,
+        })
+        .unwrap_or(-1)
+}
+
+/// Language of the audio or subtitle stream at `index` among `streams`, which
+/// should be those of the media source the index was picked from, see
+/// [`Content::media_streams_of`].
+#[must_use]
+pub fn stream_language(streams: &[MediaStream], index: Option<i32>, audio: bool) -> Option<String> {
+    let index = index?;
+    streams.iter().find_map(|stream| match stream {
+        MediaStream::Audio {
+            index: i, language, ..
+        } if audio && *i == index => language.clone(),
+        MediaStream::Subtitle {
+            index: i, language, ..
+        } if !audio && *i == index => language.clone(),
+        _ => None,
+    })
+}
+
+/// Records a download of `content` and returns the job doing it, `None` when
+/// another download of the same variant already transcodes it.
+///
+/// # Errors
+///
+/// When the media source or profile is unknown or the database can't be reached.
+pub async fn prepare(
+    db: &DatabaseConnection,
+    connection: &player_connections::Model,
+    provider: &ConnectedMediaProvider,
+    content: &Content,
+    selection: &Selection,
+) -> Result<Option<DownloadWorkerArgs>> {
+    let media_source_id = selection
+        .media_source_id
+        .clone()
+        .filter(|id| !id.is_empty());
+    if let Some(id) = &media_source_id {
+        if !content.media_sources.iter().any(|source| &source.id == id) {
+            return Err(Error::BadRequest("Unknown media source".to_string()));
+        }
+    }
+    let mut streams = vec![];
+    for stream in content.media_streams_of(media_source_id.as_deref()) {
+        match stream {
+            MediaStream::Audio { index, .. } if *index == selection.audio_index => {
+                streams.push(stream.clone());
+            }
+            MediaStream::Subtitle { index, .. } if *index == selection.subtitle_index => {
+                streams.push(stream.clone());
+            }
+            _ => {}
+        }
+    }
+    let profile = provider.resolve_profile(
+        selection
+            .profile
+            .as_deref()
+            .filter(|profile| !profile.is_empty()),
+    )?;
+    let variant = Variant {
+        profile: Some(profile.clone()),
+        media_source_id: media_source_id.clone(),
+        audio_index: (selection.audio_index >= 0).then_some(selection.audio_index),
+        subtitle_index: (selection.subtitle_index >= 0).then_some(selection.subtitle_index),
+    };
+    // Search hits and the like may not be cached yet.
+    match contents::Model::by_connection_and_id(db, connection.id, &content.id).await {
+        Ok(_) => {}
+        Err(ModelError::EntityNotFound) => {
+            contents::Model::upsert_cache_data(db, connection.id, &[content], None).await?;
+        }
+        Err(e) => return Err(e.into()),
+    }
+    let (_, download) =
+        contents::Model::start_download(db, connection.id, &content.id, &variant).await?;
+    let (shared, lease) =
+        shared_transcodes::Model::acquire(db, &connection.media_provider_id, &download).await?;
+    if lease != Lease::Owner {
+        tracing::info!(key = shared.key, ?lease, "Reusing shared transcode");
+        return Ok(None);
+    }
+    Ok(Some(DownloadWorkerArgs {
+        user_id: connection.user_id,
+        connection_id: connection.id,
+        content_download_id: download.id,
+        profile: Some(profile),
+        content: content.clone(),
+        preferred_mediastreams: streams,
+        media_source_id,
+        variant_id: Some(variant.id()),
+        shared_key: Some(shared.key),
+    }))
+}
@@ -0,0 +1,317 @@
+//! Provider images resized and re-encoded, so browsers and players only ever
+//! talk to us and never learn where the provider lives. Photos are re-encoded
+//! to JPEG, images with transparency to lossless WebP.
+//!
+//! Photos aren't WebP on purpose: the pure Rust `image` crate only encodes
+//! lossless WebP, which comes out larger than the JPEGs providers serve.
+//!
+//! Resized images are kept in the settings' `image_cache_dir`, one file per
+//! connection, item and width, and fetched again once older than [`MAX_AGE`].
+//! Widths are snapped to [`WIDTHS`] so a handful of files cover every request.
+//! Their `ETag` is a hash of their bytes.
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use image::{
+    codecs::{jpeg::JpegEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    DynamicImage,
+};
+use loco_rs::{Error, Result};
+use sha2::{Digest, Sha256};
+
+use super::settings::SETTINGS;
+use crate::initializers::media_provider::ConnectedMediaProvider;
+
+/// Widths images are served at, requested widths are rounded up to the next.
+pub const WIDTHS: [u32; 4] = [200, 400, 640, 1280];
+/// Width of images when the request doesn't pick one.
+pub const DEFAULT_WIDTH: u32 = 400;
+/// Width of catalog thumbnails, players show them bigger than our cards.
+pub const CATALOG_WIDTH: u32 = 640;
+/// How long a resized image is served before it's fetched again, so artwork
+/// changed on the provider shows up eventually. Older files are removed.
+pub const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// Quality of re-encoded photos.
+const JPEG_QUALITY: u8 = 85;
+
+/// A resized image ready to be served.
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub bytes: Vec<u8>,
+    pub etag: String,
+    pub content_type: &'static str,
+}
+
+impl Image {
+    fn new(bytes: Vec<u8>) -> Self {
+        let etag = format!("\"{}\"", hash(&bytes));
+        let content_type = image::guess_format(&bytes)
+            .map_or("application/octet-stream", |format| format.to_mime_type());
+        Self {
+            bytes,
+            etag,
+            content_type,
+        }
+    }
+}
+
+fn hash(data: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(&Sha256::digest(data)[..18])
+}
+
+/// The width to serve for a requested one, the smallest of [`WIDTHS`] at least
+/// as wide or the widest.
+#[must_use]
+pub fn snap_width(width: Option<u32>) -> u32 {
+    let width = width.unwrap_or(DEFAULT_WIDTH);
+    WIDTHS
+        .into_iter()
+        .find(|&snapped| snapped >= width)
+        .unwrap_or(WIDTHS[WIDTHS.len() - 1])
+}
+
+fn cache_dir() -> Result<&'static Path> {
+    SETTINGS
+        .get()
+        .map(|settings| settings.image_cache_dir.as_path())
+        .ok_or_else(|| Error::Message("Settings not initialized".to_string()))
+}
+
+/// Where the image of a connection's item is cached at `width`.
+#[must_use]
+pub fn cache_file(cache_dir: &Path, connection_id: i32, item_id: &str, width: u32) -> PathBuf {
+    cache_dir
+        .join(connection_id.to_string())
+        .join(format!("{}-{width}", hash(item_id.as_bytes())))
+}
+
+/// Scales an image down to `width`, keeping its aspect ratio, and encodes it to
+/// JPEG, or lossless WebP when it has transparency. Narrower images keep their
+/// size.
+///
+/// # Errors
+///
+/// When the image can't be decoded or encoded.
+pub fn encode(data: &[u8], width: u32) -> image::ImageResult<Vec<u8>> {
+    let mut image = image::load_from_memory(data)?;
+    if image.width() > width {
+        image = image.resize(width, u32::MAX, FilterType::Triangle);
+    }
+    let mut encoded = vec![];
+    // The encoders only take 8 bit RGB(A).
+    if image.color().has_alpha() {
+        DynamicImage::ImageRgba8(image.to_rgba8())
+            .write_with_encoder(WebPEncoder::new_lossless(&mut encoded))?;
+    } else {
+        DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, JPEG_QUALITY))?;
+    }
+    Ok(encoded)
+}
+
+/// The cached image of a connection's item at `width`, while it's younger than
+/// [`MAX_AGE`].
+///
+/// # Errors
+///
+/// When settings aren't initialized.
+pub async fn cached(connection_id: i32, item_id: &str, width: u32) -> Result<Option<Image>> {
+    let file = cache_file(cache_dir()?, connection_id, item_id, width);
+    Ok(read_fresh(&file).await.map(Image::new))
+}
+
+/// Fetches the image at a provider relative `path`, like the item's `icon_url`,
+/// resizes it to `width` and caches it for [`cached`].
+///
+/// # Errors
+///
+/// When the provider doesn't have the image or it isn't one.
+pub async fn fetch(
+    provider: &ConnectedMediaProvider,
+    connection_id: i32,
+    item_id: &str,
+    path: &str,
+    width: u32,
+) -> Result<Image> {
+    let file = cache_file(cache_dir()?, connection_id, item_id, width);
+    let original = provider.image(path).await.map_err(|e| {
+        tracing::warn!(error = ?e, connection_id, path, "Failed to fetch image");
+        Error::NotFound
+    })?;
+    let bytes = tokio::task::spawn_blocking(move || encode(&original, width))
+        .await
+        .map_err(|e| Error::Message(e.to_string()))?
+        .map_err(|e| Error::Message(format!("Invalid image at {path}: {e}")))?;
+    if let Err(e) = store(&file, &bytes).await {
+        tracing::warn!(error = ?e, ?file, "Failed to cache image");
+    }
+    Ok(Image::new(bytes))
+}
+
+async fn read_fresh(file: &Path) -> Option<Vec<u8>> {
+    let modified = tokio::fs::metadata(file).await.ok()?.modified().ok()?;
+    if modified.elapsed().unwrap_or_default() > MAX_AGE {
+        return None;
+    }
+    tokio::fs::read(file).await.ok()
+}
+
+async fn store(file: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = file.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    // Written aside first, so concurrent requests never read half an image.
+    let partial = file.with_extension(format!("{}.part", uuid::Uuid::new_v4()));
+    tokio::fs::write(&partial, bytes).await?;
+    tokio::fs::rename(&partial, file).await
+}
+
+/// Removes the cached images older than [`MAX_AGE`], which would be fetched
+/// again anyway, and returns how many. A missing cache directory is empty.
+///
+/// # Errors
+///
+/// When the cache directory can't be read.
+pub async fn remove_stale(cache_dir: &Path) -> std::io::Result<usize> {
+    let mut removed = 0;
+    let mut connections = match tokio::fs::read_dir(cache_dir).await {
+        Ok(connections) => connections,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    while let Some(connection) = connections.next_entry().await? {
+        if !connection.file_type().await?.is_dir() {
+            continue;
+        }
+        let mut files = tokio::fs::read_dir(connection.path()).await?;
+        while let Some(file) = files.next_entry().await? {
+            let stale = file
+                .metadata()
+                .await?
+                .modified()?
+                .elapsed()
+                .is_ok_and(|age| age > MAX_AGE);
+            if stale {
+                tokio::fs::remove_file(file.path()).await?;
+                removed += 1;
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/// Runs [`remove_stale`] on the settings' `image_cache_dir`, logging failures.
+pub async fn remove_stale_cached() {
+    let dir = match cache_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to clean the image cache");
+            return;
+        }
+    };
+    match remove_stale(dir).await {
+        Ok(0) => {}
+        Ok(removed) => tracing::info!(removed, "Removed stale cached images"),
+        Err(e) => tracing::error!(error = ?e, ?dir, "Failed to clean the image cache"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageFormat, RgbImage, RgbaImage};
+
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = std::io::Cursor::new(vec![]);
+        RgbImage::from_pixel(width, height, image::Rgb([200, 80, 40]))
+            .write_to(&mut data, ImageFormat::Png)
+            .unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn shrinks_keeping_the_aspect_ratio() {
+        let jpeg = encode(&png(600, 900), 300).unwrap();
+        let image = image::load_from_memory_with_format(&jpeg, ImageFormat::Jpeg).unwrap();
+        assert_eq!((image.width(), image.height()), (300, 450));
+
+        let jpeg = encode(&png(100, 50), 300).unwrap();
+        let image = image::load_from_memory(&jpeg).unwrap();
+        assert_eq!((image.width(), image.height()), (100, 50));
+
+        assert!(encode(b"not an image", 300).is_err());
+    }
+
+    #[test]
+    fn keeps_transparency_in_webp() {
+        let mut data = std::io::Cursor::new(vec![]);
+        RgbaImage::from_pixel(40, 20, image::Rgba([200, 80, 40, 100]))
+            .write_to(&mut data, ImageFormat::Png)
+            .unwrap();
+        let encoded = encode(&data.into_inner(), 400).unwrap();
+        assert_eq!(image::guess_format(&encoded).unwrap(), ImageFormat::WebP);
+        assert_eq!(Image::new(encoded).content_type, "image/webp");
+        let photo = Image::new(encode(&png(40, 20), 400).unwrap());
+        assert_eq!(photo.content_type, "image/jpeg");
+    }
+
+    #[test]
+    fn snaps_widths() {
+        assert_eq!(snap_width(None), DEFAULT_WIDTH);
+        assert_eq!(snap_width(Some(1)), 200);
+        assert_eq!(snap_width(Some(300)), 400);
+        assert_eq!(snap_width(Some(400)), 400);
+        assert_eq!(snap_width(Some(CATALOG_WIDTH)), CATALOG_WIDTH);
+        assert_eq!(snap_width(Some(10_000)), 1280);
+    }
+
+    #[test]
+    fn caches_per_connection_item_and_width() {
+        let dir = Path::new("/cache");
+        assert_eq!(
+            cache_file(dir, 1, "abc", 400),
+            cache_file(dir, 1, "abc", snap_width(Some(350)))
+        );
+        assert_ne!(
+            cache_file(dir, 1, "abc", 400),
+            cache_file(dir, 2, "abc", 400)
+        );
+        assert_ne!(
+            cache_file(dir, 1, "abc", 400),
+            cache_file(dir, 1, "abd", 400)
+        );
+        assert_ne!(
+            cache_file(dir, 1, "abc", 400),
+            cache_file(dir, 1, "abc", 640)
+        );
+    }
+
+    #[tokio::test]
+    async fn removes_only_stale_images() {
+        let dir = std::env::temp_dir().join(format!("images-{}", uuid::Uuid::new_v4()));
+        assert_eq!(remove_stale(&dir).await.unwrap(), 0);
+
+        let fresh = cache_file(&dir, 1, "fresh", 400);
+        let stale = cache_file(&dir, 1, "stale", 400);
+        store(&fresh, b"fresh").await.unwrap();
+        store(&stale, b"stale").await.unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&stale)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() - MAX_AGE - Duration::from_secs(60))
+            .unwrap();
+
+        assert_eq!(remove_stale(&dir).await.unwrap(), 1);
+        assert!(fresh.exists());
+        assert!(!stale.exists());
+        assert_eq!(read_fresh(&fresh).await.unwrap(), b"fresh");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,284 @@
+//! Keeps a connection's cached library tree in step with its provider, so
+//! listings don't depend on someone opening a folder with `?force=true`. A sync
+//! walks the tree from the root views, fetches what changed since the last one,
+//! restores what came back and tombstones what the provider stopped listing.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use loco_rs::model::{ModelError, ModelResult};
+use players::types::{Item, ItemsQuery, Library};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::pagination::FETCH_PAGE_SIZE,
+    initializers::media_provider::ConnectedMediaProvider,
+    models::_entities::{contents, libraries, player_connections},
+};
+
+/// Outcome of the last sync, kept in `player_connections.status`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncStatus {
+    /// Start of the last successful sync, the next one fetches changes since.
+    pub synced_from: Option<DateTime<Utc>>,
+    /// End of the last sync, successful or not.
+    pub last_sync_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// Items fetched and cached by the last sync.
+    pub updated: usize,
+    /// Items tombstoned by the last sync.
+    pub removed: u64,
+}
+
+/// Syncs the connection's library tree and records how it went. Provider errors
+/// end up in the returned status rather than failing, a sync already underway,
+/// even in another process, is left alone and the previous status returned.
+///
+/// # Errors
+///
+/// When the status can't be saved.
+pub async fn sync_connection(
+    db: &DatabaseConnection,
+    connection: &player_connections::Model,
+) -> ModelResult<SyncStatus> {
+    let previous = connection.sync_status();
+    let started = Utc::now();
+    if !player_connections::Model::start_sync(db, connection.id, started.naive_utc()).await? {
+        tracing::info!(connection_id = connection.id, "sync already underway");
+        return Ok(previous);
+    }
+    let mut counts = Counts::default();
+    let result = walk(db, connection, previous.synced_from, &mut counts).await;
+    player_connections::Model::end_sync(db, connection.id).await?;
+    let status = match result {
+        Ok(()) => SyncStatus {
+            synced_from: Some(started),
+            last_sync_at: Some(Utc::now()),
+            last_error: None,
+            updated: counts.updated,
+            removed: counts.removed,
+        },
+        Err(e) => {
+            tracing::error!(connection_id = connection.id, error = ?e, "library sync failed");
+            SyncStatus {
+                synced_from: previous.synced_from,
+                last_sync_at: Some(Utc::now()),
+                last_error: Some(e.to_string()),
+                updated: counts.updated,
+                removed: counts.removed,
+            }
+        }
+    };
+    player_connections::Model::set_sync_status(db, connection.id, &status).await?;
+    tracing::info!(
+        connection_id = connection.id,
+        elapsed = ?(Utc::now() - started),
+        updated = status.updated,
+        removed = status.removed,
+        "synced libraries"
+    );
+    Ok(status)
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    updated: usize,
+    removed: u64,
+}
+
+async fn walk(
+    db: &DatabaseConnection,
+    connection: &player_connections::Model,
+    since: Option<DateTime<Utc>>,
+    counts: &mut Counts,
+) -> ModelResult<()> {
+    let provider: ConnectedMediaProvider = connection
+        .clone()
+        .try_into()
+        .map_err(|e: loco_rs::Error| ModelError::Any(e.into()))?;
+    let roots = provider
+        .items(None)
+        .await
+        .map_err(|e| ModelError::Any(e.into()))?;
+    let roots: Vec<&Library> = roots
+        .iter()
+        .filter_map(|item| match item {
+            Item::Library(library) => Some(library),
+            Item::Content(_) => None,
+        })
+        .collect();
+    player_connections::Model::upsert_root_libraries(db, connection.id, &roots).await?;
+
+    let pending = roots.iter().map(|library| library.id.clone()).collect();
+    walk_from(db, &provider, connection.id, pending, since, counts).await
+}
+
+async fn walk_from(
+    db: &DatabaseConnection,
+    provider: &ConnectedMediaProvider,
+    connection_id: i32,
+    mut pending: Vec<String>,
+    since: Option<DateTime<Utc>>,
+    counts: &mut Counts,
+) -> ModelResult<()> {
+    while let Some(library_id) = pending.pop() {
+        let children =
+            sync_library(db, provider, connection_id, &library_id, since, counts).await?;
+        pending.extend(children);
+    }
+    Ok(())
+}
+
+/// Fully syncs one library and everything under it, like a show's seasons and
+/// episodes, without touching the connection's sync status. Returns how many
+/// items were fetched.
+///
+/// # Errors
+///
+/// When the provider or the database can't be reached.
+pub async fn sync_subtree(
+    db: &DatabaseConnection,
+    provider: &ConnectedMediaProvider,
+    connection_id: i32,
+    library_id: &str,
+) -> ModelResult<usize> {
+    let mut counts = Counts::default();
+    walk_from(
+        db,
+        provider,
+        connection_id,
+        vec![library_id.to_string()],
+        None,
+        &mut counts,
+    )
+    .await?;
+    Ok(counts.updated)
+}
+
+async fn fetch_all(
+    provider: &ConnectedMediaProvider,
+    library: &Library,
+    query: ItemsQuery,
+) -> ModelResult<Vec<Item>> {
+    let mut items = vec![];
+    let mut fetched = 0;
+    loop {
+        let page = provider
+            .items_page(library, fetched, FETCH_PAGE_SIZE, query)
+            .await
+            .map_err(|e: loco_rs::Error| ModelError::Any(e.into()))?;
+        let len = page.items.len();
+        items.extend(page.items);
+        // Excluded libraries make pages shorter, the provider's total is what counts.
+        fetched += FETCH_PAGE_SIZE;
+        let done = match page.total {
+            Some(total) => fetched >= total,
+            None => len < FETCH_PAGE_SIZE,
+        };
+        if done {
+            return Ok(items);
+        }
+    }
+}
+
+/// Syncs a library's direct children, returning the ids of the libraries among
+/// them to sync next.
+async fn sync_library(
+    db: &DatabaseConnection,
+    provider: &ConnectedMediaProvider,
+    connection_id: i32,
+    library_id: &str,
+    since: Option<DateTime<Utc>>,
+    counts: &mut Counts,
+) -> ModelResult<Vec<String>> {
+    let library = Library::from_path(library_id);
+    // With a previous sync to go by, listing what exists is cheap and only what
+    // changed since needs its metadata fetched.
+    let listing = fetch_all(
+        provider,
+        &library,
+        ItemsQuery {
+            ids_only: since.is_some(),
+            ..ItemsQuery::default()
+        },
+    )
+    .await?;
+    let mut present_libraries = vec![];
+    let mut present_contents = vec![];
+    for item in &listing {
+        match item {
+            Item::Library(library) => present_libraries.push(library.id.clone()),
+            Item::Content(content) => present_contents.push(content.id.clone()),
+        }
+    }
+    let mut changed = match since {
+        Some(since) => {
+            fetch_all(
+                provider,
+                &library,
+                ItemsQuery {
+                    changed_since: Some(since),
+                    ..ItemsQuery::default()
+                },
+            )
+            .await?
+        }
+        None => listing,
+    };
+
+    // Items that didn't change but aren't cached either, like ones that were
+    // tombstoned and came back.
+    let mut cached: HashSet<String> =
+        libraries::Model::ids_by_connection_and_parent_id(db, connection_id, library_id)
+            .await?
+            .into_iter()
+            .collect();
+    cached.extend(
+        contents::Model::ids_by_connection_and_parent_id(db, connection_id, library_id).await?,
+    );
+    for item in &changed {
+        cached.insert(item_id(item).to_string());
+    }
+    let missing: Vec<&String> = present_libraries
+        .iter()
+        .chain(&present_contents)
+        .filter(|id| !cached.contains(*id))
+        .collect();
+    for id in missing {
+        changed.push(
+            provider
+                .item(id)
+                .await
+                .map_err(|e| ModelError::Any(e.into()))?,
+        );
+    }
+
+    let mut changed_libraries = vec![];
+    let mut changed_contents = vec![];
+    for item in &changed {
+        match item {
+            Item::Library(library) => changed_libraries.push(library),
+            Item::Content(content) => changed_contents.push(content),
+        }
+    }
+    libraries::Model::upsert_cache_data(db, connection_id, &changed_libraries, Some(library_id))
+        .await?;
+    contents::Model::upsert_cache_data(db, connection_id, &changed_contents, Some(library_id))
+        .await?;
+    counts.updated += changed.len();
+    counts.removed +=
+        libraries::Model::tombstone_missing(db, connection_id, library_id, &present_libraries)
+            .await?;
+    counts.removed +=
+        contents::Model::tombstone_missing(db, connection_id, library_id, &present_contents)
+            .await?;
+    Ok(present_libraries)
+}
+
+fn item_id(item: &Item) -> &str {
+    match item {
+        Item::Library(library) => &library.id,
+        Item::Content(content) => &content.id,
+    }
+}
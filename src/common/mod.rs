@@ -1,2 +1,18 @@
+pub mod clips;
+pub mod downloads;
+pub mod images;
+pub mod library_sync;
 pub mod notifications;
+pub mod outputs;
+pub mod pagination;
+pub mod playback;
+pub mod prefetch;
+pub mod remux;
+pub mod resume;
+pub mod search;
 pub mod settings;
+pub mod skips;
+pub mod splice;
+pub mod subscriptions;
+pub mod variants;
+pub mod webhooks;
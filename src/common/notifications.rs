@@ -9,6 +9,12 @@ pub enum DownloaderStatus {
         eta: String,
         eta_seconds: usize,
     },
+    FileProgressReport {
+        done_bytes: u64,
+        total_bytes: Option<u64>,
+        eta: String,
+        eta_seconds: usize,
+    },
     SegmentFailed {
         segment_id: usize,
         error: String,
@@ -16,4 +22,15 @@ pub enum DownloaderStatus {
     Finished {
         elapsed: std::time::Duration,
     },
+    ExportFinished {
+        elapsed: std::time::Duration,
+        bytes: u64,
+    },
+    ExportFailed {
+        error: String,
+    },
+    /// The download stopped before every part of it was fetched.
+    Failed {
+        error: String,
+    },
 }
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// A playable artifact produced for a piece of content, paths are relative to the
+/// transcoding dir (and so to `/p/stream/`).
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum Output {
+    Hls {
+        path: String,
+    },
+    Mp4 {
+        path: String,
+        size: u64,
+        duration_secs: f64,
+    },
+    /// The untouched original, fetched when the profile can direct play it.
+    File {
+        path: String,
+        container: String,
+        size: u64,
+    },
+}
+
+impl Output {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Hls { path } | Self::Mp4 { path, .. } | Self::File { path, .. } => path,
+        }
+    }
+}
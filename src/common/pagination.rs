@@ -0,0 +1,157 @@
+//! Keyset cursors for library listings. Libraries are listed before contents,
+//! each in their [`SortOrder`], so a cursor is the last item shown of either
+//! and the next page continues after wherever that item sorts.
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// Items per page of a library listing.
+pub const PAGE_SIZE: u64 = 60;
+
+/// Items per request when refreshing a library from the provider.
+pub const FETCH_PAGE_SIZE: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Libraries,
+    Contents,
+}
+
+/// How a library's contents are listed, picked by each user. Libraries always
+/// come first, seasons by number and the rest by name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    /// Episodes in airing order with specials where they aired, anything else
+    /// by name.
+    #[default]
+    Episode,
+    /// By the provider's sort name, so `The Matrix` files under `m`.
+    Name,
+    /// Oldest first.
+    Year,
+    /// Newest first.
+    DateAdded,
+    /// Shortest first.
+    Runtime,
+}
+
+impl SortOrder {
+    pub const ALL: [Self; 5] = [
+        Self::Episode,
+        Self::Name,
+        Self::Year,
+        Self::DateAdded,
+        Self::Runtime,
+    ];
+
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Episode => "episode",
+            Self::Name => "name",
+            Self::Year => "year",
+            Self::DateAdded => "date_added",
+            Self::Runtime => "runtime",
+        }
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|order| order.as_str() == s)
+            .ok_or_else(|| format!("Unknown sort order: {s}"))
+    }
+}
+
+/// Position after the last item of a page, formatted like `l.abc` for use in
+/// query strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub section: Section,
+    pub id: String,
+}
+
+impl Cursor {
+    #[must_use]
+    pub fn new(section: Section, id: &str) -> Self {
+        Self {
+            section,
+            id: id.to_string(),
+        }
+    }
+
+    /// The id to continue after within `section`, `None` when the section
+    /// starts from the beginning.
+    #[must_use]
+    pub fn after_in(cursor: Option<&Self>, section: Section) -> Option<&str> {
+        cursor
+            .filter(|cursor| cursor.section == section)
+            .map(|cursor| cursor.id.as_str())
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let section = match self.section {
+            Section::Libraries => 'l',
+            Section::Contents => 'c',
+        };
+        write!(f, "{section}.{}", self.id)
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid cursor: {s}");
+        let (section, id) = s.split_once('.').ok_or_else(invalid)?;
+        let section = match section {
+            "l" => Section::Libraries,
+            "c" => Section::Contents,
+            _ => return Err(invalid()),
+        };
+        if id.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self::new(section, id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_round_trip() {
+        let cursor = Cursor::new(Section::Contents, "5c0c.1");
+        assert_eq!(cursor.to_string(), "c.5c0c.1");
+        assert_eq!("c.5c0c.1".parse::<Cursor>(), Ok(cursor.clone()));
+        assert_eq!(
+            Cursor::after_in(Some(&cursor), Section::Contents),
+            Some("5c0c.1")
+        );
+        assert_eq!(Cursor::after_in(Some(&cursor), Section::Libraries), None);
+        assert!("x.a".parse::<Cursor>().is_err());
+        assert!("l".parse::<Cursor>().is_err());
+        assert!("l.".parse::<Cursor>().is_err());
+    }
+
+    #[test]
+    fn sort_orders_round_trip() {
+        for order in SortOrder::ALL {
+            assert_eq!(order.as_str().parse(), Ok(order));
+            assert_eq!(
+                serde_json::to_value(order).unwrap(),
+                serde_json::json!(order.as_str())
+            );
+        }
+        assert!("random".parse::<SortOrder>().is_err());
+    }
+}
@@ -0,0 +1,129 @@
+//! Watch progress seen by moonlit-binge, kept locally and mirrored to the provider.
+
+use std::collections::{hash_map::Entry, HashMap};
+
+use players::types::PlaybackEvent;
+use sea_orm::{DatabaseConnection, EntityTrait};
+
+use crate::{
+    common::prefetch,
+    initializers::media_provider::ConnectedMediaProvider,
+    models::_entities::{contents, player_connections},
+};
+
+/// Records how far a viewer got into a content and reports it to the connection's
+/// provider. `stopped` is for players that say when they close a video, those
+/// positions are always recorded, others only every few seconds. Starting to
+/// watch an episode prefetches the next ones. Playback shouldn't fail over
+/// bookkeeping, so problems are only logged.
+pub async fn track(
+    db: &DatabaseConnection,
+    connection: player_connections::Model,
+    content_id: &str,
+    position_ms: u64,
+    stopped: bool,
+) {
+    let recorded = match i64::try_from(position_ms) {
+        Ok(position) => {
+            contents::Model::record_position(db, connection.id, content_id, position, stopped).await
+        }
+        Err(e) => Err(loco_rs::model::ModelError::Any(e.into())),
+    };
+    let progress = match recorded {
+        Ok(Some(progress)) => progress,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!(error = ?e, content_id, "Failed to record watch progress");
+            return;
+        }
+    };
+
+    if progress.session_started {
+        let db = db.clone();
+        let connection = connection.clone();
+        let content_id = content_id.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = prefetch::next_episodes(&db, &connection, &content_id).await {
+                tracing::warn!(error = ?e, content_id, "Failed to prefetch next episodes");
+            }
+        });
+    }
+
+    // Reported in the background so providers being slow doesn't stall playback.
+    let content_id = content_id.to_string();
+    tokio::spawn(async move {
+        let reported = async {
+            let provider: ConnectedMediaProvider = connection.try_into()?;
+            if progress.played {
+                provider
+                    .report_playback(&content_id, PlaybackEvent::Stopped, position_ms)
+                    .await?;
+                return provider.mark_played(&content_id).await;
+            }
+            let event = if stopped {
+                PlaybackEvent::Stopped
+            } else if progress.session_started {
+                PlaybackEvent::Start
+            } else {
+                PlaybackEvent::Progress
+            };
+            provider
+                .report_playback(&content_id, event, position_ms)
+                .await
+        }
+        .await;
+        if let Err(e) = reported {
+            tracing::warn!(error = ?e, content_id, "Failed to report playback");
+        }
+    });
+}
+
+/// Reports the sessions viewers left without their player saying so as stopped,
+/// so providers don't show them as still playing.
+pub async fn stop_stale_sessions(db: &DatabaseConnection) {
+    let stale = match contents::Model::stale_sessions(db, chrono::Utc::now().naive_utc()).await {
+        Ok(stale) => stale,
+        Err(e) => {
+            tracing::warn!(error = ?e, "Failed to list stale playback sessions");
+            return;
+        }
+    };
+    let mut providers: HashMap<i32, Option<ConnectedMediaProvider>> = HashMap::new();
+    for content in stale {
+        let connection_id = content.player_connection_id;
+        let provider = match providers.entry(connection_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(connect(db, connection_id).await),
+        };
+        let position_ms = content
+            .resume_position_ms
+            .or(content.duration_ms)
+            .and_then(|ms| u64::try_from(ms).ok())
+            .unwrap_or_default();
+        if let Some(provider) = provider {
+            if let Err(e) = provider
+                .report_playback(&content.content_id, PlaybackEvent::Stopped, position_ms)
+                .await
+            {
+                tracing::warn!(error = ?e, content_id = content.content_id, "Failed to report playback");
+            }
+        }
+        if let Err(e) = contents::Model::end_session(db, connection_id, &content.content_id).await {
+            tracing::warn!(error = ?e, content_id = content.content_id, "Failed to end playback session");
+        }
+    }
+}
+
+async fn connect(db: &DatabaseConnection, connection_id: i32) -> Option<ConnectedMediaProvider> {
+    let provider = async {
+        let connection = player_connections::Entity::find_by_id(connection_id)
+            .one(db)
+            .await?
+            .ok_or(loco_rs::Error::NotFound)?;
+        ConnectedMediaProvider::try_from(connection)
+    }
+    .await;
+    provider
+        .inspect_err(|e| tracing::warn!(error = ?e, connection_id, "Failed to connect to provider"))
+        .ok()
+}
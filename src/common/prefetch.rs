@@ -0,0 +1,122 @@
+//! Downloads of the episodes after the one being watched, so a binge doesn't
+//! stop to wait on the transcoder. Each user picks how many episodes ahead to
+//! go, `download_quota` in the settings caps how many of their downloads can
+//! be transcoding at once.
+
+use loco_rs::{Error, Result};
+use players::types::{ContentKind, Item};
+use sea_orm::{DatabaseConnection, EntityTrait};
+
+use crate::{
+    common::{downloads, settings::SETTINGS, variants::Variant},
+    initializers::media_provider::ConnectedMediaProvider,
+    models::{
+        _entities::{content_downloads, contents, player_connections, users},
+        contents::ContentWithModel,
+    },
+};
+
+/// Downloads a user can have transcoding when `download_quota` isn't set.
+pub const DEFAULT_DOWNLOAD_QUOTA: u64 = 6;
+/// Most episodes a user can prefetch.
+pub const MAX_PREFETCH_EPISODES: i32 = 10;
+
+/// Starts downloads of the episodes following `content_id` in its season, with
+/// the profile and languages it was downloaded with. Returns how many were
+/// started, none when it isn't an episode or the user's quota is used up.
+///
+/// # Errors
+///
+/// When the provider or database can't be reached, or downloads can't be queued.
+pub async fn next_episodes(
+    db: &DatabaseConnection,
+    connection: &player_connections::Model,
+    content_id: &str,
+) -> Result<u64> {
+    let user = users::Entity::find_by_id(connection.user_id)
+        .one(db)
+        .await?
+        .ok_or(Error::NotFound)?;
+    let wanted = u64::try_from(user.prefetch_episodes.min(MAX_PREFETCH_EPISODES)).unwrap_or(0);
+    if wanted == 0 {
+        return Ok(0);
+    }
+    let cached = contents::Model::by_connection_and_id(db, connection.id, content_id).await?;
+    let current = ContentWithModel::try_from(cached.clone())?.content;
+    if !matches!(current.kind, ContentKind::Episode { .. }) {
+        return Ok(0);
+    }
+    let quota = SETTINGS
+        .get()
+        .and_then(|settings| settings.download_quota)
+        .unwrap_or(DEFAULT_DOWNLOAD_QUOTA);
+    let mut budget =
+        quota.saturating_sub(content_downloads::Model::in_progress_by_user(db, user.id).await?);
+    if budget == 0 {
+        tracing::info!(
+            user_id = user.id,
+            quota,
+            "Download quota used up, not prefetching"
+        );
+        return Ok(0);
+    }
+
+    let next: Vec<contents::Model> = contents::Model::next_in_parent(db, &cached, wanted)
+        .await?
+        .into_iter()
+        .filter(|next| next.status.is_none())
+        .collect();
+    if next.is_empty() {
+        return Ok(0);
+    }
+    let variant = content_downloads::Model::find_variant(db, connection.id, content_id, None)
+        .await
+        .ok()
+        .and_then(|download| download.variant)
+        .and_then(|variant| serde_json::from_value::<Variant>(variant).ok())
+        .unwrap_or_default();
+    let streams = current.media_streams_of(variant.media_source_id.as_deref());
+    let audio_language = downloads::stream_language(streams, variant.audio_index, true);
+    let subtitle_language = downloads::stream_language(streams, variant.subtitle_index, false);
+
+    let provider: ConnectedMediaProvider = connection.clone().try_into()?;
+    let mut started = 0;
+    for cached in next {
+        if budget == 0 {
+            break;
+        }
+        let content = match provider.item(&cached.content_id).await {
+            Ok(Item::Content(content)) => content,
+            Ok(Item::Library(_)) => continue,
+            Err(e) => {
+                tracing::warn!(error = ?e, content_id = cached.content_id, "Failed to fetch next episode");
+                continue;
+            }
+        };
+        if !matches!(content.kind, ContentKind::Episode { .. }) {
+            continue;
+        }
+        let selection = downloads::Selection {
+            audio_index: downloads::stream_index(&content, audio_language.as_deref(), true),
+            subtitle_index: downloads::stream_index(&content, subtitle_language.as_deref(), false),
+            media_source_id: None,
+            profile: variant.profile.clone(),
+        };
+        if let Some(work) =
+            downloads::prepare(db, connection, &provider, &content, &selection).await?
+        {
+            provider.provider.queue_download(work).await?;
+        }
+        budget -= 1;
+        started += 1;
+    }
+    if started > 0 {
+        tracing::info!(
+            connection_id = connection.id,
+            content_id,
+            started,
+            "Prefetching next episodes"
+        );
+    }
+    Ok(started)
+}
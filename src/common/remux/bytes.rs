@@ -0,0 +1,156 @@
+//! Big endian reading and ISO BMFF box writing helpers.
+
+use eyre::eyre;
+
+/// Bounds checked big endian cursor over a byte slice.
+pub struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub fn take(&mut self, len: usize) -> eyre::Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| eyre!("unexpected end of data at {} (+{len})", self.pos))?;
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    pub fn skip(&mut self, len: usize) -> eyre::Result<()> {
+        self.take(len).map(|_| ())
+    }
+
+    pub fn u8(&mut self) -> eyre::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> eyre::Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn u64(&mut self) -> eyre::Result<u64> {
+        let b = self.take(8)?;
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(b);
+        Ok(u64::from_be_bytes(buf))
+    }
+}
+
+/// A box found while walking a buffer, `offset` is where its header starts.
+pub struct BoxRef<'a> {
+    pub kind: [u8; 4],
+    pub offset: usize,
+    pub body: &'a [u8],
+    /// The whole box including its header.
+    pub raw: &'a [u8],
+}
+
+/// Splits a buffer into its top level boxes.
+pub fn boxes(data: &[u8]) -> eyre::Result<Vec<BoxRef<'_>>> {
+    let mut out = vec![];
+    let mut offset = 0;
+    while offset + 8 <= data.len() {
+        let mut reader = Reader::new(&data[offset..]);
+        let size = reader.u32()?;
+        let mut kind = [0u8; 4];
+        kind.copy_from_slice(reader.take(4)?);
+        let (header, size) = match size {
+            0 => (8, data.len() - offset),
+            1 => (16, usize::try_from(reader.u64()?)?),
+            size => (8, size as usize),
+        };
+        if size < header || offset + size > data.len() {
+            return Err(eyre!(
+                "box {} at {offset} has invalid size {size}",
+                String::from_utf8_lossy(&kind)
+            ));
+        }
+        out.push(BoxRef {
+            kind,
+            offset,
+            body: &data[offset + header..offset + size],
+            raw: &data[offset..offset + size],
+        });
+        offset += size;
+    }
+    Ok(out)
+}
+
+/// Finds the first child box of the given kind.
+pub fn find<'a>(data: &'a [u8], kind: &[u8; 4]) -> eyre::Result<Option<BoxRef<'a>>> {
+    Ok(boxes(data)?.into_iter().find(|b| &b.kind == kind))
+}
+
+/// Like [`find`] but errors when the box is missing.
+pub fn require<'a>(data: &'a [u8], kind: &[u8; 4]) -> eyre::Result<BoxRef<'a>> {
+    find(data, kind)?.ok_or_else(|| eyre!("missing {} box", String::from_utf8_lossy(kind)))
+}
+
+/// Growable buffer for writing boxes.
+#[derive(Default)]
+pub struct Writer(pub Vec<u8>);
+
+impl Writer {
+    pub fn u8(&mut self, v: u8) -> &mut Self {
+        self.0.push(v);
+        self
+    }
+
+    pub fn u16(&mut self, v: u16) -> &mut Self {
+        self.0.extend_from_slice(&v.to_be_bytes());
+        self
+    }
+
+    pub fn u32(&mut self, v: u32) -> &mut Self {
+        self.0.extend_from_slice(&v.to_be_bytes());
+        self
+    }
+
+    pub fn u64(&mut self, v: u64) -> &mut Self {
+        self.0.extend_from_slice(&v.to_be_bytes());
+        self
+    }
+
+    pub fn bytes(&mut self, v: &[u8]) -> &mut Self {
+        self.0.extend_from_slice(v);
+        self
+    }
+
+    pub fn zeros(&mut self, len: usize) -> &mut Self {
+        self.0.resize(self.0.len() + len, 0);
+        self
+    }
+
+    /// Writes a box, `body` fills in its contents.
+    pub fn boxed(&mut self, kind: &[u8; 4], body: impl FnOnce(&mut Self)) -> &mut Self {
+        let start = self.0.len();
+        self.u32(0).bytes(kind);
+        body(self);
+        let size = u32::try_from(self.0.len() - start).expect("box larger than 4GiB");
+        self.0[start..start + 4].copy_from_slice(&size.to_be_bytes());
+        self
+    }
+
+    /// Writes a full box (with version and flags).
+    pub fn full_boxed(
+        &mut self,
+        kind: &[u8; 4],
+        version: u8,
+        flags: u32,
+        body: impl FnOnce(&mut Self),
+    ) -> &mut Self {
+        self.boxed(kind, |w| {
+            w.u32((u32::from(version) << 24) | (flags & 0x00ff_ffff));
+            body(w);
+        })
+    }
+}
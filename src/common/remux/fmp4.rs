@@ -0,0 +1,252 @@
+//! Fragmented MP4 (CMAF) demuxing, the init segment describes the tracks and every
+//! media segment carries `moof` + `mdat` pairs.
+
+use eyre::{bail, eyre, OptionExt};
+
+use super::{
+    bytes::{boxes, find, require, Reader},
+    Demux, Sample, TrackInfo, TrackKind,
+};
+
+const NON_SYNC_SAMPLE: u32 = 0x0001_0000;
+
+struct Track {
+    id: u32,
+    info: TrackInfo,
+    default_duration: u32,
+    default_size: u32,
+    default_flags: u32,
+    next_dts: u64,
+    samples: Vec<Sample>,
+}
+
+#[derive(Default)]
+pub struct Demuxer {
+    tracks: Vec<Track>,
+}
+
+impl Demuxer {
+    pub fn new(init: &[u8]) -> eyre::Result<Self> {
+        let mut demuxer = Self::default();
+        demuxer.parse_moov(require(init, b"moov")?.body)?;
+        Ok(demuxer)
+    }
+
+    fn parse_moov(&mut self, moov: &[u8]) -> eyre::Result<()> {
+        let trex = match find(moov, b"mvex")? {
+            Some(mvex) => boxes(mvex.body)?
+                .into_iter()
+                .filter(|b| &b.kind == b"trex")
+                .map(|b| {
+                    let mut r = Reader::new(b.body);
+                    r.skip(4)?;
+                    let id = r.u32()?;
+                    r.skip(4)?;
+                    Ok((id, [r.u32()?, r.u32()?, r.u32()?]))
+                })
+                .collect::<eyre::Result<Vec<_>>>()?,
+            None => vec![],
+        };
+
+        for trak in boxes(moov)?.into_iter().filter(|b| &b.kind == b"trak") {
+            let tkhd = require(trak.body, b"tkhd")?.body;
+            let mut r = Reader::new(tkhd);
+            let version = r.u8()?;
+            r.skip(if version == 1 { 3 + 16 } else { 3 + 8 })?;
+            let id = r.u32()?;
+            let dimensions = tkhd.get(tkhd.len().saturating_sub(8)..).unwrap_or_default();
+            let mut r = Reader::new(dimensions);
+            let width = u16::try_from(r.u32()? >> 16)?;
+            let height = u16::try_from(r.u32()? >> 16)?;
+
+            let mdia = require(trak.body, b"mdia")?.body;
+            let mdhd = require(mdia, b"mdhd")?.body;
+            let mut r = Reader::new(mdhd);
+            let version = r.u8()?;
+            r.skip(if version == 1 { 3 + 16 } else { 3 + 8 })?;
+            let timescale = r.u32()?;
+
+            let hdlr = require(mdia, b"hdlr")?.body;
+            let kind = match hdlr.get(8..12) {
+                Some(b"vide") => TrackKind::Video,
+                Some(b"soun") => TrackKind::Audio,
+                _ => continue,
+            };
+
+            let stbl = require(require(mdia, b"minf")?.body, b"stbl")?.body;
+            let stsd = require(stbl, b"stsd")?.body;
+            let sample_entry = boxes(stsd.get(8..).ok_or_eyre("truncated stsd")?)?
+                .into_iter()
+                .next()
+                .ok_or_eyre("stsd has no sample entries")?
+                .raw
+                .to_vec();
+
+            let [default_duration, default_size, default_flags] = trex
+                .iter()
+                .find(|(trex_id, _)| *trex_id == id)
+                .map_or([0; 3], |(_, defaults)| *defaults);
+            self.tracks.push(Track {
+                id,
+                info: TrackInfo {
+                    kind,
+                    timescale,
+                    sample_entry,
+                    width,
+                    height,
+                },
+                default_duration,
+                default_size,
+                default_flags,
+                next_dts: 0,
+                samples: vec![],
+            });
+        }
+        if self.tracks.is_empty() {
+            bail!("init segment has no audio or video tracks");
+        }
+        Ok(())
+    }
+
+    fn parse_traf(&mut self, segment: &[u8], moof_offset: usize, traf: &[u8]) -> eyre::Result<()> {
+        let tfhd = require(traf, b"tfhd")?.body;
+        let mut r = Reader::new(tfhd);
+        let flags = r.u32()? & 0x00ff_ffff;
+        let id = r.u32()?;
+        let Some(track) = self.tracks.iter_mut().find(|t| t.id == id) else {
+            return Ok(());
+        };
+        let base = if flags & 0x1 != 0 {
+            usize::try_from(r.u64()?)?
+        } else {
+            moof_offset
+        };
+        if flags & 0x2 != 0 {
+            r.skip(4)?;
+        }
+        let default_duration = if flags & 0x8 != 0 {
+            r.u32()?
+        } else {
+            track.default_duration
+        };
+        let default_size = if flags & 0x10 != 0 {
+            r.u32()?
+        } else {
+            track.default_size
+        };
+        let default_flags = if flags & 0x20 != 0 {
+            r.u32()?
+        } else {
+            track.default_flags
+        };
+
+        if let Some(tfdt) = find(traf, b"tfdt")? {
+            let mut r = Reader::new(tfdt.body);
+            track.next_dts = if r.u8()? == 1 {
+                r.skip(3)?;
+                r.u64()?
+            } else {
+                r.skip(3)?;
+                u64::from(r.u32()?)
+            };
+        }
+
+        let mut data_end = base;
+        for trun in boxes(traf)?.into_iter().filter(|b| &b.kind == b"trun") {
+            let mut r = Reader::new(trun.body);
+            let header = r.u32()?;
+            let version = header >> 24;
+            let flags = header & 0x00ff_ffff;
+            let count = r.u32()?;
+            let mut offset = if flags & 0x1 != 0 {
+                usize::try_from(i64::try_from(base)? + i64::from(r.u32()?.cast_signed()))?
+            } else {
+                data_end
+            };
+            let first_flags = if flags & 0x4 != 0 {
+                Some(r.u32()?)
+            } else {
+                None
+            };
+
+            for i in 0..count {
+                let duration = if flags & 0x100 != 0 {
+                    r.u32()?
+                } else {
+                    default_duration
+                };
+                let size = if flags & 0x200 != 0 {
+                    r.u32()?
+                } else {
+                    default_size
+                };
+                let sample_flags = match (i, first_flags) {
+                    (0, Some(first)) => {
+                        if flags & 0x400 != 0 {
+                            r.u32()?;
+                        }
+                        first
+                    }
+                    _ if flags & 0x400 != 0 => r.u32()?,
+                    _ => default_flags,
+                };
+                let cts_offset = if flags & 0x800 != 0 {
+                    let raw = r.u32()?;
+                    if version == 0 {
+                        i32::try_from(raw)?
+                    } else {
+                        raw.cast_signed()
+                    }
+                } else {
+                    0
+                };
+
+                let end = offset + usize::try_from(size)?;
+                let data = segment
+                    .get(offset..end)
+                    .ok_or_else(|| eyre!("sample {i} of track {id} is outside the segment"))?;
+                track.samples.push(Sample {
+                    data: data.to_vec(),
+                    dts: track.next_dts,
+                    duration: Some(duration),
+                    cts_offset,
+                    sync: sample_flags & NON_SYNC_SAMPLE == 0,
+                });
+                track.next_dts += u64::from(duration);
+                offset = end;
+            }
+            data_end = offset;
+        }
+        Ok(())
+    }
+}
+
+impl Demux for Demuxer {
+    fn push_segment(&mut self, data: &[u8]) -> eyre::Result<()> {
+        let top = boxes(data)?;
+        if self.tracks.is_empty() {
+            let moov = top
+                .iter()
+                .find(|b| &b.kind == b"moov")
+                .ok_or_eyre("fragmented MP4 segment without an init segment")?;
+            self.parse_moov(moov.body)?;
+        }
+        for moof in top.iter().filter(|b| &b.kind == b"moof") {
+            for traf in boxes(moof.body)?.into_iter().filter(|b| &b.kind == b"traf") {
+                self.parse_traf(data, moof.offset, traf.body)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn tracks(&self) -> Vec<TrackInfo> {
+        self.tracks.iter().map(|t| t.info.clone()).collect()
+    }
+
+    fn take_samples(&mut self) -> Vec<Vec<Sample>> {
+        self.tracks
+            .iter_mut()
+            .map(|t| std::mem::take(&mut t.samples))
+            .collect()
+    }
+}
@@ -0,0 +1,186 @@
+//! Just enough H.264 to repackage Annex B streams as MP4 samples.
+
+use eyre::{eyre, OptionExt};
+
+pub const NAL_IDR: u8 = 5;
+pub const NAL_SPS: u8 = 7;
+pub const NAL_PPS: u8 = 8;
+pub const NAL_AUD: u8 = 9;
+
+/// Splits an Annex B byte stream into NAL units (without start codes).
+pub fn split_annex_b(data: &[u8]) -> Vec<&[u8]> {
+    let mut nals = vec![];
+    let mut start = None;
+    let mut i = 0;
+    while i + 3 <= data.len() {
+        if data[i] == 0 && data[i + 1] == 0 && data[i + 2] == 1 {
+            if let Some(start) = start {
+                nals.push(trim_trailing_zeros(&data[start..i]));
+            }
+            i += 3;
+            start = Some(i);
+        } else {
+            i += 1;
+        }
+    }
+    if let Some(start) = start {
+        nals.push(&data[start..]);
+    }
+    nals.into_iter().filter(|nal| !nal.is_empty()).collect()
+}
+
+fn trim_trailing_zeros(nal: &[u8]) -> &[u8] {
+    let end = nal.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+    &nal[..end]
+}
+
+pub fn nal_type(nal: &[u8]) -> u8 {
+    nal.first().map_or(0, |b| b & 0x1f)
+}
+
+/// Removes emulation prevention bytes (`00 00 03`) from a NAL payload.
+fn unescape(nal: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(nal.len());
+    let mut zeros = 0;
+    for &b in nal {
+        if zeros >= 2 && b == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if b == 0 { zeros + 1 } else { 0 };
+        out.push(b);
+    }
+    out
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn bit(&mut self) -> eyre::Result<u32> {
+        let byte = self
+            .data
+            .get(self.pos / 8)
+            .ok_or_eyre("SPS ended unexpectedly")?;
+        let bit = (byte >> (7 - self.pos % 8)) & 1;
+        self.pos += 1;
+        Ok(u32::from(bit))
+    }
+
+    fn bits(&mut self, n: u32) -> eyre::Result<u32> {
+        (0..n).try_fold(0, |acc, _| Ok((acc << 1) | self.bit()?))
+    }
+
+    fn ue(&mut self) -> eyre::Result<u32> {
+        let mut zeros = 0;
+        while self.bit()? == 0 {
+            zeros += 1;
+            if zeros > 31 {
+                return Err(eyre!("invalid exp-golomb code"));
+            }
+        }
+        Ok((1u32 << zeros) - 1 + self.bits(zeros)?)
+    }
+
+    fn se(&mut self) -> eyre::Result<i32> {
+        let v = self.ue()?;
+        let magnitude = i32::try_from(v.div_ceil(2))?;
+        Ok(if v % 2 == 1 { magnitude } else { -magnitude })
+    }
+}
+
+/// Picture dimensions decoded from a sequence parameter set.
+pub fn sps_dimensions(sps: &[u8]) -> eyre::Result<(u16, u16)> {
+    let rbsp = unescape(sps);
+    let mut r = BitReader {
+        data: rbsp.get(1..).ok_or_eyre("empty SPS")?,
+        pos: 0,
+    };
+    let profile_idc = r.bits(8)?;
+    r.bits(16)?; // constraint flags, level_idc
+    r.ue()?; // seq_parameter_set_id
+    let mut chroma_format_idc = 1;
+    if matches!(
+        profile_idc,
+        100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+    ) {
+        chroma_format_idc = r.ue()?;
+        if chroma_format_idc == 3 {
+            r.bit()?; // separate_colour_plane_flag
+        }
+        r.ue()?; // bit_depth_luma_minus8
+        r.ue()?; // bit_depth_chroma_minus8
+        r.bit()?; // qpprime_y_zero_transform_bypass_flag
+        if r.bit()? == 1 {
+            let lists = if chroma_format_idc == 3 { 12 } else { 8 };
+            for i in 0..lists {
+                if r.bit()? == 1 {
+                    let size = if i < 6 { 16 } else { 64 };
+                    let (mut last, mut next) = (8i32, 8i32);
+                    for _ in 0..size {
+                        if next != 0 {
+                            next = (last + r.se()? + 256) % 256;
+                        }
+                        if next != 0 {
+                            last = next;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    r.ue()?; // log2_max_frame_num_minus4
+    match r.ue()? {
+        0 => {
+            r.ue()?; // log2_max_pic_order_cnt_lsb_minus4
+        }
+        1 => {
+            r.bit()?;
+            r.se()?;
+            r.se()?;
+            for _ in 0..r.ue()? {
+                r.se()?;
+            }
+        }
+        _ => {}
+    }
+    r.ue()?; // max_num_ref_frames
+    r.bit()?; // gaps_in_frame_num_value_allowed_flag
+    let width_mbs = r.ue()? + 1;
+    let height_map_units = r.ue()? + 1;
+    let frame_mbs_only = r.bit()?;
+    if frame_mbs_only == 0 {
+        r.bit()?; // mb_adaptive_frame_field_flag
+    }
+    r.bit()?; // direct_8x8_inference_flag
+    let (mut crop_x, mut crop_y) = (0, 0);
+    if r.bit()? == 1 {
+        let (left, right, top, bottom) = (r.ue()?, r.ue()?, r.ue()?, r.ue()?);
+        let (unit_x, unit_y) = match chroma_format_idc {
+            1 => (2, 2 * (2 - frame_mbs_only)),
+            2 => (2, 2 - frame_mbs_only),
+            _ => (1, 2 - frame_mbs_only),
+        };
+        crop_x = (left + right) * unit_x;
+        crop_y = (top + bottom) * unit_y;
+    }
+    let width = (width_mbs * 16).saturating_sub(crop_x);
+    let height = ((2 - frame_mbs_only) * height_map_units * 16).saturating_sub(crop_y);
+    Ok((u16::try_from(width)?, u16::try_from(height)?))
+}
+
+/// Builds an `avcC` decoder configuration record.
+pub fn avcc(sps: &[u8], pps: &[u8]) -> eyre::Result<Vec<u8>> {
+    if sps.len() < 4 {
+        return Err(eyre!("SPS too short"));
+    }
+    let mut out = vec![1, sps[1], sps[2], sps[3], 0xff, 0xe1];
+    out.extend_from_slice(&u16::try_from(sps.len())?.to_be_bytes());
+    out.extend_from_slice(sps);
+    out.push(1);
+    out.extend_from_slice(&u16::try_from(pps.len())?.to_be_bytes());
+    out.extend_from_slice(pps);
+    Ok(out)
+}
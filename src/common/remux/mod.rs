@@ -0,0 +1,321 @@
+//! Pure Rust remuxing of stored HLS downloads into a single progressive MP4.
+//!
+//! Segments are demuxed one at a time (MPEG-TS or fragmented MP4) and their samples
+//! are appended to a scratch file. Once every segment has been read the final file is
+//! written with `moov` in front of `mdat` ("faststart"), so players can begin playback
+//! and seek without fetching the whole file first. Nothing is re-encoded.
+
+mod bytes;
+mod fmp4;
+mod h264;
+mod mp4;
+mod ts;
+
+use std::path::Path;
+
+use eyre::{bail, Context};
+
+pub use mp4::Summary;
+pub use ts::{trim as trim_ts, Trimmed};
+
+/// Kind of an elementary stream carried by a segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackKind {
+    Video,
+    Audio,
+}
+
+/// Static description of a track, shared by every segment of the playlist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackInfo {
+    pub kind: TrackKind,
+    pub timescale: u32,
+    /// Complete sample entry box (`avc1`, `mp4a`, ...) to place into `stsd`.
+    pub sample_entry: Vec<u8>,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// A single access unit in MP4 (length prefixed) form.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub data: Vec<u8>,
+    /// Decode timestamp in the track timescale.
+    pub dts: u64,
+    /// Explicit duration, when the container carries one (fMP4, AAC).
+    /// Otherwise derived from the next sample's decode timestamp.
+    pub duration: Option<u32>,
+    /// Presentation minus decode timestamp.
+    pub cts_offset: i32,
+    pub sync: bool,
+}
+
+trait Demux {
+    /// Feeds one complete media segment into the demuxer.
+    fn push_segment(&mut self, data: &[u8]) -> eyre::Result<()>;
+    /// Tracks discovered so far, the order matches [`Demux::take_samples`].
+    fn tracks(&self) -> Vec<TrackInfo>;
+    /// Takes the samples demuxed since the last call, grouped per track.
+    fn take_samples(&mut self) -> Vec<Vec<Sample>>;
+}
+
+/// Remuxes the HLS download rooted at `base_dir` (its `main.m3u8` master playlist,
+/// picking the highest bandwidth variant) into a faststart MP4 at `output`.
+///
+/// # Errors
+///
+/// When the playlists or segments can't be read or parsed, the codecs aren't
+/// supported, or the output can't be written.
+pub fn remux_hls_to_mp4(base_dir: &Path, output: &Path) -> eyre::Result<Summary> {
+    let master = std::fs::read(base_dir.join("main.m3u8")).wrap_err("reading main.m3u8")?;
+    let master = m3u8_rs::parse_master_playlist_res(&master)
+        .map_err(|e| eyre::eyre!("Failed to parse master playlist: {e}"))?;
+    let variant = master
+        .variants
+        .iter()
+        .filter(|variant| !variant.is_i_frame)
+        .max_by_key(|variant| variant.bandwidth)
+        .ok_or_else(|| eyre::eyre!("Master playlist has no variants"))?;
+    let media = std::fs::read(base_dir.join(&variant.uri))
+        .wrap_err_with(|| format!("reading {}", variant.uri))?;
+    let media = m3u8_rs::parse_media_playlist_res(&media)
+        .map_err(|e| eyre::eyre!("Failed to parse media playlist: {e}"))?;
+
+    let scratch = output.with_extension("mp4.part");
+    let mut writer = mp4::Writer::create(&scratch)?;
+    let mut demuxer: Option<Box<dyn Demux>> = None;
+
+    for segment in &media.segments {
+        if let Some(map) = &segment.map {
+            let init = std::fs::read(base_dir.join(&map.uri))
+                .wrap_err_with(|| format!("reading init segment {}", map.uri))?;
+            match demuxer.as_mut() {
+                Some(_) => bail!("Changing EXT-X-MAP mid playlist is not supported"),
+                None => demuxer = Some(Box::new(fmp4::Demuxer::new(&init)?)),
+            }
+        }
+
+        let data = std::fs::read(base_dir.join(&segment.uri))
+            .wrap_err_with(|| format!("reading segment {}", segment.uri))?;
+        let demuxer = demuxer.get_or_insert_with(|| match data.first() {
+            Some(&ts::SYNC_BYTE) => Box::new(ts::Demuxer::default()),
+            _ => Box::new(fmp4::Demuxer::default()),
+        });
+        demuxer
+            .push_segment(&data)
+            .wrap_err_with(|| format!("demuxing segment {}", segment.uri))?;
+        writer.set_tracks(&demuxer.tracks())?;
+        for (track, samples) in demuxer.take_samples().into_iter().enumerate() {
+            writer.write_chunk(track, samples)?;
+        }
+    }
+
+    let summary = writer.finish(&scratch, output)?;
+    std::fs::remove_file(&scratch).wrap_err("removing scratch file")?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPS: [u8; 26] = [
+        0x67, 0x64, 0x00, 0x1f, 0xac, 0xd9, 0x40, 0x50, 0x05, 0xbb, 0x01, 0x10, 0x00, 0x00, 0x03,
+        0x00, 0x10, 0x00, 0x00, 0x03, 0x03, 0xc0, 0xf1, 0x83, 0x19, 0x60,
+    ];
+    const PPS: [u8; 6] = [0x68, 0xeb, 0xe3, 0xcb, 0x22, 0xc0];
+
+    fn packet(pid: u16, unit_start: bool, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![
+            ts::SYNC_BYTE,
+            (u8::from(unit_start) << 6) | u8::try_from(pid >> 8).unwrap(),
+            u8::try_from(pid & 0xff).unwrap(),
+        ];
+        let stuffing = 184 - payload.len();
+        if stuffing == 0 {
+            out.push(0x10);
+        } else {
+            out.push(0x30);
+            out.push(u8::try_from(stuffing - 1).unwrap());
+            if stuffing > 1 {
+                out.push(0);
+                out.resize(out.len() + stuffing - 2, 0xff);
+            }
+        }
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn timestamp(prefix: u8, v: u64) -> [u8; 5] {
+        let v = v & 0x1_ffff_ffff;
+        [
+            (prefix << 4) | u8::try_from((v >> 29) & 0x0e).unwrap() | 1,
+            u8::try_from((v >> 22) & 0xff).unwrap(),
+            u8::try_from((v >> 14) & 0xfe).unwrap() | 1,
+            u8::try_from((v >> 7) & 0xff).unwrap(),
+            u8::try_from((v << 1) & 0xfe).unwrap() | 1,
+        ]
+    }
+
+    fn segment(start: u64) -> Vec<u8> {
+        let mut out = packet(
+            0,
+            true,
+            &[
+                0, 0, 0xb0, 13, 0, 1, 0xc1, 0, 0, 0, 1, 0xf0, 0x00, 0, 0, 0, 0,
+            ],
+        );
+        out.extend(packet(
+            0x1000,
+            true,
+            &[
+                0, 2, 0xb0, 23, 0, 1, 0xc1, 0, 0, 0xe1, 0x00, 0xf0, 0, 0x1b, 0xe1, 0x00, 0xf0, 0,
+                0x0f, 0xe1, 0x01, 0xf0, 0, 0, 0, 0, 0,
+            ],
+        ));
+        for (frame, idr) in [(0, true), (1, false)] {
+            let dts = start + frame * 3003;
+            let mut pes = vec![0, 0, 1, 0xe0, 0, 0, 0x80, 0xc0, 10];
+            pes.extend(timestamp(3, dts + 3003));
+            pes.extend(timestamp(1, dts));
+            if idr {
+                pes.extend([0, 0, 0, 1]);
+                pes.extend(SPS);
+                pes.extend([0, 0, 1]);
+                pes.extend(PPS);
+            }
+            pes.extend([0, 0, 1, if idr { 0x65 } else { 0x41 }, 0x88, 0x84, 0x21]);
+            out.extend(packet(0x100, true, &pes));
+        }
+        let mut pes = vec![0, 0, 1, 0xc0, 0, 0, 0x80, 0x80, 5];
+        pes.extend(timestamp(2, start));
+        for _ in 0..2 {
+            // AAC LC, 44.1kHz, stereo, 4 byte payload.
+            pes.extend([0xff, 0xf1, 0x50, 0x80, 0x01, 0x7f, 0xfc, 1, 2, 3, 4]);
+        }
+        out.extend(packet(0x101, true, &pes));
+        out
+    }
+
+    #[test]
+    fn trims_ts_segments_at_keyframes() {
+        let mut data = segment(0)[..2 * 188].to_vec();
+        for frame in 0..6_u64 {
+            let idr = frame % 2 == 0;
+            let dts = frame * 3003;
+            let mut pes = vec![0, 0, 1, 0xe0, 0, 0, 0x80, 0xc0, 10];
+            pes.extend(timestamp(3, dts + 3003));
+            pes.extend(timestamp(1, dts));
+            pes.extend([0, 0, 1, if idr { 0x65 } else { 0x41 }, 0x88, 0x84, 0x21]);
+            data.extend(packet(0x100, true, &pes));
+            let mut pes = vec![0, 0, 1, 0xc0, 0, 0, 0x80, 0x80, 5];
+            pes.extend(timestamp(2, dts + 3003));
+            pes.extend([0xff, 0xf1, 0x50, 0x80, 0x01, 0x7f, 0xfc, 1, 2, 3, 4]);
+            data.extend(packet(0x101, true, &pes));
+        }
+
+        let trimmed = trim_ts(&data, Some(0.07), Some(0.1)).unwrap().unwrap();
+        assert_eq!(trimmed.start_secs, 6006.0 / 90_000.0);
+        assert_eq!(trimmed.end_secs, Some(12012.0 / 90_000.0));
+        let packets: Vec<&[u8]> = trimmed.data.chunks_exact(188).collect();
+        assert_eq!(packets.len(), 2 + 2 * 2);
+        // Continuity counters restart for what's left of the video stream.
+        let video: Vec<u8> = packets
+            .iter()
+            .filter(|p| p[2] == 0x00 && p[1] & 0x1f == 0x01)
+            .map(|p| p[3] & 0x0f)
+            .collect();
+        assert_eq!(video, [0, 1]);
+
+        assert!(trim_ts(&data, None, None).unwrap().is_none());
+        assert!(trim_ts(&data, Some(0.01), None).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_truncated_ts_segments() {
+        let headers = &segment(0)[..2 * 188];
+
+        // PTS and DTS flagged, but the PES header only has room for 2 bytes.
+        let mut data = headers.to_vec();
+        data.extend(packet(
+            0x100,
+            true,
+            &[0, 0, 1, 0xe0, 0, 0, 0x80, 0xc0, 2, 0, 0],
+        ));
+        assert!(ts::Demuxer::default().push_segment(&data).is_err());
+
+        // A PMT whose section is only the generic header and CRC.
+        let mut data = headers[..188].to_vec();
+        data.extend(packet(
+            0x1000,
+            true,
+            &[0, 2, 0xb0, 9, 0, 1, 0xc1, 0, 0, 0, 0, 0, 0],
+        ));
+        assert!(ts::Demuxer::default().push_segment(&data).is_err());
+    }
+
+    #[test]
+    fn keeps_high_sample_rates_out_of_the_sample_entry() {
+        let mut data = segment(0);
+        // AAC LC at 96kHz instead of 44.1kHz.
+        let positions: Vec<usize> = data
+            .windows(3)
+            .enumerate()
+            .filter(|(_, w)| *w == [0xff, 0xf1, 0x50])
+            .map(|(i, _)| i + 2)
+            .collect();
+        for i in positions {
+            data[i] = 0x40;
+        }
+
+        let mut demuxer = ts::Demuxer::default();
+        demuxer.push_segment(&data).unwrap();
+        let audio = demuxer
+            .tracks()
+            .into_iter()
+            .find(|t| t.kind == TrackKind::Audio)
+            .unwrap();
+        assert_eq!(audio.timescale, 96_000);
+        // The 16.16 rate field is left empty instead of wrapping around.
+        assert_eq!(&audio.sample_entry[4..8], b"mp4a");
+        assert_eq!(audio.sample_entry[32..36], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn remuxes_ts_segments_into_faststart_mp4() {
+        let dir = std::env::temp_dir().join(format!("remux-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("720p")).unwrap();
+        std::fs::write(
+            dir.join("main.m3u8"),
+            "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1000000,RESOLUTION=1280x720\n720p.m3u8\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("720p.m3u8"),
+            "#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXTINF:0.0667,\n720p/0.ts\n#EXTINF:0.0667,\n720p/1.ts\n#EXT-X-ENDLIST\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("720p/0.ts"), segment(0)).unwrap();
+        std::fs::write(dir.join("720p/1.ts"), segment(6006)).unwrap();
+
+        let output = dir.join("main.mp4");
+        let summary = remux_hls_to_mp4(&dir, &output).unwrap();
+        assert_eq!(summary.tracks, 2);
+
+        let file = std::fs::read(&output).unwrap();
+        let top = bytes::boxes(&file).unwrap();
+        let kinds: Vec<_> = top.iter().map(|b| &b.kind).collect();
+        assert_eq!(kinds, [b"ftyp", b"moov", b"mdat"]);
+        let traks = bytes::boxes(top[1].body)
+            .unwrap()
+            .into_iter()
+            .filter(|b| &b.kind == b"trak")
+            .count();
+        assert_eq!(traks, 2);
+        // 4 video samples (length prefix + 4 byte slice) and 4 AAC frames.
+        assert_eq!(top[2].body.len(), 4 * 8 + 4 * 4);
+        assert!(!dir.join("main.mp4.part").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,356 @@
+//! Progressive MP4 writer. Sample data is appended to a scratch file as it arrives
+//! and the final file is assembled as `ftyp` + `moov` + `mdat` once all tables are known.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use eyre::{bail, Context};
+
+use super::{bytes::Writer as BoxWriter, Sample, TrackInfo, TrackKind};
+
+const MOVIE_TIMESCALE: u32 = 1000;
+const MATRIX: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+
+/// What ended up in the exported file.
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub duration_secs: f64,
+    pub bytes: u64,
+    pub tracks: usize,
+}
+
+#[derive(Default)]
+struct Track {
+    info: Option<TrackInfo>,
+    /// Offset into the scratch file and number of samples of every chunk.
+    chunks: Vec<(u64, u32)>,
+    sizes: Vec<u32>,
+    dts: Vec<u64>,
+    durations: Vec<Option<u32>>,
+    cts_offsets: Vec<i32>,
+    sync: Vec<bool>,
+}
+
+impl Track {
+    fn info(&self) -> &TrackInfo {
+        self.info.as_ref().expect("tracks are set before writing")
+    }
+
+    /// Resolves the duration of every sample, falling back to the next sample's decode
+    /// time and finally to the previous sample's duration.
+    fn resolved_durations(&self) -> Vec<u32> {
+        let mut out: Vec<u32> = Vec::with_capacity(self.dts.len());
+        for i in 0..self.dts.len() {
+            let duration = self.durations[i]
+                .or_else(|| {
+                    self.dts
+                        .get(i + 1)
+                        .and_then(|next| u32::try_from(next.saturating_sub(self.dts[i])).ok())
+                })
+                .or_else(|| out.last().copied())
+                .unwrap_or(0);
+            out.push(duration);
+        }
+        out
+    }
+}
+
+pub struct Writer {
+    scratch: BufWriter<File>,
+    written: u64,
+    tracks: Vec<Track>,
+}
+
+impl Writer {
+    pub fn create(scratch: &Path) -> eyre::Result<Self> {
+        let file = File::create(scratch).wrap_err("creating scratch file")?;
+        Ok(Self {
+            scratch: BufWriter::new(file),
+            written: 0,
+            tracks: vec![],
+        })
+    }
+
+    pub fn set_tracks(&mut self, tracks: &[TrackInfo]) -> eyre::Result<()> {
+        if tracks.is_empty() {
+            return Ok(());
+        }
+        if self.tracks.is_empty() {
+            self.tracks = tracks
+                .iter()
+                .map(|info| Track {
+                    info: Some(info.clone()),
+                    ..Track::default()
+                })
+                .collect();
+            return Ok(());
+        }
+        if self.tracks.len() != tracks.len()
+            || self
+                .tracks
+                .iter()
+                .zip(tracks)
+                .any(|(a, b)| a.info().kind != b.kind || a.info().timescale != b.timescale)
+        {
+            bail!("Track layout changed mid playlist");
+        }
+        Ok(())
+    }
+
+    pub fn write_chunk(&mut self, track: usize, samples: Vec<Sample>) -> eyre::Result<()> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        let Some(state) = self.tracks.get_mut(track) else {
+            bail!("Samples for unknown track {track}");
+        };
+        state
+            .chunks
+            .push((self.written, u32::try_from(samples.len())?));
+        for sample in samples {
+            self.scratch.write_all(&sample.data)?;
+            self.written += sample.data.len() as u64;
+            state.sizes.push(u32::try_from(sample.data.len())?);
+            state.dts.push(sample.dts);
+            state.durations.push(sample.duration);
+            state.cts_offsets.push(sample.cts_offset);
+            state.sync.push(sample.sync);
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self, scratch: &Path, output: &Path) -> eyre::Result<Summary> {
+        self.scratch.flush()?;
+        drop(self.scratch);
+        self.tracks.retain(|t| !t.sizes.is_empty());
+        if self.tracks.is_empty() {
+            bail!("No samples were found in the playlist");
+        }
+
+        let ftyp = {
+            let mut w = BoxWriter::default();
+            w.boxed(b"ftyp", |w| {
+                w.bytes(b"isom").u32(0x200);
+                w.bytes(b"isom")
+                    .bytes(b"iso2")
+                    .bytes(b"avc1")
+                    .bytes(b"mp41");
+            });
+            w.0
+        };
+        let large = self.written + (1 << 26) > u64::from(u32::MAX);
+        let mdat_header: u64 = if large { 16 } else { 8 };
+        // Table sizes don't depend on the offsets, so measure first and then rebuild.
+        let (probe, _) = build_moov(&self.tracks, 0, large);
+        let data_start = ftyp.len() as u64 + probe.len() as u64 + mdat_header;
+        let (moov, duration_secs) = build_moov(&self.tracks, data_start, large);
+
+        let partial = output.with_extension("mp4.tmp");
+        let mut out = BufWriter::new(File::create(&partial).wrap_err("creating output file")?);
+        out.write_all(&ftyp)?;
+        out.write_all(&moov)?;
+        if large {
+            out.write_all(&1u32.to_be_bytes())?;
+            out.write_all(b"mdat")?;
+            out.write_all(&(self.written + 16).to_be_bytes())?;
+        } else {
+            out.write_all(&u32::try_from(self.written + 8)?.to_be_bytes())?;
+            out.write_all(b"mdat")?;
+        }
+        let mut scratch = File::open(scratch).wrap_err("reopening scratch file")?;
+        std::io::copy(&mut scratch, &mut out).wrap_err("copying sample data")?;
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        std::fs::rename(&partial, output).wrap_err("moving output into place")?;
+
+        Ok(Summary {
+            duration_secs,
+            bytes: data_start + self.written,
+            tracks: self.tracks.len(),
+        })
+    }
+}
+
+/// Builds the `moov` box, returning it alongside the movie duration in seconds.
+#[allow(clippy::cast_precision_loss)]
+fn build_moov(tracks: &[Track], data_start: u64, large: bool) -> (Vec<u8>, f64) {
+    let starts: Vec<f64> = tracks
+        .iter()
+        .map(|t| t.dts[0] as f64 / f64::from(t.info().timescale))
+        .collect();
+    let earliest = starts.iter().copied().fold(f64::INFINITY, f64::min);
+
+    let mut traks = BoxWriter::default();
+    let mut movie_duration = 0u64;
+    for (index, track) in tracks.iter().enumerate() {
+        let info = track.info();
+        let durations = track.resolved_durations();
+        let media_duration: u64 = durations.iter().map(|d| u64::from(*d)).sum();
+        let delay = to_movie_units(starts[index] - earliest);
+        let presented = media_duration * u64::from(MOVIE_TIMESCALE) / u64::from(info.timescale);
+        movie_duration = movie_duration.max(delay + presented);
+        let media_time = track.cts_offsets.iter().copied().min().unwrap_or(0).max(0);
+
+        let track_id = u32::try_from(index + 1).expect("few tracks");
+        traks.boxed(b"trak", |w| {
+            w.full_boxed(b"tkhd", 1, 0x3, |w| {
+                w.u64(0).u64(0).u32(track_id).u32(0).u64(delay + presented);
+                w.zeros(8).u16(0).u16(0);
+                w.u16(if info.kind == TrackKind::Audio {
+                    0x0100
+                } else {
+                    0
+                })
+                .u16(0);
+                for v in MATRIX {
+                    w.u32(v);
+                }
+                w.u32(u32::from(info.width) << 16)
+                    .u32(u32::from(info.height) << 16);
+            });
+            w.boxed(b"edts", |w| {
+                w.full_boxed(b"elst", 1, 0, |w| {
+                    w.u32(if delay > 0 { 2 } else { 1 });
+                    if delay > 0 {
+                        w.u64(delay).u64(u64::MAX).u16(1).u16(0);
+                    }
+                    w.u64(presented)
+                        .u64(u64::from(media_time.unsigned_abs()))
+                        .u16(1)
+                        .u16(0);
+                });
+            });
+            w.boxed(b"mdia", |w| {
+                w.full_boxed(b"mdhd", 1, 0, |w| {
+                    w.u64(0).u64(0).u32(info.timescale).u64(media_duration);
+                    // Language "und", packed ISO-639-2/T.
+                    w.u16(0x55c4).u16(0);
+                });
+                w.full_boxed(b"hdlr", 0, 0, |w| {
+                    let (handler, name): (&[u8; 4], &[u8]) = match info.kind {
+                        TrackKind::Video => (b"vide", b"VideoHandler\0"),
+                        TrackKind::Audio => (b"soun", b"SoundHandler\0"),
+                    };
+                    w.u32(0).bytes(handler).zeros(12).bytes(name);
+                });
+                w.boxed(b"minf", |w| {
+                    match info.kind {
+                        TrackKind::Video => w.full_boxed(b"vmhd", 0, 1, |w| {
+                            w.zeros(8);
+                        }),
+                        TrackKind::Audio => w.full_boxed(b"smhd", 0, 0, |w| {
+                            w.zeros(4);
+                        }),
+                    };
+                    w.boxed(b"dinf", |w| {
+                        w.full_boxed(b"dref", 0, 0, |w| {
+                            w.u32(1).full_boxed(b"url ", 0, 1, |_| {});
+                        });
+                    });
+                    w.boxed(b"stbl", |w| {
+                        write_stbl(w, track, &durations, data_start, large);
+                    });
+                });
+            });
+        });
+    }
+
+    let mut moov = BoxWriter::default();
+    moov.boxed(b"moov", |w| {
+        w.full_boxed(b"mvhd", 1, 0, |w| {
+            w.u64(0).u64(0).u32(MOVIE_TIMESCALE).u64(movie_duration);
+            w.u32(0x0001_0000).u16(0x0100).zeros(10);
+            for v in MATRIX {
+                w.u32(v);
+            }
+            w.zeros(24)
+                .u32(u32::try_from(tracks.len() + 1).expect("few tracks"));
+        });
+        w.bytes(&traks.0);
+    });
+    (moov.0, movie_duration as f64 / f64::from(MOVIE_TIMESCALE))
+}
+
+fn write_stbl(w: &mut BoxWriter, track: &Track, durations: &[u32], data_start: u64, large: bool) {
+    w.full_boxed(b"stsd", 0, 0, |w| {
+        w.u32(1).bytes(&track.info().sample_entry);
+    });
+    let stts = run_lengths(durations.iter().copied());
+    w.full_boxed(b"stts", 0, 0, |w| {
+        w.u32(u32::try_from(stts.len()).expect("sample count fits u32"));
+        for (count, duration) in &stts {
+            w.u32(*count).u32(*duration);
+        }
+    });
+    if track.cts_offsets.iter().any(|c| *c != 0) {
+        let ctts = run_lengths(track.cts_offsets.iter().copied());
+        w.full_boxed(b"ctts", 1, 0, |w| {
+            w.u32(u32::try_from(ctts.len()).expect("sample count fits u32"));
+            for (count, offset) in &ctts {
+                w.u32(*count).u32(offset.cast_unsigned());
+            }
+        });
+    }
+    if track.sync.iter().any(|s| !s) {
+        w.full_boxed(b"stss", 0, 0, |w| {
+            let sync: Vec<u32> = (1..)
+                .zip(&track.sync)
+                .filter_map(|(n, s)| s.then_some(n))
+                .collect();
+            w.u32(u32::try_from(sync.len()).expect("sample count fits u32"));
+            for n in sync {
+                w.u32(n);
+            }
+        });
+    }
+    let stsc = run_lengths(track.chunks.iter().map(|(_, count)| *count));
+    w.full_boxed(b"stsc", 0, 0, |w| {
+        w.u32(u32::try_from(stsc.len()).expect("chunk count fits u32"));
+        let mut first_chunk = 1;
+        for (chunks, samples) in &stsc {
+            w.u32(first_chunk).u32(*samples).u32(1);
+            first_chunk += chunks;
+        }
+    });
+    w.full_boxed(b"stsz", 0, 0, |w| {
+        w.u32(0)
+            .u32(u32::try_from(track.sizes.len()).expect("sample count fits u32"));
+        for size in &track.sizes {
+            w.u32(*size);
+        }
+    });
+    let count = u32::try_from(track.chunks.len()).expect("chunk count fits u32");
+    if large {
+        w.full_boxed(b"co64", 0, 0, |w| {
+            w.u32(count);
+            for (offset, _) in &track.chunks {
+                w.u64(data_start + offset);
+            }
+        });
+    } else {
+        w.full_boxed(b"stco", 0, 0, |w| {
+            w.u32(count);
+            for (offset, _) in &track.chunks {
+                w.u32(u32::try_from(data_start + offset).expect("checked by `large`"));
+            }
+        });
+    }
+}
+
+fn run_lengths<T: PartialEq + Copy>(values: impl Iterator<Item = T>) -> Vec<(u32, T)> {
+    let mut out: Vec<(u32, T)> = vec![];
+    for value in values {
+        match out.last_mut() {
+            Some((count, last)) if *last == value => *count += 1,
+            _ => out.push((1, value)),
+        }
+    }
+    out
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn to_movie_units(secs: f64) -> u64 {
+    (secs * f64::from(MOVIE_TIMESCALE)).round().max(0.0) as u64
+}
@@ -0,0 +1,529 @@
+//! MPEG-TS demuxing of H.264 video and ADTS AAC audio.
+
+use eyre::{bail, eyre, OptionExt};
+
+use super::{bytes::Writer, h264, Demux, Sample, TrackInfo, TrackKind};
+
+pub const SYNC_BYTE: u8 = 0x47;
+const PACKET_SIZE: usize = 188;
+const STREAM_TYPE_AAC: u8 = 0x0f;
+const STREAM_TYPE_H264: u8 = 0x1b;
+const TS_TIMESCALE: u64 = 90_000;
+const PTS_WRAP: u64 = 1 << 33;
+const AAC_FRAME_SAMPLES: u32 = 1024;
+
+const SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+struct Track {
+    pid: u16,
+    kind: TrackKind,
+    pes: Vec<u8>,
+    info: Option<TrackInfo>,
+    samples: Vec<Sample>,
+    /// Last raw 33-bit timestamp and the amount added for wraparounds so far.
+    last_ts: Option<u64>,
+    wrap_offset: u64,
+}
+
+impl Track {
+    fn unwrap_ts(&mut self, raw: u64) -> u64 {
+        if let Some(last) = self.last_ts {
+            if raw + PTS_WRAP / 2 < last {
+                self.wrap_offset += PTS_WRAP;
+            }
+        }
+        self.last_ts = Some(raw);
+        raw + self.wrap_offset
+    }
+}
+
+#[derive(Default)]
+pub struct Demuxer {
+    pmt_pid: Option<u16>,
+    tracks: Vec<Track>,
+}
+
+impl Demux for Demuxer {
+    fn push_segment(&mut self, data: &[u8]) -> eyre::Result<()> {
+        if !data.len().is_multiple_of(PACKET_SIZE) {
+            bail!("segment size {} is not a multiple of 188", data.len());
+        }
+        for packet in data.chunks_exact(PACKET_SIZE) {
+            self.push_packet(packet)?;
+        }
+        // HLS segments always end on a PES boundary.
+        for index in 0..self.tracks.len() {
+            self.flush_pes(index)?;
+        }
+        Ok(())
+    }
+
+    fn tracks(&self) -> Vec<TrackInfo> {
+        self.tracks.iter().filter_map(|t| t.info.clone()).collect()
+    }
+
+    fn take_samples(&mut self) -> Vec<Vec<Sample>> {
+        self.tracks
+            .iter_mut()
+            .filter(|t| t.info.is_some())
+            .map(|t| std::mem::take(&mut t.samples))
+            .collect()
+    }
+}
+
+impl Demuxer {
+    fn push_packet(&mut self, packet: &[u8]) -> eyre::Result<()> {
+        if packet[0] != SYNC_BYTE {
+            bail!("lost MPEG-TS sync");
+        }
+        let unit_start = packet[1] & 0x40 != 0;
+        let pid = (u16::from(packet[1] & 0x1f) << 8) | u16::from(packet[2]);
+        let adaptation = (packet[3] >> 4) & 0x3;
+        let mut payload = &packet[4..];
+        if adaptation & 0x2 != 0 {
+            let len = usize::from(payload[0]);
+            payload = payload.get(1 + len..).unwrap_or_default();
+        }
+        if adaptation & 0x1 == 0 {
+            return Ok(());
+        }
+
+        if pid == 0 {
+            self.parse_pat(section(payload, unit_start)?)?;
+        } else if Some(pid) == self.pmt_pid {
+            self.parse_pmt(section(payload, unit_start)?)?;
+        } else if let Some(index) = self.tracks.iter().position(|t| t.pid == pid) {
+            if unit_start {
+                self.flush_pes(index)?;
+            }
+            self.tracks[index].pes.extend_from_slice(payload);
+        }
+        Ok(())
+    }
+
+    fn parse_pat(&mut self, section: &[u8]) -> eyre::Result<()> {
+        let programs = section_body(section)?;
+        for program in programs.chunks_exact(4) {
+            let number = u16::from_be_bytes([program[0], program[1]]);
+            if number != 0 {
+                self.pmt_pid = Some((u16::from(program[2] & 0x1f) << 8) | u16::from(program[3]));
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_pmt(&mut self, section: &[u8]) -> eyre::Result<()> {
+        let mut streams = pmt_streams(section_body(section)?)?;
+        while streams.len() >= 5 {
+            let stream_type = streams[0];
+            let pid = (u16::from(streams[1] & 0x1f) << 8) | u16::from(streams[2]);
+            let es_info_len = usize::from(u16::from_be_bytes([streams[3], streams[4]]) & 0x0fff);
+            streams = streams.get(5 + es_info_len..).unwrap_or_default();
+
+            let kind = match stream_type {
+                STREAM_TYPE_H264 => TrackKind::Video,
+                STREAM_TYPE_AAC => TrackKind::Audio,
+                _ => continue,
+            };
+            if self.tracks.iter().any(|t| t.pid == pid) {
+                continue;
+            }
+            self.tracks.push(Track {
+                pid,
+                kind,
+                pes: vec![],
+                info: None,
+                samples: vec![],
+                last_ts: None,
+                wrap_offset: 0,
+            });
+        }
+        Ok(())
+    }
+
+    fn flush_pes(&mut self, index: usize) -> eyre::Result<()> {
+        let track = &mut self.tracks[index];
+        if track.pes.is_empty() {
+            return Ok(());
+        }
+        let pes = std::mem::take(&mut track.pes);
+        if pes.len() < 9 || pes[..3] != [0, 0, 1] {
+            bail!("invalid PES start on pid {}", track.pid);
+        }
+        let flags = pes[7] >> 6;
+        let header_len = usize::from(pes[8]);
+        let (header, payload) = pes
+            .get(9..)
+            .filter(|rest| rest.len() >= header_len)
+            .map(|rest| rest.split_at(header_len))
+            .ok_or_eyre("truncated PES header")?;
+        if flags & 0x2 == 0 {
+            bail!("PES without PTS on pid {}", track.pid);
+        }
+        let raw_pts = read_timestamp(header)?;
+        let pts = track.unwrap_ts(raw_pts);
+        let dts = if flags == 0x3 {
+            let raw_dts = read_timestamp(header.get(5..).unwrap_or_default())?;
+            pts.saturating_sub((raw_pts + PTS_WRAP - raw_dts) % PTS_WRAP)
+        } else {
+            pts
+        };
+
+        match track.kind {
+            TrackKind::Video => push_video(track, payload, pts, dts),
+            TrackKind::Audio => push_audio(track, payload, pts),
+        }
+    }
+}
+
+fn section(payload: &[u8], unit_start: bool) -> eyre::Result<&[u8]> {
+    if !unit_start {
+        bail!("PSI sections spanning packets are not supported");
+    }
+    let pointer = usize::from(*payload.first().ok_or_eyre("empty PSI packet")?);
+    payload.get(1 + pointer..).ok_or_eyre("invalid PSI pointer")
+}
+
+/// Strips the generic section header and trailing CRC.
+fn section_body(section: &[u8]) -> eyre::Result<&[u8]> {
+    if section.len() < 3 {
+        bail!("truncated PSI section");
+    }
+    let len = usize::from(u16::from_be_bytes([section[1], section[2]]) & 0x0fff);
+    if len < 9 {
+        bail!("PSI section too short");
+    }
+    section
+        .get(8..3 + len - 4)
+        .ok_or_eyre("PSI section exceeds packet")
+}
+
+/// The elementary stream entries of a PMT section body.
+fn pmt_streams(body: &[u8]) -> eyre::Result<&[u8]> {
+    let info = body.get(2..4).ok_or_eyre("truncated PMT")?;
+    let info_len = usize::from(u16::from_be_bytes([info[0], info[1]]) & 0x0fff);
+    body.get(4 + info_len..).ok_or_eyre("truncated PMT")
+}
+
+fn read_timestamp(b: &[u8]) -> eyre::Result<u64> {
+    if b.len() < 5 {
+        bail!("truncated PES timestamp");
+    }
+    Ok((u64::from(b[0] >> 1) & 0x07) << 30
+        | u64::from(b[1]) << 22
+        | u64::from(b[2] >> 1) << 15
+        | u64::from(b[3]) << 7
+        | u64::from(b[4] >> 1))
+}
+
+fn push_video(track: &mut Track, payload: &[u8], pts: u64, dts: u64) -> eyre::Result<()> {
+    let mut data = vec![];
+    let mut sync = false;
+    let (mut sps, mut pps) = (None, None);
+    for nal in h264::split_annex_b(payload) {
+        match h264::nal_type(nal) {
+            h264::NAL_SPS => sps = Some(nal),
+            h264::NAL_PPS => pps = Some(nal),
+            h264::NAL_AUD => {}
+            kind => {
+                sync |= kind == h264::NAL_IDR;
+                data.extend_from_slice(&u32::try_from(nal.len())?.to_be_bytes());
+                data.extend_from_slice(nal);
+            }
+        }
+    }
+    if track.info.is_none() {
+        if let (Some(sps), Some(pps)) = (sps, pps) {
+            let (width, height) = h264::sps_dimensions(sps)?;
+            track.info = Some(TrackInfo {
+                kind: TrackKind::Video,
+                timescale: 90_000,
+                sample_entry: avc1(width, height, &h264::avcc(sps, pps)?),
+                width,
+                height,
+            });
+        }
+    }
+    if data.is_empty() || track.info.is_none() {
+        return Ok(());
+    }
+    track.samples.push(Sample {
+        data,
+        dts,
+        duration: None,
+        cts_offset: i32::try_from(pts - dts)?,
+        sync,
+    });
+    Ok(())
+}
+
+fn push_audio(track: &mut Track, mut payload: &[u8], pts: u64) -> eyre::Result<()> {
+    let mut frame = 0u64;
+    while payload.len() >= 7 {
+        if payload[0] != 0xff || payload[1] & 0xf0 != 0xf0 {
+            bail!("lost ADTS sync");
+        }
+        let header_len = if payload[1] & 0x1 == 0 { 9 } else { 7 };
+        let object_type = (payload[2] >> 6) + 1;
+        let rate_index = (payload[2] >> 2) & 0xf;
+        let channels = ((payload[2] & 0x1) << 2) | (payload[3] >> 6);
+        let frame_len = (usize::from(payload[3] & 0x3) << 11)
+            | (usize::from(payload[4]) << 3)
+            | (usize::from(payload[5]) >> 5);
+        let rate = *SAMPLE_RATES
+            .get(usize::from(rate_index))
+            .ok_or_else(|| eyre!("invalid AAC sample rate index {rate_index}"))?;
+        let data = payload
+            .get(header_len..frame_len)
+            .ok_or_eyre("truncated ADTS frame")?;
+
+        let info = track.info.get_or_insert_with(|| {
+            let config = (u16::from(object_type) << 11)
+                | (u16::from(rate_index) << 7)
+                | (u16::from(channels) << 3);
+            TrackInfo {
+                kind: TrackKind::Audio,
+                timescale: rate,
+                sample_entry: mp4a(channels.into(), rate, &config.to_be_bytes()),
+                width: 0,
+                height: 0,
+            }
+        });
+        let dts =
+            pts * u64::from(info.timescale) / TS_TIMESCALE + frame * u64::from(AAC_FRAME_SAMPLES);
+        track.samples.push(Sample {
+            data: data.to_vec(),
+            dts,
+            duration: Some(AAC_FRAME_SAMPLES),
+            cts_offset: 0,
+            sync: true,
+        });
+        frame += 1;
+        payload = &payload[frame_len..];
+    }
+    Ok(())
+}
+
+fn avc1(width: u16, height: u16, avcc: &[u8]) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.boxed(b"avc1", |w| {
+        w.zeros(6).u16(1).zeros(16);
+        w.u16(width).u16(height);
+        w.u32(0x0048_0000).u32(0x0048_0000).u32(0).u16(1);
+        w.zeros(32).u16(0x0018).u16(0xffff);
+        w.boxed(b"avcC", |w| {
+            w.bytes(avcc);
+        });
+    });
+    w.0
+}
+
+fn mp4a(channels: u16, rate: u32, config: &[u8]) -> Vec<u8> {
+    // Descriptor lengths all fit a single byte for a 2 byte AudioSpecificConfig.
+    let config_len = u8::try_from(config.len()).expect("short AudioSpecificConfig");
+    let mut w = Writer::default();
+    w.boxed(b"mp4a", |w| {
+        w.zeros(6).u16(1).zeros(8);
+        // 16.16 fixed point, rates above 65535 Hz (88.2/96 kHz) don't fit and
+        // are left to the AudioSpecificConfig like other muxers do.
+        let fixed_rate = u16::try_from(rate).map_or(0, |rate| u32::from(rate) << 16);
+        w.u16(channels).u16(16).u16(0).u16(0).u32(fixed_rate);
+        w.full_boxed(b"esds", 0, 0, |w| {
+            w.u8(0x03).u8(3 + 2 + 13 + 2 + config_len + 3).u16(0).u8(0);
+            w.u8(0x04).u8(13 + 2 + config_len).u8(0x40).u8(0x15);
+            w.zeros(3).u32(0).u32(0);
+            w.u8(0x05).u8(config_len).bytes(config);
+            w.u8(0x06).u8(1).u8(2);
+        });
+    });
+    w.0
+}
+
+/// An MPEG-TS segment cut down by [`trim`].
+pub struct Trimmed {
+    pub data: Vec<u8>,
+    /// Where the kept part starts, in seconds from the segment's first frame.
+    pub start_secs: f64,
+    /// Where the kept part ends, `None` when it runs to the end of the segment.
+    pub end_secs: Option<f64>,
+}
+
+/// Start of a PES packet of one of the program's streams.
+struct Unit {
+    pid: u16,
+    pts: u64,
+    keyframe: bool,
+}
+
+/// Cuts a segment down to `from..to` (seconds from its first frame) without
+/// re-encoding. The kept part starts on the last keyframe at or before `from` and
+/// stops before the first keyframe at or after `to`, so it can hold a little more
+/// than asked for. Returns `None` when there's nothing to cut or no H.264 video to
+/// find keyframes in.
+///
+/// # Errors
+///
+/// When the segment isn't valid MPEG-TS.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+pub fn trim(data: &[u8], from: Option<f64>, to: Option<f64>) -> eyre::Result<Option<Trimmed>> {
+    if !data.len().is_multiple_of(PACKET_SIZE) {
+        bail!("segment size {} is not a multiple of 188", data.len());
+    }
+    let mut pmt_pid = None;
+    let mut video_pid = None;
+    let mut media_pids = vec![];
+    let mut units: Vec<Unit> = vec![];
+    let mut current: Vec<(u16, usize)> = vec![];
+    let mut packet_units = Vec::with_capacity(data.len() / PACKET_SIZE);
+
+    for packet in data.chunks_exact(PACKET_SIZE) {
+        if packet[0] != SYNC_BYTE {
+            bail!("lost MPEG-TS sync");
+        }
+        let unit_start = packet[1] & 0x40 != 0;
+        let pid = (u16::from(packet[1] & 0x1f) << 8) | u16::from(packet[2]);
+        let adaptation = (packet[3] >> 4) & 0x3;
+        let mut payload = &packet[4..];
+        let mut random_access = false;
+        if adaptation & 0x2 != 0 {
+            let len = usize::from(payload[0]);
+            random_access = len > 0 && payload[1] & 0x40 != 0;
+            payload = payload.get(1 + len..).unwrap_or_default();
+        }
+        if adaptation & 0x1 == 0 {
+            packet_units.push(current.iter().find(|(p, _)| *p == pid).map(|(_, u)| *u));
+            continue;
+        }
+
+        if pid == 0 && unit_start {
+            let programs = section_body(section(payload, unit_start)?)?;
+            pmt_pid = programs
+                .chunks_exact(4)
+                .find(|p| u16::from_be_bytes([p[0], p[1]]) != 0)
+                .map(|p| (u16::from(p[2] & 0x1f) << 8) | u16::from(p[3]));
+        } else if Some(pid) == pmt_pid && unit_start {
+            let mut streams = pmt_streams(section_body(section(payload, unit_start)?)?)?;
+            while streams.len() >= 5 {
+                let stream_pid = (u16::from(streams[1] & 0x1f) << 8) | u16::from(streams[2]);
+                if streams[0] == STREAM_TYPE_H264 && video_pid.is_none() {
+                    video_pid = Some(stream_pid);
+                }
+                if !media_pids.contains(&stream_pid) {
+                    media_pids.push(stream_pid);
+                }
+                let es_info_len =
+                    usize::from(u16::from_be_bytes([streams[3], streams[4]]) & 0x0fff);
+                streams = streams.get(5 + es_info_len..).unwrap_or_default();
+            }
+        } else if unit_start
+            && media_pids.contains(&pid)
+            && payload.len() >= 14
+            && payload[..3] == [0, 0, 1]
+            && payload[7] & 0x80 != 0
+        {
+            let header_len = usize::from(payload[8]);
+            let keyframe = Some(pid) == video_pid
+                && (random_access
+                    || h264::split_annex_b(payload.get(9 + header_len..).unwrap_or_default())
+                        .iter()
+                        .any(|nal| matches!(h264::nal_type(nal), h264::NAL_IDR | h264::NAL_SPS)));
+            units.push(Unit {
+                pid,
+                pts: read_timestamp(&payload[9..14])?,
+                keyframe,
+            });
+            current.retain(|(p, _)| *p != pid);
+            current.push((pid, units.len() - 1));
+        }
+        packet_units.push(current.iter().find(|(p, _)| *p == pid).map(|(_, u)| *u));
+    }
+
+    let Some(video_pid) = video_pid else {
+        return Ok(None);
+    };
+    let video: Vec<usize> = (0..units.len())
+        .filter(|i| units[*i].pid == video_pid)
+        .collect();
+    let Some(base) = video.iter().map(|i| units[*i].pts).min() else {
+        return Ok(None);
+    };
+    let relative = |pts: u64| (pts + PTS_WRAP - base) % PTS_WRAP;
+    let ticks = |secs: f64| (secs.max(0.0) * TS_TIMESCALE as f64) as u64;
+
+    // Positions in `video`, which is in decode order.
+    let start = from.filter(|from| *from > 0.0).and_then(|from| {
+        video
+            .iter()
+            .rposition(|i| units[*i].keyframe && relative(units[*i].pts) <= ticks(from))
+            .filter(|pos| *pos > 0)
+    });
+    let end = to.and_then(|to| {
+        video
+            .iter()
+            .enumerate()
+            .skip(start.unwrap_or_default() + 1)
+            .find(|(_, i)| units[**i].keyframe && relative(units[**i].pts) >= ticks(to))
+            .map(|(pos, _)| pos)
+    });
+    if start.is_none() && end.is_none() {
+        return Ok(None);
+    }
+    let start_pts = start.map(|pos| relative(units[video[pos]].pts));
+    let end_pts = end.map(|pos| relative(units[video[pos]].pts));
+
+    let keep_unit = |unit: usize| {
+        if units[unit].pid == video_pid {
+            let pos = video.binary_search(&unit).unwrap_or_default();
+            start.is_none_or(|start| pos >= start) && end.is_none_or(|end| pos < end)
+        } else {
+            let pts = relative(units[unit].pts);
+            start_pts.is_none_or(|start| pts >= start) && end_pts.is_none_or(|end| pts < end)
+        }
+    };
+
+    let mut out = Vec::with_capacity(data.len());
+    let mut counters: Vec<(u16, u8)> = vec![];
+    for (packet, unit) in data.chunks_exact(PACKET_SIZE).zip(packet_units) {
+        let pid = (u16::from(packet[1] & 0x1f) << 8) | u16::from(packet[2]);
+        let keep = match unit {
+            Some(unit) => keep_unit(unit),
+            // Continuations of PES packets that started in an earlier segment.
+            None if media_pids.contains(&pid) => start.is_none(),
+            None => true,
+        };
+        if !keep {
+            continue;
+        }
+        // Continuity counters have to stay gapless for the packets that are left.
+        let mut packet = packet.to_vec();
+        let has_payload = (packet[3] >> 4) & 0x1 != 0;
+        let index = counters
+            .iter()
+            .position(|(p, _)| *p == pid)
+            .unwrap_or_else(|| {
+                counters.push((pid, packet[3] & 0x0f));
+                counters.len() - 1
+            });
+        let counter = &mut counters[index].1;
+        if has_payload {
+            packet[3] = (packet[3] & 0xf0) | *counter;
+            *counter = (*counter + 1) & 0x0f;
+        } else {
+            packet[3] = (packet[3] & 0xf0) | (counter.wrapping_sub(1) & 0x0f);
+        }
+        out.extend_from_slice(&packet);
+    }
+
+    Ok(Some(Trimmed {
+        data: out,
+        start_secs: start_pts.map_or(0.0, |pts| pts as f64 / TS_TIMESCALE as f64),
+        end_secs: end_pts.map(|pts| pts as f64 / TS_TIMESCALE as f64),
+    }))
+}
@@ -0,0 +1,178 @@
+//! Start offsets for HLS playlists (`?t=1h12m`) and the segment markers that let
+//! `/p/stream` remember where a viewer left off.
+
+use m3u8_rs::{MediaPlaylist, Start};
+
+use super::clips;
+
+/// Query parameter carrying a segment's start in milliseconds.
+pub const POSITION_PARAM: &str = "at";
+
+/// Parses offsets like `1h12m`, `90s`, `4350` or `1:12:30` into milliseconds.
+#[must_use]
+pub fn parse_offset(offset: &str) -> Option<u64> {
+    let offset = offset.trim();
+    if !offset.contains(['h', 'm', 's']) {
+        return clips::parse_timestamp(offset);
+    }
+    let mut total = 0.0_f64;
+    let mut number = String::new();
+    for c in offset.chars() {
+        let unit = match c {
+            'h' => 3600.0,
+            'm' => 60.0,
+            's' => 1.0,
+            c if c.is_ascii_digit() || c == '.' => {
+                number.push(c);
+                continue;
+            }
+            _ => return None,
+        };
+        total += number.parse::<f64>().ok()? * unit;
+        number.clear();
+    }
+    if !number.is_empty() {
+        return None;
+    }
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    Some((total * 1000.0).round() as u64)
+}
+
+/// Formats milliseconds the way [`parse_offset`] reads them, e.g. `1h12m5s`.
+#[must_use]
+pub fn format_offset(ms: u64) -> String {
+    let secs = ms / 1000;
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    let mut out = String::new();
+    if hours > 0 {
+        out.push_str(&format!("{hours}h"));
+    }
+    if minutes > 0 {
+        out.push_str(&format!("{minutes}m"));
+    }
+    if seconds > 0 || out.is_empty() {
+        out.push_str(&format!("{seconds}s"));
+    }
+    out
+}
+
+/// Drops the segments that end before `offset_ms` and points `EXT-X-START` at the
+/// rest of the way into the first remaining one. Offsets past the end keep the
+/// last segment so players still get something to play. Also returns how many
+/// milliseconds were dropped from the front.
+#[must_use]
+pub fn start_at(media: &MediaPlaylist, offset_ms: u64) -> (MediaPlaylist, u64) {
+    #[allow(clippy::cast_precision_loss)]
+    let offset = offset_ms as f64 / 1000.0;
+    let mut media = media.clone();
+    let mut position = 0.0_f64;
+    let mut dropped = 0;
+    let mut map = None;
+    for (i, segment) in media.segments.iter().enumerate() {
+        let duration = f64::from(segment.duration);
+        if position + duration > offset || i + 1 == media.segments.len() {
+            break;
+        }
+        if segment.map.is_some() {
+            map.clone_from(&segment.map);
+        }
+        position += duration;
+        dropped += 1;
+    }
+    if dropped == 0 && offset <= 0.0 {
+        return (media, 0);
+    }
+
+    let discontinuities = media.segments[..dropped]
+        .iter()
+        .filter(|s| s.discontinuity)
+        .count();
+    media.segments.drain(..dropped);
+    if let Some(first) = media.segments.first_mut() {
+        if dropped > 0 && first.discontinuity {
+            first.discontinuity = false;
+            media.discontinuity_sequence += 1;
+        }
+        if first.map.is_none() {
+            first.map = map;
+        }
+    }
+    media.media_sequence += dropped as u64;
+    media.discontinuity_sequence += discontinuities as u64;
+    let remainder = offset - position;
+    if remainder > 0.0 {
+        media.start = Some(Start {
+            time_offset: remainder,
+            precise: Some(true),
+            other_attributes: Default::default(),
+        });
+    }
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    (media, (position * 1000.0).round() as u64)
+}
+
+/// Appends `query` and the segment's start within the content to every segment
+/// uri, so fetching a segment tells us how far the viewer got.
+pub fn mark_positions(media: &mut MediaPlaylist, query: &str) {
+    let mut position = 0.0_f64;
+    for segment in &mut media.segments {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let at = (position * 1000.0).round() as u64;
+        let separator = if segment.uri.contains('?') { '&' } else { '?' };
+        segment.uri = format!("{}{separator}{query}&{POSITION_PARAM}={at}", segment.uri);
+        position += f64::from(segment.duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use m3u8_rs::MediaSegment;
+
+    use super::*;
+
+    #[test]
+    fn parses_and_formats_offsets() {
+        assert_eq!(parse_offset("1h12m"), Some(4_320_000));
+        assert_eq!(parse_offset("90s"), Some(90_000));
+        assert_eq!(parse_offset("1m30.5s"), Some(90_500));
+        assert_eq!(parse_offset("4350"), Some(4_350_000));
+        assert_eq!(parse_offset("1:12:00"), Some(4_320_000));
+        assert_eq!(parse_offset("1x"), None);
+        assert_eq!(parse_offset("12h5"), None);
+        assert_eq!(format_offset(4_325_000), "1h12m5s");
+        assert_eq!(format_offset(3_600_000), "1h");
+        assert_eq!(format_offset(400), "0s");
+    }
+
+    #[test]
+    fn starts_playlists_at_offsets() {
+        let media = MediaPlaylist {
+            target_duration: 4,
+            segments: (0..4)
+                .map(|i| MediaSegment {
+                    uri: format!("v0/{i}.ts"),
+                    duration: 4.0,
+                    discontinuity: i == 2,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        let (started, dropped_ms) = start_at(&media, 9_000);
+        assert_eq!(dropped_ms, 8_000);
+        let uris: Vec<_> = started.segments.iter().map(|s| s.uri.as_str()).collect();
+        assert_eq!(uris, ["v0/2.ts", "v0/3.ts"]);
+        assert!(!started.segments[0].discontinuity);
+        assert_eq!(started.media_sequence, 2);
+        assert_eq!(started.discontinuity_sequence, 1);
+        assert!((started.start.as_ref().unwrap().time_offset - 1.0).abs() < 1e-9);
+
+        let (past_end, _) = start_at(&media, 60_000);
+        assert_eq!(past_end.segments.len(), 1);
+
+        let mut marked = media;
+        mark_positions(&mut marked, "token=x&c=1");
+        assert_eq!(marked.segments[1].uri, "v0/1.ts?token=x&c=1&at=4000");
+    }
+}
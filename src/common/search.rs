@@ -0,0 +1,37 @@
+//! Full-text search over the cached libraries, see the generated `search`
+//! columns of `contents` and `libraries`.
+
+/// Results per search, from the cache and from the provider each.
+pub const SEARCH_LIMIT: u64 = 60;
+
+/// Turns what someone typed into a `to_tsquery` matching every word as a
+/// prefix, so results show up while still typing. `None` when nothing
+/// searchable is left, operators and punctuation are dropped.
+#[must_use]
+pub fn prefix_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("{}:*", word.to_lowercase()))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" & "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_every_word_as_a_prefix() {
+        assert_eq!(
+            prefix_query("  The Expanse s01 ").as_deref(),
+            Some("the:* & expanse:* & s01:*")
+        );
+        assert_eq!(prefix_query("Ōkami").as_deref(), Some("ōkami:*"));
+        assert_eq!(
+            prefix_query("don't | !panic").as_deref(),
+            Some("don:* & t:* & panic:*")
+        );
+        assert_eq!(prefix_query(" '&|! "), None);
+    }
+}
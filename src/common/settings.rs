@@ -9,6 +9,17 @@ pub struct Settings {
     pub transcoding_dir: std::path::PathBuf,
     pub ip_source: axum_client_ip::SecureClientIpSource,
     pub file_server_addr: Option<String>,
+    /// Most downloads a user can have transcoding before prefetching holds off,
+    /// [`crate::common::prefetch::DEFAULT_DOWNLOAD_QUOTA`] when unset.
+    #[serde(default)]
+    pub download_quota: Option<u64>,
+    /// Where resized provider images are kept, see [`crate::common::images`].
+    #[serde(default = "default_image_cache_dir")]
+    pub image_cache_dir: std::path::PathBuf,
+}
+
+fn default_image_cache_dir() -> std::path::PathBuf {
+    ".images".into()
 }
 
 impl Settings {
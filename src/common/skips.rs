@@ -0,0 +1,135 @@
+//! Drops skippable parts (intros, credits, recaps) from downloaded HLS playlists.
+//!
+//! The ranges are fetched from the provider when a content is downloaded and kept in
+//! [`SKIP_RANGES_FILE`] next to its playlists.
+
+use std::path::Path;
+
+use m3u8_rs::MediaSegment;
+use players::types::{SkipKind, SkipRange};
+
+pub const SKIP_RANGES_FILE: &str = "skip_ranges.json";
+
+/// Parses a comma separated list of skip kinds, like `intro,outro`.
+///
+/// # Errors
+///
+/// When one of the kinds is unknown.
+pub fn parse_kinds(kinds: &str) -> Result<Vec<SkipKind>, String> {
+    kinds
+        .split(',')
+        .map(str::trim)
+        .filter(|kind| !kind.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Reads the skip ranges stored with a download, downloads without any have none.
+pub async fn load(download_dir: &Path) -> Vec<SkipRange> {
+    let Ok(data) = tokio::fs::read(download_dir.join(SKIP_RANGES_FILE)).await else {
+        return vec![];
+    };
+    serde_json::from_slice(&data).unwrap_or_else(|e| {
+        tracing::warn!(error = ?e, dir = ?download_dir, "Invalid skip ranges");
+        vec![]
+    })
+}
+
+/// Removes the segments whose middle falls inside one of the `ranges` of the given
+/// `kinds`. Playback jumps where segments were removed, so the segment after a cut
+/// starts a discontinuity and carries the init section it would otherwise lose.
+#[must_use]
+pub fn cut(
+    segments: &[MediaSegment],
+    ranges: &[SkipRange],
+    kinds: &[SkipKind],
+) -> Vec<MediaSegment> {
+    let ranges: Vec<&SkipRange> = ranges.iter().filter(|r| kinds.contains(&r.kind)).collect();
+    if ranges.is_empty() {
+        return segments.to_vec();
+    }
+    let mut kept = Vec::with_capacity(segments.len());
+    let mut position = 0.0_f64;
+    let mut map = None;
+    let mut skipped = false;
+    for segment in segments {
+        let duration = f64::from(segment.duration);
+        let middle_ms = (position + duration / 2.0) * 1000.0;
+        position += duration;
+        if segment.map.is_some() {
+            map.clone_from(&segment.map);
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let inside = ranges
+            .iter()
+            .any(|r| (r.start_ms as f64) <= middle_ms && middle_ms < r.end_ms as f64);
+        if inside {
+            skipped = true;
+            continue;
+        }
+        let mut segment = segment.clone();
+        if skipped {
+            segment.discontinuity = !kept.is_empty() || segment.discontinuity;
+            if segment.map.is_none() {
+                segment.map.clone_from(&map);
+            }
+            skipped = false;
+        }
+        kept.push(segment);
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use m3u8_rs::Map;
+
+    use super::*;
+
+    fn segments(count: usize) -> Vec<MediaSegment> {
+        (0..count)
+            .map(|i| MediaSegment {
+                uri: format!("{i}.m4s"),
+                duration: 6.0,
+                map: (i == 0).then(|| Map {
+                    uri: "init.mp4".to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cuts_segments_inside_selected_ranges() {
+        let ranges = [
+            SkipRange {
+                kind: SkipKind::Intro,
+                start_ms: 0,
+                end_ms: 13_000,
+            },
+            SkipRange {
+                kind: SkipKind::Outro,
+                start_ms: 40_000,
+                end_ms: 60_000,
+            },
+        ];
+        let kept = cut(&segments(10), &ranges, &[SkipKind::Intro]);
+
+        let uris: Vec<&str> = kept.iter().map(|s| s.uri.as_str()).collect();
+        assert_eq!(
+            uris,
+            ["2.m4s", "3.m4s", "4.m4s", "5.m4s", "6.m4s", "7.m4s", "8.m4s", "9.m4s"]
+        );
+        // Nothing played before it, so no discontinuity, but the init section moves.
+        assert!(!kept[0].discontinuity);
+        assert_eq!(kept[0].map.as_ref().unwrap().uri, "init.mp4");
+        assert!(kept.iter().skip(1).all(|s| !s.discontinuity));
+
+        let kept = cut(&segments(10), &ranges, &[SkipKind::Intro, SkipKind::Outro]);
+        let uris: Vec<&str> = kept.iter().map(|s| s.uri.as_str()).collect();
+        assert_eq!(uris, ["2.m4s", "3.m4s", "4.m4s", "5.m4s", "6.m4s"]);
+        assert!(parse_kinds("intro, outro").is_ok());
+        assert!(parse_kinds("opening").is_err());
+    }
+}
@@ -0,0 +1,505 @@
+//! Joins downloaded HLS contents into one playlist that plays them back to back.
+//!
+//! Contents rarely share an identical ladder, so variants are matched by resolution
+//! (falling back to bandwidth rank) and every rung of the spliced master points at
+//! one media playlist made of the matching variant of each content.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{DateTime, FixedOffset};
+use eyre::{eyre, OptionExt};
+use m3u8_rs::{
+    DateRange, MasterPlaylist, MediaPlaylist, MediaPlaylistType, QuotedOrUnquoted, Resolution,
+    VariantStream,
+};
+use players::types::{SkipKind, SkipRange};
+
+use super::skips;
+
+/// `CLASS` of the `EXT-X-DATERANGE` tag that marks where a content starts.
+pub const ITEM_DATERANGE_CLASS: &str = "moonlit-binge.item";
+
+/// A downloaded content taking part in a splice.
+pub struct SpliceItem {
+    pub content_id: String,
+    pub title: Option<String>,
+    /// Prefix for the content's segment uris, relative to the spliced media playlists.
+    pub uri_prefix: String,
+    pub master: MasterPlaylist,
+    /// Media playlists of the content, keyed by their uri in `master`.
+    pub media: HashMap<String, MediaPlaylist>,
+    pub skip_ranges: Vec<SkipRange>,
+}
+
+pub struct Spliced {
+    pub master: MasterPlaylist,
+    /// Media playlists keyed by their uri in `master`.
+    pub media: BTreeMap<String, MediaPlaylist>,
+    /// Things that will likely work but might trip up some players.
+    pub warnings: Vec<String>,
+}
+
+/// Splices `items` in order, leaving out the parts that fall in skip ranges of the
+/// `skip` kinds. Program date times start at `start`.
+///
+/// # Errors
+///
+/// When there are no items, an item has no variants or a media playlist of a
+/// picked variant is missing.
+pub fn splice(
+    items: &[SpliceItem],
+    skip: &[SkipKind],
+    start: DateTime<FixedOffset>,
+) -> eyre::Result<Spliced> {
+    if items.is_empty() {
+        return Err(eyre!("Nothing to splice"));
+    }
+    let mut warnings = vec![];
+    let ladders = items
+        .iter()
+        .map(|item| {
+            let mut variants: Vec<&VariantStream> = item
+                .master
+                .variants
+                .iter()
+                .filter(|v| !v.is_i_frame)
+                .collect();
+            variants.sort_by_key(|v| std::cmp::Reverse(v.bandwidth));
+            if variants.is_empty() {
+                return Err(eyre!("{} has no variants", item.content_id));
+            }
+            if !item.master.alternatives.is_empty() {
+                warnings.push(format!(
+                    "{} has alternative renditions, they are left out",
+                    item.content_id
+                ));
+            }
+            Ok(variants)
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    let rungs = common_ladder(&ladders, &mut warnings);
+    let mut master = MasterPlaylist {
+        version: items.iter().filter_map(|item| item.master.version).max(),
+        independent_segments: items.iter().all(|item| item.master.independent_segments),
+        ..Default::default()
+    };
+    let mut media = BTreeMap::new();
+    for (rung_idx, rung) in rungs.iter().enumerate() {
+        let uri = format!("v{rung_idx}.m3u8");
+        master.variants.push(VariantStream {
+            uri: uri.clone(),
+            bandwidth: rung.iter().map(|v| v.bandwidth).max().unwrap_or_default(),
+            average_bandwidth: rung.iter().filter_map(|v| v.average_bandwidth).max(),
+            codecs: merge_codecs(items, rung, &mut warnings),
+            resolution: rung[0].resolution,
+            frame_rate: rung.iter().filter_map(|v| v.frame_rate).reduce(f64::max),
+            ..Default::default()
+        });
+        media.insert(uri, splice_media(items, rung, skip, start)?);
+    }
+
+    // Every rung repeats the warnings of the contents it matched.
+    let mut seen = HashSet::new();
+    warnings.retain(|warning| seen.insert(warning.clone()));
+    for warning in &warnings {
+        tracing::warn!(warning, "Splicing");
+    }
+    Ok(Spliced {
+        master,
+        media,
+        warnings,
+    })
+}
+
+/// Picks one variant of every item per rung. Resolutions all items have in common
+/// make up the ladder, without any the variants are matched by bandwidth rank.
+fn common_ladder<'a>(
+    ladders: &[Vec<&'a VariantStream>],
+    warnings: &mut Vec<String>,
+) -> Vec<Vec<&'a VariantStream>> {
+    let mut common: Vec<Option<Resolution>> = vec![];
+    for variant in &ladders[0] {
+        let resolution = variant.resolution;
+        if !common.contains(&resolution)
+            && ladders[1..]
+                .iter()
+                .all(|ladder| ladder.iter().any(|v| v.resolution == resolution))
+        {
+            common.push(resolution);
+        }
+    }
+
+    if common.is_empty() {
+        warnings.push("No resolution is shared by all contents, matching by bandwidth".to_string());
+        let depth = ladders.iter().map(Vec::len).min().unwrap_or_default();
+        return (0..depth)
+            .map(|rank| ladders.iter().map(|ladder| ladder[rank]).collect())
+            .collect();
+    }
+
+    common
+        .sort_by_key(|resolution| std::cmp::Reverse(resolution.map_or(0, |r| r.width * r.height)));
+    common
+        .into_iter()
+        .map(|resolution| {
+            let first = *ladders[0]
+                .iter()
+                .find(|v| v.resolution == resolution)
+                .expect("resolution comes from the first ladder");
+            ladders
+                .iter()
+                .map(|ladder| {
+                    let candidates = ladder.iter().filter(|v| v.resolution == resolution);
+                    // Ladders are sorted by bandwidth, so this prefers the best match.
+                    candidates
+                        .clone()
+                        .find(|v| v.codecs == first.codecs)
+                        .or_else(|| candidates.clone().next())
+                        .copied()
+                        .unwrap_or(first)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// `CODECS` of a spliced rung. Players pick decoders from it, so it lists the most
+/// demanding profile of each codec. Differing profiles or codecs are reported.
+fn merge_codecs(
+    items: &[SpliceItem],
+    rung: &[&VariantStream],
+    warnings: &mut Vec<String>,
+) -> Option<String> {
+    let mut merged: Vec<String> = vec![];
+    for (item, variant) in items.iter().zip(rung) {
+        let Some(codecs) = &variant.codecs else {
+            continue;
+        };
+        for codec in codecs.split(',').map(str::trim) {
+            let family = codec_family(codec);
+            match merged.iter_mut().find(|c| codec_family(c) == family) {
+                Some(existing) if existing != codec => {
+                    warnings.push(format!(
+                        "{} uses {codec} where other contents use {existing}",
+                        item.content_id
+                    ));
+                    if codec > existing.as_str() {
+                        *existing = codec.to_string();
+                    }
+                }
+                Some(_) => {}
+                None => merged.push(codec.to_string()),
+            }
+        }
+    }
+    let families = |codecs: &Option<String>| -> Vec<String> {
+        let mut families: Vec<String> = codecs
+            .iter()
+            .flat_map(|c| c.split(','))
+            .map(|c| codec_family(c.trim()).to_string())
+            .collect();
+        families.sort();
+        families
+    };
+    if rung
+        .iter()
+        .any(|v| v.codecs.is_some() && families(&v.codecs) != families(&rung[0].codecs))
+    {
+        warnings.push(
+            "Contents use different codecs, some players will stop at the boundary".to_string(),
+        );
+    }
+    (!merged.is_empty()).then(|| merged.join(","))
+}
+
+fn codec_family(codec: &str) -> &str {
+    codec.split('.').next().unwrap_or(codec)
+}
+
+/// Concatenates the media playlists of one rung. Discontinuities are only added
+/// where one content ends and the next starts, each start is tagged with a
+/// program date time and a date range naming the content.
+fn splice_media(
+    items: &[SpliceItem],
+    rung: &[&VariantStream],
+    skip: &[SkipKind],
+    start: DateTime<FixedOffset>,
+) -> eyre::Result<MediaPlaylist> {
+    let mut spliced = MediaPlaylist {
+        end_list: true,
+        playlist_type: Some(MediaPlaylistType::Vod),
+        independent_segments: true,
+        ..Default::default()
+    };
+    let mut offset = 0.0_f64;
+    for (idx, (item, variant)) in items.iter().zip(rung).enumerate() {
+        let media = item.media.get(&variant.uri).ok_or_eyre(format!(
+            "{} is missing media playlist {}",
+            item.content_id, variant.uri
+        ))?;
+        spliced.version = spliced.version.max(media.version);
+        spliced.end_list &= media.end_list;
+        spliced.independent_segments &= media.independent_segments;
+
+        let segments = skips::cut(&media.segments, &item.skip_ranges, skip);
+        let duration: f64 = segments.iter().map(|s| f64::from(s.duration)).sum();
+        #[allow(clippy::cast_possible_truncation)]
+        let started_at = start + chrono::Duration::milliseconds((offset * 1000.0) as i64);
+        for (segment_idx, mut segment) in segments.into_iter().enumerate() {
+            segment.uri = prefix_uri(&item.uri_prefix, &segment.uri);
+            if let Some(map) = segment.map.as_mut() {
+                map.uri = prefix_uri(&item.uri_prefix, &map.uri);
+            }
+            if segment_idx == 0 {
+                segment.discontinuity = idx > 0;
+                segment.program_date_time = Some(started_at);
+                segment.daterange = Some(item_daterange(idx, item, started_at, duration));
+            } else {
+                segment.program_date_time = None;
+                segment.daterange = None;
+            }
+            spliced.segments.push(segment);
+        }
+        offset += duration;
+    }
+    if !spliced.end_list {
+        spliced.playlist_type = None;
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let target_duration = spliced
+        .segments
+        .iter()
+        .map(|s| s.duration.ceil() as u64)
+        .max()
+        .unwrap_or_default();
+    spliced.target_duration = target_duration;
+    Ok(spliced)
+}
+
+fn item_daterange(
+    idx: usize,
+    item: &SpliceItem,
+    started_at: DateTime<FixedOffset>,
+    duration: f64,
+) -> DateRange {
+    let mut attributes = HashMap::from([(
+        "X-CONTENT-ID".to_string(),
+        QuotedOrUnquoted::Quoted(item.content_id.clone()),
+    )]);
+    if let Some(title) = &item.title {
+        // Quoted strings can't hold double quotes or line breaks.
+        let title = title.replace(['"', '\n', '\r'], "'");
+        attributes.insert("X-TITLE".to_string(), QuotedOrUnquoted::Quoted(title));
+    }
+    DateRange {
+        id: format!("item-{idx}"),
+        class: Some(ITEM_DATERANGE_CLASS.to_string()),
+        start_date: started_at,
+        end_date: None,
+        duration: Some(duration),
+        planned_duration: None,
+        x_prefixed: Some(attributes),
+        end_on_next: false,
+        other_attributes: None,
+    }
+}
+
+fn prefix_uri(prefix: &str, uri: &str) -> String {
+    if uri.contains("://") {
+        uri.to_string()
+    } else {
+        format!("{prefix}/{uri}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use m3u8_rs::MediaSegment;
+
+    use super::*;
+
+    fn variant(uri: &str, bandwidth: u64, height: u64, codecs: &str) -> VariantStream {
+        VariantStream {
+            uri: uri.to_string(),
+            bandwidth,
+            codecs: Some(codecs.to_string()),
+            resolution: Some(Resolution {
+                width: height * 16 / 9,
+                height,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn item(content_id: &str, variants: Vec<VariantStream>, durations: &[f32]) -> SpliceItem {
+        let media = variants
+            .iter()
+            .map(|v| {
+                let segments = durations
+                    .iter()
+                    .enumerate()
+                    .map(|(i, duration)| MediaSegment {
+                        uri: format!("{}/{i}.ts", v.uri.trim_end_matches(".m3u8")),
+                        duration: *duration,
+                        ..Default::default()
+                    })
+                    .collect();
+                let playlist = MediaPlaylist {
+                    target_duration: 6,
+                    segments,
+                    end_list: true,
+                    ..Default::default()
+                };
+                (v.uri.clone(), playlist)
+            })
+            .collect();
+        SpliceItem {
+            content_id: content_id.to_string(),
+            title: Some(format!("Episode {content_id}")),
+            uri_prefix: format!("../../single/1/{content_id}"),
+            master: MasterPlaylist {
+                variants,
+                ..Default::default()
+            },
+            media,
+            skip_ranges: vec![SkipRange {
+                kind: SkipKind::Intro,
+                start_ms: 0,
+                end_ms: 4_000,
+            }],
+        }
+    }
+
+    fn start() -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339("2024-07-29T20:00:00+00:00").unwrap()
+    }
+
+    #[test]
+    fn matches_variants_by_resolution() {
+        let items = [
+            item(
+                "a",
+                vec![
+                    variant("hi.m3u8", 5_000_000, 1080, "avc1.640028,mp4a.40.2"),
+                    variant("lo.m3u8", 1_000_000, 480, "avc1.4d401f,mp4a.40.2"),
+                ],
+                &[6.0, 6.0, 2.5],
+            ),
+            item(
+                "b",
+                vec![
+                    variant("main.m3u8", 1_200_000, 480, "avc1.4d401f,mp4a.40.2"),
+                    variant("big.m3u8", 8_000_000, 2160, "avc1.640033,mp4a.40.2"),
+                ],
+                &[4.0, 7.2],
+            ),
+        ];
+        let spliced = splice(&items, &[], start()).unwrap();
+
+        assert_eq!(spliced.master.variants.len(), 1);
+        let rung = &spliced.master.variants[0];
+        assert_eq!(rung.resolution.unwrap().height, 480);
+        assert_eq!(rung.bandwidth, 1_200_000);
+        assert!(spliced.warnings.is_empty(), "{:?}", spliced.warnings);
+
+        let media = &spliced.media[&rung.uri];
+        assert_eq!(media.target_duration, 8);
+        let discontinuities: Vec<bool> = media.segments.iter().map(|s| s.discontinuity).collect();
+        assert_eq!(discontinuities, [false, false, false, true, false]);
+        assert_eq!(media.segments[0].uri, "../../single/1/a/lo/0.ts");
+        assert_eq!(media.segments[3].uri, "../../single/1/b/main/0.ts");
+
+        let second = media.segments[3].daterange.as_ref().unwrap();
+        assert_eq!(second.id, "item-1");
+        assert_eq!(second.class.as_deref(), Some(ITEM_DATERANGE_CLASS));
+        assert_eq!(
+            media.segments[3].program_date_time,
+            Some(start() + chrono::Duration::milliseconds(14_500))
+        );
+    }
+
+    #[test]
+    fn skips_selected_ranges() {
+        let items = [
+            item(
+                "a",
+                vec![variant("a.m3u8", 1_000_000, 480, "avc1.4d401f")],
+                &[6.0, 6.0, 2.5],
+            ),
+            item(
+                "b",
+                vec![variant("b.m3u8", 1_000_000, 480, "avc1.4d401f")],
+                &[4.0, 7.2],
+            ),
+        ];
+        let spliced = splice(&items, &[SkipKind::Intro], start()).unwrap();
+
+        let media = &spliced.media["v0.m3u8"];
+        let uris: Vec<&str> = media.segments.iter().map(|s| s.uri.as_str()).collect();
+        assert_eq!(
+            uris,
+            [
+                "../../single/1/a/a/1.ts",
+                "../../single/1/a/a/2.ts",
+                "../../single/1/b/b/1.ts"
+            ]
+        );
+        assert!(media.segments[2].discontinuity);
+        assert_eq!(
+            media.segments[2].program_date_time,
+            Some(start() + chrono::Duration::milliseconds(8_500))
+        );
+    }
+
+    #[test]
+    fn falls_back_to_bandwidth_rank_and_warns_on_codecs() {
+        let items = [
+            item(
+                "a",
+                vec![variant("a.m3u8", 3_000_000, 720, "avc1.64001f,mp4a.40.2")],
+                &[6.0],
+            ),
+            item(
+                "b",
+                vec![variant("b.m3u8", 2_000_000, 576, "avc1.4d401e,mp4a.40.2")],
+                &[6.0],
+            ),
+        ];
+        let spliced = splice(&items, &[], start()).unwrap();
+
+        assert_eq!(spliced.master.variants.len(), 1);
+        assert_eq!(
+            spliced.master.variants[0].codecs.as_deref(),
+            Some("avc1.64001f,mp4a.40.2")
+        );
+        assert_eq!(spliced.warnings.len(), 2, "{:?}", spliced.warnings);
+    }
+
+    #[test]
+    fn warns_once_per_mismatch() {
+        let items = [
+            item(
+                "a",
+                vec![
+                    variant("a-720.m3u8", 3_000_000, 720, "avc1.64001f,mp4a.40.2"),
+                    variant("a-480.m3u8", 1_500_000, 480, "avc1.64001f,mp4a.40.2"),
+                ],
+                &[6.0],
+            ),
+            item(
+                "b",
+                vec![
+                    variant("b-576.m3u8", 2_000_000, 576, "avc1.4d401e,mp4a.40.5"),
+                    variant("b-360.m3u8", 1_000_000, 360, "avc1.4d401e,mp4a.40.5"),
+                ],
+                &[6.0],
+            ),
+        ];
+        let spliced = splice(&items, &[], start()).unwrap();
+
+        assert_eq!(spliced.master.variants.len(), 2);
+        assert_eq!(spliced.warnings.len(), 3, "{:?}", spliced.warnings);
+    }
+}
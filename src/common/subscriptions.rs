@@ -0,0 +1,109 @@
+//! Following a show or season: new episodes that turn up in a connection's
+//! synced cache are downloaded with the subscription's rules, and announced on
+//! the dashboard like any other download once they're ready.
+
+use loco_rs::{Error, Result};
+use players::types::{Content, ContentKind, Item, LibraryKind};
+use sea_orm::DatabaseConnection;
+
+use crate::{
+    common::{downloads, library_sync},
+    initializers::media_provider::ConnectedMediaProvider,
+    models::{
+        _entities::{player_connections, subscriptions},
+        subscriptions::Rules,
+    },
+};
+
+/// Subscribes to a show or season of the connection. Its episodes are synced
+/// first, so only the ones that come after get downloaded.
+///
+/// # Errors
+///
+/// When the library isn't a show or season, or the provider or database can't
+/// be reached.
+pub async fn subscribe(
+    db: &DatabaseConnection,
+    connection: &player_connections::Model,
+    library_id: &str,
+    rules: &Rules,
+) -> Result<subscriptions::Model> {
+    let provider: ConnectedMediaProvider = connection.clone().try_into()?;
+    let library = match provider.item(library_id).await? {
+        Item::Library(library)
+            if matches!(library.kind, LibraryKind::Show | LibraryKind::Season { .. }) =>
+        {
+            library
+        }
+        _ => return Err(Error::BadRequest("Not a show or season".to_string())),
+    };
+    library_sync::sync_subtree(db, &provider, connection.id, &library.id).await?;
+    Ok(subscriptions::Model::create(db, connection.id, &library.id, &library.name, rules).await?)
+}
+
+fn selection(content: &Content, rules: &Rules) -> downloads::Selection {
+    downloads::Selection {
+        audio_index: downloads::stream_index(content, rules.audio_language.as_deref(), true),
+        subtitle_index: downloads::stream_index(content, rules.subtitle_language.as_deref(), false),
+        media_source_id: None,
+        profile: rules.profile.clone(),
+    }
+}
+
+/// Starts downloads of the new episodes of the connection's subscriptions,
+/// going by what the last sync cached. Returns how many were started.
+///
+/// # Errors
+///
+/// When the provider or database can't be reached, or downloads can't be queued.
+pub async fn download_new(
+    db: &DatabaseConnection,
+    connection: &player_connections::Model,
+) -> Result<i64> {
+    let subscriptions = subscriptions::Model::by_connection(db, connection.id).await?;
+    if subscriptions.is_empty() {
+        return Ok(0);
+    }
+    let provider: ConnectedMediaProvider = connection.clone().try_into()?;
+    let mut total = 0;
+    for subscription in &subscriptions {
+        let rules = subscription.rules();
+        let mut started = 0;
+        for cached in subscription.new_contents(db).await? {
+            // The cache may be missing streams, and the user may have lost access.
+            let content = match provider.item(&cached.content_id).await {
+                Ok(Item::Content(content)) => content,
+                Ok(Item::Library(_)) => continue,
+                Err(e) => {
+                    tracing::warn!(error = ?e, subscription_id = subscription.id, content_id = cached.content_id, "Failed to fetch new episode");
+                    continue;
+                }
+            };
+            if !matches!(content.kind, ContentKind::Episode { .. }) {
+                continue;
+            }
+            let work = downloads::prepare(
+                db,
+                connection,
+                &provider,
+                &content,
+                &selection(&content, &rules),
+            )
+            .await?;
+            if let Some(work) = work {
+                provider.provider.queue_download(work).await?;
+            }
+            started += 1;
+        }
+        subscriptions::Model::record_check(db, subscription.id, started).await?;
+        if started > 0 {
+            tracing::info!(
+                subscription_id = subscription.id,
+                started,
+                "Downloading new episodes"
+            );
+        }
+        total += started;
+    }
+    Ok(total)
+}
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+
+/// The settings that make two downloads of the same content differ. Downloads are
+/// stored under `single/<connection>/<content>/<variant id>/`, or under
+/// `shared/<key>/` (see [`shared_key`]) when they come from the shared cache.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Default)]
+pub struct Variant {
+    pub profile: Option<String>,
+    pub media_source_id: Option<String>,
+    pub audio_index: Option<i32>,
+    pub subtitle_index: Option<i32>,
+}
+
+/// Path segment that stream links can use instead of a variant id to get the most
+/// recent finished variant.
+pub const DEFAULT_VARIANT: &str = "default";
+
+impl Variant {
+    /// Stable id of the variant, the same settings always produce the same id.
+    #[must_use]
+    pub fn id(&self) -> String {
+        let canonical = format!(
+            "{}\0{}\0{}\0{}",
+            self.profile.as_deref().unwrap_or_default(),
+            self.media_source_id.as_deref().unwrap_or_default(),
+            self.audio_index.map(|i| i.to_string()).unwrap_or_default(),
+            self.subtitle_index
+                .map(|i| i.to_string())
+                .unwrap_or_default(),
+        );
+        fnv1a(&canonical)
+    }
+
+    /// Short human readable description for the dashboard.
+    #[must_use]
+    pub fn label(&self) -> String {
+        let mut parts = vec![self
+            .profile
+            .clone()
+            .unwrap_or_else(|| "Default".to_string())];
+        if let Some(index) = self.audio_index {
+            parts.push(format!("audio #{index}"));
+        }
+        if let Some(index) = self.subtitle_index {
+            parts.push(format!("subtitles #{index}"));
+        }
+        if let Some(source) = &self.media_source_id {
+            let short: String = source.chars().take(8).collect();
+            parts.push(format!("source {short}"));
+        }
+        parts.join(" · ")
+    }
+}
+
+/// Key of a transcode in the shared cache. Everything that changes the produced
+/// segments is part of it, so users of the same provider asking for the same
+/// variant of a content end up with the same key.
+#[must_use]
+pub fn shared_key(media_provider_id: &str, content_id: &str, variant_id: &str) -> String {
+    fnv1a(&format!("{media_provider_id}\0{content_id}\0{variant_id}"))
+}
+
+// FNV-1a, `DefaultHasher` isn't guaranteed to be stable across releases.
+fn fnv1a(canonical: &str) -> String {
+    let hash = canonical
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_only_depend_on_the_settings() {
+        let variant = Variant {
+            profile: Some("quest".to_string()),
+            media_source_id: None,
+            audio_index: Some(1),
+            subtitle_index: None,
+        };
+        assert_eq!(variant.id(), variant.clone().id());
+        assert_eq!(variant.id().len(), 16);
+        // Fields can't bleed into each other.
+        let moved = Variant {
+            audio_index: None,
+            subtitle_index: Some(1),
+            ..variant.clone()
+        };
+        assert_ne!(variant.id(), moved.id());
+        assert_ne!(variant.id(), Variant::default().id());
+
+        assert_ne!(
+            shared_key("jf", "movie", &variant.id()),
+            shared_key("other-jf", "movie", &variant.id())
+        );
+    }
+
+    #[test]
+    fn labels_describe_the_variant() {
+        assert_eq!(Variant::default().label(), "Default");
+        let variant = Variant {
+            profile: Some("quest".to_string()),
+            media_source_id: Some("ディレクターズカット版".to_string()),
+            audio_index: Some(1),
+            subtitle_index: Some(3),
+        };
+        assert_eq!(
+            variant.label(),
+            "quest · audio #1 · subtitles #3 · source ディレクターズカ"
+        );
+    }
+}
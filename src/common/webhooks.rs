@@ -0,0 +1,221 @@
+//! Events pushed by the jellyfin webhook plugin, keeping cached libraries up to
+//! date between syncs. Deleted items are tombstoned, added and updated ones are
+//! fetched again with every connection's own credentials, and watch progress
+//! saved on other clients is taken over.
+
+use loco_rs::Result;
+use players::types::Item;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    common::subscriptions,
+    initializers::media_provider::ConnectedMediaProvider,
+    models::_entities::{contents, libraries, player_connections},
+};
+
+/// Jellyfin counts time in ticks of 100ns.
+const TICKS_PER_MS: i64 = 10_000;
+
+/// The fields of the webhook plugin's default template that matter here, the
+/// rest are ignored.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct JellyfinEvent {
+    pub notification_type: String,
+    #[serde(default)]
+    pub item_id: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub playback_position_ticks: Option<i64>,
+    #[serde(default)]
+    pub played: Option<bool>,
+}
+
+impl JellyfinEvent {
+    /// Whether handling the event asks the provider for the item, too slow to
+    /// keep the webhook waiting.
+    #[must_use]
+    pub fn fetches_items(&self) -> bool {
+        matches!(self.notification_type.as_str(), "ItemAdded" | "ItemUpdated")
+    }
+}
+
+/// What an event changed.
+#[derive(Debug, Default, Serialize)]
+pub struct Outcome {
+    /// Connections the event applied to.
+    pub connections: usize,
+    /// Cached items fetched again or taking over watch progress.
+    pub updated: usize,
+    /// Cached items tombstoned.
+    pub removed: u64,
+}
+
+/// Applies an event of a jellyfin provider to its connections' caches.
+///
+/// # Errors
+///
+/// When the database can't be reached.
+pub async fn handle_jellyfin(
+    db: &DatabaseConnection,
+    media_provider_id: &str,
+    event: &JellyfinEvent,
+) -> Result<Outcome> {
+    let mut outcome = Outcome::default();
+    let Some(item_id) = event.item_id.as_deref() else {
+        tracing::debug!(event.notification_type, "Ignoring webhook without an item");
+        return Ok(outcome);
+    };
+    // Jellyfin formats ids without dashes in webhooks, its API and so the cache
+    // with them.
+    let Ok(item_id) = Uuid::parse_str(item_id) else {
+        tracing::debug!(
+            event.notification_type,
+            item_id,
+            "Ignoring webhook with an invalid item id"
+        );
+        return Ok(outcome);
+    };
+    let item_id = item_id.hyphenated().to_string();
+    let item_id = item_id.as_str();
+    let connections =
+        player_connections::Model::by_media_provider(db, Some(media_provider_id)).await?;
+    match event.notification_type.as_str() {
+        "ItemDeleted" => {
+            let ids: Vec<i32> = connections.iter().map(|connection| connection.id).collect();
+            outcome.connections = ids.len();
+            outcome.removed += libraries::Model::tombstone(db, &ids, item_id).await?;
+            outcome.removed += contents::Model::tombstone(db, &ids, item_id).await?;
+        }
+        "UserDataSaved" => {
+            let Some(user_id) = event.user_id.as_deref() else {
+                return Ok(outcome);
+            };
+            let position_ms = event
+                .playback_position_ticks
+                .map(|ticks| ticks / TICKS_PER_MS);
+            for connection in &connections {
+                if !is_provider_user(connection, user_id) {
+                    continue;
+                }
+                outcome.connections += 1;
+                let updated = contents::Model::apply_user_data(
+                    db,
+                    connection.id,
+                    item_id,
+                    position_ms,
+                    event.played.unwrap_or_default(),
+                )
+                .await?;
+                outcome.updated += usize::from(updated);
+            }
+        }
+        "ItemAdded" | "ItemUpdated" => {
+            let added = event.notification_type == "ItemAdded";
+            for connection in &connections {
+                outcome.connections += 1;
+                match refresh_item(db, connection, item_id, added).await {
+                    Ok(updated) => outcome.updated += usize::from(updated),
+                    Err(e) => {
+                        tracing::warn!(error = ?e, connection_id = connection.id, item_id, "Failed to refresh item");
+                        continue;
+                    }
+                }
+                if added {
+                    if let Err(e) = subscriptions::download_new(db, connection).await {
+                        tracing::error!(error = ?e, connection_id = connection.id, "Failed to download new episodes");
+                    }
+                }
+            }
+        }
+        other => tracing::debug!(notification_type = other, "Ignoring webhook"),
+    }
+    Ok(outcome)
+}
+
+/// Jellyfin formats ids without dashes in webhooks, the API may not.
+fn is_provider_user(connection: &player_connections::Model, user_id: &str) -> bool {
+    let Ok(provider) = ConnectedMediaProvider::try_from(connection.clone()) else {
+        return false;
+    };
+    provider.provider_user_id().is_some_and(|id| {
+        id.replace('-', "")
+            .eq_ignore_ascii_case(&user_id.replace('-', ""))
+    })
+}
+
+/// Whether the connection cached the listing of `library_id`.
+async fn is_listing_cached(
+    db: &DatabaseConnection,
+    connection_id: i32,
+    library_id: &str,
+) -> Result<bool> {
+    Ok(
+        !libraries::Model::ids_by_connection_and_parent_id(db, connection_id, library_id)
+            .await?
+            .is_empty()
+            || !contents::Model::ids_by_connection_and_parent_id(db, connection_id, library_id)
+                .await?
+                .is_empty(),
+    )
+}
+
+/// Fetches an item again for a connection that cached it, or that cached the
+/// library an added item went into. Returns whether the cache changed, items
+/// the connection's user can't see are left alone.
+async fn refresh_item(
+    db: &DatabaseConnection,
+    connection: &player_connections::Model,
+    item_id: &str,
+    added: bool,
+) -> Result<bool> {
+    let provider: ConnectedMediaProvider = connection.clone().try_into()?;
+    let item = match provider.item(item_id).await {
+        Ok(item) => item,
+        Err(e) => {
+            tracing::debug!(error = ?e, connection_id = connection.id, item_id, "Item not visible to connection");
+            return Ok(false);
+        }
+    };
+    match &item {
+        Item::Library(library) => {
+            let parent_id =
+                match libraries::Model::find_by_connection_and_id(db, connection.id, item_id).await
+                {
+                    Ok(cached) => cached.parent_id,
+                    Err(_) if added => library.parent_id.clone(),
+                    Err(_) => return Ok(false),
+                };
+            let Some(parent_id) = parent_id else {
+                return Ok(false);
+            };
+            if !is_listing_cached(db, connection.id, &parent_id).await? {
+                return Ok(false);
+            }
+            libraries::Model::upsert_cache_data(db, connection.id, &[library], Some(&parent_id))
+                .await?;
+        }
+        Item::Content(content) => {
+            let parent_id =
+                match contents::Model::by_connection_and_id(db, connection.id, item_id).await {
+                    // Updates don't bring back what a sync tombstoned.
+                    Ok(cached) if cached.removed_at.is_some() => return Ok(false),
+                    Ok(cached) => cached.parent_id,
+                    Err(_) if added => content.parent_id.clone(),
+                    Err(_) => return Ok(false),
+                };
+            let Some(parent_id) = parent_id else {
+                return Ok(false);
+            };
+            if !is_listing_cached(db, connection.id, &parent_id).await? {
+                return Ok(false);
+            }
+            contents::Model::upsert_cache_data(db, connection.id, &[content], Some(&parent_id))
+                .await?;
+        }
+    }
+    Ok(true)
+}
@@ -8,27 +8,33 @@ use axum::{
     },
     Extension,
 };
-use axum_htmx::HxRequest;
+use axum_extra::extract::Form;
+use axum_htmx::{HxRefresh, HxRequest};
 use futures_util::{Stream, StreamExt};
 use loco_rs::prelude::*;
-use players::types::{Content, Item};
+use players::types::{Content, Item, Shelf};
 use sea_orm::{Order, QueryOrder};
+use serde::Deserialize;
 use serde_json::json;
 use tokio::sync::Mutex;
 
 use crate::{
+    common::{pagination::SortOrder, prefetch},
     initializers::{
         media_provider::{ConnectedMediaProvider, MediaProviders},
         view_engine::BetterTeraView,
     },
     models::{
-        _entities::{player_connections, users},
+        _entities::{clips as clip_models, player_connections, users},
         content_downloads::Notification,
     },
     views,
 };
 
-use super::extractors::{auth::JWTWithUser, Format};
+use super::extractors::{auth::JWTWithUser, Format, ProtoHost};
+
+/// Most clips listed on the home page.
+const HOME_CLIPS: u64 = 12;
 
 /// Renders the dashboard home page
 ///
@@ -46,7 +52,99 @@ pub async fn render_home(
         .filter(player_connections::Column::UserId.eq(auth.user.id))
         .all(&ctx.db)
         .await?;
-    views::dashboard::home(f, &connections)
+    let clips = clip_models::Model::recent_by_user(&ctx.db, auth.user.id, HOME_CLIPS).await?;
+    views::dashboard::home(f, &auth.user, &connections, &clips)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PrefetchParams {
+    episodes: i32,
+}
+
+/// Sets how many of the next episodes to download when the user starts one.
+///
+/// # Errors
+///
+/// When the count is out of range or the database can't be reached
+pub async fn update_prefetch(
+    State(ctx): State<AppContext>,
+    auth: JWTWithUser<users::Model>,
+    Form(params): Form<PrefetchParams>,
+) -> Result<Response> {
+    if !(0..=prefetch::MAX_PREFETCH_EPISODES).contains(&params.episodes) {
+        return Err(Error::BadRequest(format!(
+            "Prefetch between 0 and {} episodes",
+            prefetch::MAX_PREFETCH_EPISODES
+        )));
+    }
+    auth.user
+        .into_active_model()
+        .set_prefetch_episodes(&ctx.db, params.episodes)
+        .await?;
+    format::text("Saved")
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LibrarySortParams {
+    sort: SortOrder,
+}
+
+/// Sets how the user's library listings are sorted, then reloads the page to
+/// show the listing in its new order.
+///
+/// # Errors
+///
+/// When the database can't be reached
+pub async fn update_library_sort(
+    State(ctx): State<AppContext>,
+    auth: JWTWithUser<users::Model>,
+    Form(params): Form<LibrarySortParams>,
+) -> Result<Response> {
+    auth.user
+        .into_active_model()
+        .set_library_sort(&ctx.db, params.sort)
+        .await?;
+    Ok((HxRefresh(true), "Saved").into_response())
+}
+
+/// Items per shelf on the home page.
+const SHELF_LIMIT: usize = 12;
+
+/// Renders a connection's shelves, loaded separately so a slow provider doesn't
+/// hold up the home page. Shelves that fail to load are left out.
+///
+/// # Errors
+///
+/// When the user has no such connection or render fails
+pub async fn render_shelves(
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+    ViewEngine(v): ViewEngine<BetterTeraView>,
+    ProtoHost(host): ProtoHost,
+    auth: JWTWithUser<users::Model>,
+) -> Result<Response> {
+    let connection =
+        player_connections::Model::find_by_user_and_id(&ctx.db, auth.user.id, id).await?;
+    let provider: ConnectedMediaProvider = connection.clone().try_into()?;
+    let loaded = futures_util::future::join_all(Shelf::ALL.map(|shelf| {
+        player_connections::Model::shelf(&ctx.db, &provider, connection.id, shelf, SHELF_LIMIT)
+    }))
+    .await;
+    let mut shelves = vec![];
+    for (shelf, items) in Shelf::ALL.into_iter().zip(loaded) {
+        match items {
+            Ok(items) if !items.is_empty() => shelves.push(json!({"shelf": shelf, "items": items})),
+            Ok(_) => {}
+            Err(e) => {
+                tracing::warn!(error = ?e, connection_id = connection.id, ?shelf, "Failed to load shelf");
+            }
+        }
+    }
+    format::render().view(
+        &v,
+        "dashboard/shelves.html",
+        json!({"provider": &provider.provider, "connection": &connection, "shelves": shelves, "protohost": host}),
+    )
 }
 
 pub async fn notify_sub(
@@ -168,5 +266,8 @@ pub fn routes() -> Routes {
     Routes::new()
         .add("/", get(render_home))
         .add("/sub", get(notify_sub))
+        .add("/shelves/:id", get(render_shelves))
+        .add("/prefetch", post(update_prefetch))
+        .add("/library_sort", post(update_library_sort))
     // .add("/pub", get(notify_pub))
 }
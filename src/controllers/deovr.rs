@@ -0,0 +1,78 @@
+//! [DeoVR](https://deovr.com) remote selection, so Quest users can browse and play
+//! finished downloads without a browser. The player logs in with a login and
+//! password form, the password being the user's API key.
+
+use axum::debug_handler;
+use axum_extra::extract::Form;
+use loco_rs::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    controllers::extractors::ProtoHost,
+    models::{
+        _entities::{contents, users},
+        contents::CatalogScope,
+    },
+    views::deovr::{self, Selection},
+};
+
+#[derive(Debug, Default, Deserialize)]
+struct Login {
+    #[serde(default)]
+    password: Option<String>,
+}
+
+async fn authenticate(ctx: &AppContext, login: Option<Login>) -> Result<Option<users::Model>> {
+    let Some(api_key) = login
+        .and_then(|login| login.password)
+        .filter(|key| !key.is_empty())
+    else {
+        return Ok(None);
+    };
+    match users::Model::find_by_api_key(&ctx.db, &api_key).await {
+        Ok(user) => Ok(Some(user)),
+        Err(ModelError::EntityNotFound) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[debug_handler]
+async fn index(
+    State(ctx): State<AppContext>,
+    ProtoHost(host): ProtoHost,
+    login: Option<Form<Login>>,
+) -> Result<Response> {
+    let Some(user) = authenticate(&ctx, login.map(|Form(login)| login)).await? else {
+        return format::json(Selection::unauthorized());
+    };
+    let entries = contents::Model::downloaded_by_user(&ctx.db, user.id, CatalogScope::All).await?;
+    format::json(deovr::selection(&host, &entries))
+}
+
+#[debug_handler]
+async fn video(
+    Path((connection_id, content_id)): Path<(i32, String)>,
+    State(ctx): State<AppContext>,
+    ProtoHost(host): ProtoHost,
+    login: Option<Form<Login>>,
+) -> Result<Response> {
+    let Some(user) = authenticate(&ctx, login.map(|Form(login)| login)).await? else {
+        return format::json(Selection::unauthorized());
+    };
+    let entry = contents::Model::downloaded_by_user(
+        &ctx.db,
+        user.id,
+        CatalogScope::Content(connection_id, &content_id),
+    )
+    .await?
+    .pop()
+    .ok_or(Error::NotFound)?;
+    format::json(deovr::video(&host, &entry))
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("deovr")
+        .add("/", get(index).post(index))
+        .add("/:connection/:content", get(video).post(video))
+}
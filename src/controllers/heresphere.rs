@@ -0,0 +1,170 @@
+//! [HereSphere](https://heresphere.com) JSON API, so the player can browse and play
+//! finished downloads. The player logs in with a username and password, the
+//! password being the user's API key.
+
+use axum::{body::Bytes, debug_handler};
+use axum_extra::extract::Query;
+use loco_rs::prelude::*;
+use players::types::UserData;
+use serde::{de::DeserializeOwned, Deserialize};
+use uuid::Uuid;
+
+use crate::{
+    common::playback,
+    controllers::{extractors::ProtoHost, player_connections::token_connection},
+    initializers::media_provider::ConnectedMediaProvider,
+    models::{
+        _entities::{contents, users},
+        contents::{CatalogEntry, CatalogScope},
+    },
+    views::heresphere::{self, Unauthorized, VERSION, VERSION_HEADER},
+};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Credentials {
+    #[serde(default)]
+    password: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VideoParams {
+    #[serde(flatten)]
+    credentials: Credentials,
+    /// Set when the user toggled the favorite button.
+    #[serde(default)]
+    is_favorite: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EventParams {
+    /// Url of the video, as listed in the library.
+    id: String,
+    event: i32,
+    /// Playback position in milliseconds.
+    time: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct EventQuery {
+    token: Uuid,
+}
+
+/// `event` sent when a video is closed.
+const EVENT_CLOSE: i32 = 3;
+
+/// The player posts an empty body until the user has logged in.
+fn parse_body<T: DeserializeOwned + Default>(body: &Bytes) -> Result<T> {
+    if body.is_empty() {
+        return Ok(T::default());
+    }
+    serde_json::from_slice(body).map_err(|e| Error::BadRequest(e.to_string()))
+}
+
+async fn authenticate(ctx: &AppContext, credentials: &Credentials) -> Result<Option<users::Model>> {
+    let Some(api_key) = credentials
+        .password
+        .as_deref()
+        .filter(|key| !key.is_empty())
+    else {
+        return Ok(None);
+    };
+    match users::Model::find_by_api_key(&ctx.db, api_key).await {
+        Ok(user) => Ok(Some(user)),
+        Err(ModelError::EntityNotFound) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn respond<T: serde::Serialize>(body: &T) -> Result<Response> {
+    format::render().header(VERSION_HEADER, VERSION).json(body)
+}
+
+#[debug_handler]
+async fn index(
+    State(ctx): State<AppContext>,
+    ProtoHost(host): ProtoHost,
+    body: Bytes,
+) -> Result<Response> {
+    let credentials: Credentials = parse_body(&body)?;
+    let Some(user) = authenticate(&ctx, &credentials).await? else {
+        return respond(&Unauthorized::default());
+    };
+    let entries = contents::Model::downloaded_by_user(&ctx.db, user.id, CatalogScope::All).await?;
+    respond(&heresphere::index(&host, &entries))
+}
+
+#[debug_handler]
+async fn video(
+    Path((connection_id, content_id)): Path<(i32, String)>,
+    State(ctx): State<AppContext>,
+    ProtoHost(host): ProtoHost,
+    body: Bytes,
+) -> Result<Response> {
+    let params: VideoParams = parse_body(&body)?;
+    let Some(user) = authenticate(&ctx, &params.credentials).await? else {
+        return respond(&Unauthorized::default());
+    };
+    let entry: CatalogEntry = contents::Model::downloaded_by_user(
+        &ctx.db,
+        user.id,
+        CatalogScope::Content(connection_id, &content_id),
+    )
+    .await?
+    .pop()
+    .ok_or(Error::NotFound)?;
+
+    let provider: ConnectedMediaProvider = entry.connection.clone().try_into()?;
+    if let Some(favorite) = params.is_favorite {
+        provider.set_favorite(&content_id, favorite).await?;
+    }
+    let user_data = match provider.user_data(&content_id).await {
+        Ok(user_data) => user_data,
+        Err(e) => {
+            tracing::warn!(error = ?e, content_id, "Failed to fetch user data");
+            UserData::default()
+        }
+    };
+    respond(&heresphere::video(&host, &entry, &user_data))
+}
+
+/// Playback events, sent to the `eventServer` of a video.
+#[debug_handler]
+async fn event(
+    State(ctx): State<AppContext>,
+    Query(query): Query<EventQuery>,
+    body: Bytes,
+) -> Result<Response> {
+    // Sent without a JSON content type.
+    let params: EventParams =
+        serde_json::from_slice(&body).map_err(|e| Error::BadRequest(e.to_string()))?;
+    let connection = token_connection(&ctx, query.token).await?;
+    let mut parts = params.id.trim_end_matches('/').rsplitn(3, '/');
+    let (Some(content_id), Some(id)) = (parts.next(), parts.next()) else {
+        return Err(Error::BadRequest("Unknown video".to_string()));
+    };
+    if id != connection.id.to_string() {
+        return Err(Error::BadRequest("Unknown video".to_string()));
+    }
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let position_ms = params.time.max(0.0) as u64;
+    let content_id = content_id.to_string();
+    playback::track(
+        &ctx.db,
+        connection,
+        &content_id,
+        position_ms,
+        params.event == EVENT_CLOSE,
+    )
+    .await;
+    format::empty()
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("heresphere")
+        .add("/", post(index))
+        .add("/event", post(event))
+        .add("/:connection/:content", post(video))
+}
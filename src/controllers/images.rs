@@ -0,0 +1,95 @@
+//! Images of libraries and contents at `/images/<connection>/<item>?width=`, for
+//! the signed in user or players holding the connection's stream token.
+
+use axum::{
+    debug_handler,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+};
+use axum_extra::extract::Query;
+use loco_rs::prelude::*;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::{extractors::auth::JWTWithUser, player_connections::token_connection};
+use crate::{
+    common::images,
+    initializers::media_provider::ConnectedMediaProvider,
+    models::_entities::{player_connections, users},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct ImageQuery {
+    width: Option<u32>,
+    /// Stream token of the connection, for players that can't sign in.
+    token: Option<Uuid>,
+}
+
+#[debug_handler]
+async fn show(
+    Path((connection_id, item_id)): Path<(i32, String)>,
+    State(ctx): State<AppContext>,
+    Query(query): Query<ImageQuery>,
+    headers: HeaderMap,
+    auth: Option<JWTWithUser<users::Model>>,
+) -> Result<Response> {
+    let connection = match (query.token, auth) {
+        (Some(token), _) => {
+            let connection = token_connection(&ctx, token).await?;
+            if connection.id != connection_id {
+                return unauthorized("Invalid stream token");
+            }
+            connection
+        }
+        (None, Some(auth)) => {
+            player_connections::Model::find_by_user_and_id(&ctx.db, auth.user.id, connection_id)
+                .await?
+        }
+        (None, None) => return unauthorized("unauthorized!"),
+    };
+    let width = images::snap_width(query.width);
+    // Served from the cache first, so the provider isn't asked for the item.
+    let image = if let Some(image) = images::cached(connection.id, &item_id, width).await? {
+        image
+    } else {
+        let provider: ConnectedMediaProvider = connection.clone().try_into()?;
+        let item = player_connections::Model::item(&ctx.db, &provider, &connection, &item_id)
+            .await
+            .map_err(|e| match e {
+                ModelError::EntityNotFound => Error::NotFound,
+                e => e.into(),
+            })?;
+        let path = item.icon_url().ok_or(Error::NotFound)?;
+        images::fetch(&provider, connection.id, &item_id, path, width).await?
+    };
+
+    let etag = HeaderValue::from_str(&image.etag).map_err(|e| Error::Message(e.to_string()))?;
+    let cache_control = HeaderValue::from_static("private, max-age=86400");
+    let unchanged = headers
+        .get(header::IF_NONE_MATCH)
+        .is_some_and(|given| given == etag);
+    if unchanged {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (header::CACHE_CONTROL, cache_control)],
+        )
+            .into_response());
+    }
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(image.content_type),
+            ),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        image.bytes,
+    )
+        .into_response())
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("images")
+        .add("/:connection/:item", get(show))
+}
@@ -3,6 +3,12 @@ pub mod middlewares;
 
 pub mod auth;
 pub mod dashboard;
+pub mod deovr;
+pub mod heresphere;
+pub mod images;
 pub mod player_connections;
 pub mod playlist;
+pub mod short_links;
+pub mod subscriptions;
 pub mod user;
+pub mod webhooks;
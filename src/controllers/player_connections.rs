@@ -1,29 +1,47 @@
 #![allow(clippy::missing_errors_doc)]
 #![allow(clippy::unnecessary_struct_initialization)]
 #![allow(clippy::unused_async)]
-use axum::{body::Bytes, debug_handler, Extension};
+use axum::{
+    body::{Body, Bytes},
+    debug_handler,
+    extract::Request,
+    response::Redirect,
+    Extension,
+};
 use axum_extra::extract::{Form, Query};
 use axum_htmx::HxRequest;
 use loco_rs::prelude::*;
-use players::types::{Item, Library, MediaStream};
+use players::types::{Item, Library, SkipKind, SkipRange, Stereo, VrLayout};
 
 use crate::{
     controllers::extractors::{auth::JWTWithUser, ProtoHost},
-    models::_entities::{contents, player_connections},
+    models::_entities::{content_downloads, contents, player_connections},
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::IntoDeserializer, Deserialize, Serialize};
+use tower::ServiceExt;
+use tower_http::services::ServeDir;
+use uuid::Uuid;
 
 use crate::{
+    common::{
+        clips, downloads, library_sync, pagination, playback, resume, settings::SETTINGS, skips,
+        subscriptions, variants::DEFAULT_VARIANT,
+    },
     initializers::{
         media_provider::{ConnectedMediaProvider, MediaProviders},
         view_engine::BetterTeraView,
     },
-    models::_entities::{
-        player_connections::{ActiveModel, Entity, Model},
-        users,
+    models::{
+        _entities::{
+            clips as clip_models, player_connections::ActiveModel,
+            sea_orm_active_enums::StatusName, users,
+        },
+        contents::CatalogScope,
+    },
+    views::{
+        self,
+        exports::{self, ExportOptions},
     },
-    views,
-    workers::downloader::{DownloadWorker, DownloadWorkerArgs},
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -38,11 +56,6 @@ pub struct SetupParams {
     pub setup: Option<String>,
 }
 
-async fn load_item(ctx: &AppContext, id: i32) -> Result<Model> {
-    let item = Entity::find_by_id(id).one(&ctx.db).await?;
-    item.ok_or_else(|| Error::NotFound)
-}
-
 #[debug_handler]
 pub async fn new(
     ViewEngine(v): ViewEngine<BetterTeraView>,
@@ -111,6 +124,8 @@ pub async fn add(
 #[derive(Deserialize)]
 pub struct LibraryQuery {
     force: Option<bool>,
+    /// Cursor of the previous page, see [`pagination::Cursor`].
+    after: Option<String>,
 }
 
 #[debug_handler]
@@ -121,13 +136,15 @@ pub async fn show(
     State(ctx): State<AppContext>,
     ProtoHost(host): ProtoHost,
     auth: JWTWithUser<users::Model>,
-    Query(LibraryQuery { force }): Query<LibraryQuery>,
+    Query(LibraryQuery { force, .. }): Query<LibraryQuery>,
 ) -> Result<Response> {
-    let (connection, provider, _, items) = player_connections::Model::library_and_items(
+    let (connection, provider, _, page) = player_connections::Model::library_and_items(
         &ctx.db,
         auth.user.id,
         id,
         None,
+        auth.user.library_sort(),
+        None,
         force.is_some(),
     )
     .await?;
@@ -135,10 +152,59 @@ pub async fn show(
         &v,
         boosted,
         "show",
-        &serde_json::json!({"provider": &provider.provider, "connection": &connection, "items": items, "protohost": host}),
+        &serde_json::json!({"provider": &provider.provider, "connection": &connection, "items": page.items, "protohost": host, "sync": connection.sync_status(), "syncing": connection.is_syncing(chrono::Utc::now().naive_utc())}),
+    )
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchQuery {
+    #[serde(default)]
+    q: String,
+}
+
+/// Libraries and contents matching `?q=`, selectable into the transcode form
+/// like a library's items.
+#[debug_handler]
+pub async fn search(
+    Path(id): Path<i32>,
+    ViewEngine(v): ViewEngine<BetterTeraView>,
+    HxRequest(boosted): HxRequest,
+    State(ctx): State<AppContext>,
+    ProtoHost(host): ProtoHost,
+    auth: JWTWithUser<users::Model>,
+    Query(SearchQuery { q }): Query<SearchQuery>,
+) -> Result<Response> {
+    let (connection, provider, items) =
+        player_connections::Model::search(&ctx.db, auth.user.id, id, q.trim()).await?;
+    views::player_connections::base_view(
+        &v,
+        boosted,
+        "show",
+        &serde_json::json!({"provider": &provider.provider, "connection": &connection, "items": items, "query": q, "protohost": host}),
     )
 }
 
+/// Starts syncing the connection's libraries in the background, then downloads
+/// new episodes of its subscriptions.
+#[debug_handler]
+pub async fn sync(
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+    auth: JWTWithUser<users::Model>,
+) -> Result<Response> {
+    let connection =
+        player_connections::Model::find_by_user_and_id(&ctx.db, auth.user.id, id).await?;
+    tokio::task::spawn(async move {
+        if let Err(e) = library_sync::sync_connection(&ctx.db, &connection).await {
+            tracing::error!(error = ?e, connection_id = connection.id, "Failed to record library sync");
+        }
+        if let Err(e) = subscriptions::download_new(&ctx.db, &connection).await {
+            tracing::error!(error = ?e, connection_id = connection.id, "Failed to download new episodes");
+        }
+    });
+    format::text("Syncing, reload in a bit")
+}
+
 #[debug_handler]
 pub async fn show_library(
     Path((id, library)): Path<(i32, String)>,
@@ -147,21 +213,34 @@ pub async fn show_library(
     State(ctx): State<AppContext>,
     ProtoHost(host): ProtoHost,
     auth: JWTWithUser<users::Model>,
-    Query(LibraryQuery { force }): Query<LibraryQuery>,
+    Query(LibraryQuery { force, after }): Query<LibraryQuery>,
 ) -> Result<Response> {
-    let (connection, provider, parent, items) = player_connections::Model::library_and_items(
+    let after: Option<pagination::Cursor> = after
+        .map(|after| after.parse())
+        .transpose()
+        .map_err(Error::BadRequest)?;
+    let sort = auth.user.library_sort();
+    let (connection, provider, parent, page) = player_connections::Model::library_and_items(
         &ctx.db,
         auth.user.id,
         id,
         Some(&library),
+        sort,
+        after.as_ref(),
         force.is_some(),
     )
     .await?;
+    // Infinite scroll only asks for the next items, not the whole page.
+    let action = if boosted && after.is_some() {
+        "items"
+    } else {
+        "show"
+    };
     views::player_connections::base_view(
         &v,
         boosted,
-        "show",
-        &serde_json::json!({"provider": &provider.provider, "connection": &connection, "parent": parent, "items": items, "protohost": host}),
+        action,
+        &serde_json::json!({"provider": &provider.provider, "connection": &connection, "parent": parent, "items": page.items, "next": page.next.map(|next| next.to_string()), "sort": sort, "sort_orders": pagination::SortOrder::ALL, "protohost": host}),
     )
 }
 
@@ -176,9 +255,12 @@ pub async fn transcode(
     ViewEngine(v): ViewEngine<BetterTeraView>,
     HxRequest(boosted): HxRequest,
     State(ctx): State<AppContext>,
+    auth: JWTWithUser<users::Model>,
     Query(data): Query<TranscodeInitParams>,
 ) -> Result<Response> {
-    let connection = load_item(&ctx, connection_id).await?;
+    let connection =
+        player_connections::Model::find_by_user_and_id(&ctx.db, auth.user.id, connection_id)
+            .await?;
     let provider: ConnectedMediaProvider = connection.clone().try_into()?;
     let mut items = vec![];
     for content_id in data.content_ids {
@@ -202,50 +284,53 @@ pub struct TranscodeStartParams {
     preferred_audio_streams: Vec<i32>,
     #[serde(rename = "preferred_subtitle")]
     preferred_subtitle_streams: Vec<i32>,
+    #[serde(default, rename = "media_source")]
+    media_sources: Vec<String>,
     profile: Option<String>,
 }
 
 pub async fn transcode_start(
     Path(connection_id): Path<i32>,
     State(ctx): State<AppContext>,
+    auth: JWTWithUser<users::Model>,
     Form(data): Form<TranscodeStartParams>,
 ) -> Result<Response> {
-    let connection = load_item(&ctx, connection_id).await?;
+    let connection =
+        player_connections::Model::find_by_user_and_id(&ctx.db, auth.user.id, connection_id)
+            .await?;
+    // Every content needs its streams picked, checked before anything is prepared.
+    let selections = (0..data.contents.len())
+        .map(|i| {
+            let (Some(&audio_index), Some(&subtitle_index)) = (
+                data.preferred_audio_streams.get(i),
+                data.preferred_subtitle_streams.get(i),
+            ) else {
+                return Err(Error::BadRequest(
+                    "Missing stream preferences for a content".to_string(),
+                ));
+            };
+            Ok(downloads::Selection {
+                audio_index,
+                subtitle_index,
+                media_source_id: data.media_sources.get(i).cloned(),
+                profile: data.profile.clone(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
     let provider: ConnectedMediaProvider = connection.clone().try_into()?;
     let mut work = vec![];
-    for (i, content) in data.contents.iter().enumerate() {
-        let item = provider.item(&content).await?;
+    for (content, selection) in data.contents.iter().zip(&selections) {
+        // Asked with the user's own credentials, so this also checks that they can
+        // access the content before it's handed out from the shared cache.
+        let item = provider.item(content).await?;
 
         match item {
             Item::Content(content) => {
-                let mut streams = vec![];
-                let preferred_audio_stream = data.preferred_audio_streams[i];
-                let preferred_subtitle_stream = data.preferred_subtitle_streams[i];
-                for stream in &content.media_streams {
-                    match stream {
-                        MediaStream::Audio { index, .. } if index == &preferred_audio_stream => {
-                            streams.push(stream.clone());
-                        }
-                        MediaStream::Subtitle { index, .. }
-                            if index == &preferred_subtitle_stream =>
-                        {
-                            streams.push(stream.clone());
-                        }
-                        _ => {}
-                    }
+                if let Some(args) =
+                    downloads::prepare(&ctx.db, &connection, &provider, &content, selection).await?
+                {
+                    work.push(args);
                 }
-                let (_, download) =
-                    contents::Model::start_download(&ctx.db, connection.id, &content.id)
-                        .await
-                        .unwrap();
-                work.push(DownloadWorkerArgs {
-                    user_id: connection.user_id,
-                    connection_id: connection.id,
-                    content_download_id: download.id,
-                    profile: data.profile.clone(),
-                    content: content.clone(),
-                    preferred_mediastreams: streams,
-                });
             }
             Item::Library(_) => {
                 return Err(Error::BadRequest("Not a content item".to_string()));
@@ -260,12 +345,211 @@ pub async fn transcode_start(
     Ok(Response::new("k".into()))
 }
 
+#[derive(Deserialize, Debug)]
+pub struct ClipParams {
+    name: String,
+    /// `[[hh:]mm:]ss[.fff]` into the content.
+    start: String,
+    end: String,
+    /// Variant to cut from, defaults to the most recent finished one.
+    #[serde(default)]
+    variant: Option<String>,
+}
+
+/// Cuts a clip out of a finished HLS download and responds with its stream url.
+#[debug_handler]
+pub async fn clip_create(
+    Path((connection_id, content_id)): Path<(i32, String)>,
+    State(ctx): State<AppContext>,
+    ProtoHost(host): ProtoHost,
+    auth: JWTWithUser<users::Model>,
+    Form(params): Form<ClipParams>,
+) -> Result<Response> {
+    // Makes sure the user owns the connection.
+    player_connections::Model::find_by_user_and_id(&ctx.db, auth.user.id, connection_id).await?;
+    let (Some(start_ms), Some(end_ms)) = (
+        clips::parse_timestamp(&params.start),
+        clips::parse_timestamp(&params.end),
+    ) else {
+        return Err(Error::BadRequest("Invalid timestamp".to_string()));
+    };
+    if end_ms <= start_ms {
+        return Err(Error::BadRequest(
+            "The clip ends before it starts".to_string(),
+        ));
+    }
+    let name = params.name.trim();
+    if name.is_empty() {
+        return Err(Error::BadRequest("The clip needs a name".to_string()));
+    }
+    let download = content_downloads::Model::find_variant(
+        &ctx.db,
+        connection_id,
+        &content_id,
+        params.variant.as_deref().filter(|v| !v.is_empty()),
+    )
+    .await
+    .map_err(|e| match e {
+        ModelError::EntityNotFound => Error::BadRequest("Content is not downloaded".to_string()),
+        e => e.into(),
+    })?;
+    if download.status != StatusName::Success {
+        return Err(Error::BadRequest(
+            "The download hasn't finished".to_string(),
+        ));
+    }
+
+    let settings = SETTINGS
+        .get()
+        .ok_or_else(|| Error::Message("Settings not initialized".to_string()))?;
+    let download_dir = settings.transcoding_dir.join(download.storage_path());
+    if !tokio::fs::try_exists(download_dir.join("main.m3u8")).await? {
+        return Err(Error::BadRequest(
+            "Only HLS downloads can be clipped".to_string(),
+        ));
+    }
+
+    let to_i64 = |ms: u64| i64::try_from(ms).map_err(|e| Error::BadRequest(e.to_string()));
+    let clip =
+        clip_models::Model::create(&ctx.db, &download, name, to_i64(start_ms)?, to_i64(end_ms)?)
+            .await?;
+    let clip_path = clip.storage_path();
+    let clip_dir = settings.transcoding_dir.join(&clip_path);
+    let uri_prefix = format!("../../{}", download.storage_path());
+    #[allow(clippy::cast_precision_loss)]
+    let (start, end) = (start_ms as f64 / 1000.0, end_ms as f64 / 1000.0);
+    let built = tokio::task::spawn_blocking(move || {
+        clips::build(&download_dir, &clip_dir, &uri_prefix, start, end)
+    })
+    .await
+    .map_err(|e| Error::Message(e.to_string()))?;
+
+    match built {
+        Ok(duration) => {
+            tracing::info!(clip = %clip.id, duration, "Built clip");
+            clip_models::Model::set_status(&ctx.db, clip.id, StatusName::Success).await?;
+            format::text(&format!("{host}/p/stream/{clip_path}/main.m3u8"))
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, clip = %clip.id, "Failed to build clip");
+            clip_models::Model::set_status(&ctx.db, clip.id, StatusName::Error).await?;
+            Err(Error::BadRequest(e.to_string()))
+        }
+    }
+}
+
+/// A library or season's finished downloads as a playlist for world video
+/// players.
+#[debug_handler]
+pub async fn export(
+    Path((connection_id, library_id)): Path<(i32, String)>,
+    State(ctx): State<AppContext>,
+    ProtoHost(host): ProtoHost,
+    auth: JWTWithUser<users::Model>,
+    Query(options): Query<ExportOptions>,
+) -> Result<Response> {
+    let catalog = contents::Model::downloaded_by_user(
+        &ctx.db,
+        auth.user.id,
+        CatalogScope::Library(connection_id, &library_id),
+    )
+    .await?;
+    let entries = exports::entries(&host, &catalog, options.mp4);
+    exports::render(options.format, &entries)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LayoutParams {
+    /// Empty to go back to the detected layout.
+    #[serde(default)]
+    projection: String,
+    #[serde(default)]
+    stereo: String,
+}
+
+fn parse_layout<'de, T: Deserialize<'de>>(value: &'de str) -> Result<T> {
+    T::deserialize(value.into_deserializer())
+        .map_err(|e: serde::de::value::Error| Error::BadRequest(e.to_string()))
+}
+
+/// Overrides the VR layout of a content and responds with its new label.
+#[debug_handler]
+pub async fn layout_update(
+    Path((connection_id, content_id)): Path<(i32, String)>,
+    State(ctx): State<AppContext>,
+    auth: JWTWithUser<users::Model>,
+    Form(params): Form<LayoutParams>,
+) -> Result<Response> {
+    // Makes sure the user owns the connection.
+    player_connections::Model::find_by_user_and_id(&ctx.db, auth.user.id, connection_id).await?;
+    let layout = if params.projection.is_empty() {
+        None
+    } else {
+        Some(VrLayout {
+            projection: parse_layout(&params.projection)?,
+            stereo: if params.stereo.is_empty() {
+                Stereo::default()
+            } else {
+                parse_layout(&params.stereo)?
+            },
+        })
+    };
+    let content =
+        contents::Model::set_layout_override(&ctx.db, connection_id, &content_id, layout).await?;
+    format::text(&content.content.layout.label())
+}
+
+pub async fn download_remove(
+    Path((connection_id, download_id)): Path<(i32, Uuid)>,
+    State(ctx): State<AppContext>,
+    auth: JWTWithUser<users::Model>,
+) -> Result<Response> {
+    // Makes sure the user owns the connection.
+    player_connections::Model::find_by_user_and_id(&ctx.db, auth.user.id, connection_id).await?;
+    let settings = SETTINGS
+        .get()
+        .ok_or_else(|| Error::Message("Settings not initialized".to_string()))?;
+    // Shared transcodes are left to the `shared_gc` task.
+    for dir in content_downloads::Model::remove(&ctx.db, connection_id, download_id).await? {
+        let dir = settings.transcoding_dir.join(dir);
+        match tokio::fs::remove_dir_all(&dir).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::error!(error = ?e, ?dir, "Failed to remove download"),
+        }
+    }
+    format::empty()
+}
+
 pub async fn stream(
     Path(path): Path<String>,
     State(ctx): State<AppContext>,
-) -> Result<impl IntoResponse> {
+    request: Request,
+) -> Result<Response> {
+    if let Some(resolved) = resolve_default_variant(&ctx, &path).await? {
+        let query = request
+            .uri()
+            .query()
+            .map(|q| format!("?{q}"))
+            .unwrap_or_default();
+        return Ok(Redirect::temporary(&format!("/p/stream/{resolved}{query}")).into_response());
+    }
+    let params = axum::extract::Query::<StreamParams>::try_from_uri(request.uri())
+        .map_err(|e| Error::BadRequest(e.to_string()))?
+        .0;
+    if let (Some(token), Some(content_id), Some(at)) = (params.token, &params.c, params.at) {
+        record_position(&ctx, token, content_id, at).await;
+    }
+    if !path.ends_with(".m3u8") && !path.ends_with(".ts") {
+        return serve_file(&path, request).await;
+    }
     let p = std::path::Path::new(&path);
-    let body: Vec<u8> = ctx.storage.download(p).await?;
+    let mut body: Vec<u8> = ctx.storage.download(p).await?;
+    if path.ends_with(".m3u8")
+        && (params.skip.is_some() || params.t.is_some() || params.token.is_some())
+    {
+        body = rewrite_playlist(&ctx, p, &body, &params).await?;
+    }
     let content_type = if path.ends_with(".ts") {
         "video/mp2t"
     } else if path.ends_with(".m3u8") {
@@ -277,7 +561,197 @@ pub async fn stream(
     Ok((
         axum::response::AppendHeaders([("content-type", content_type)]),
         Bytes::from(body),
-    ))
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamParams {
+    /// Comma separated skip kinds to leave out of HLS playlists, e.g. `intro,outro`.
+    skip: Option<String>,
+    /// Where playback should start, e.g. `1h12m` or `1:12:00`.
+    t: Option<String>,
+    /// The connection's stream token, fetches carrying it update the watch progress.
+    token: Option<Uuid>,
+    /// Content and position of a segment, added to segment uris by [`resume::mark_positions`].
+    c: Option<String>,
+    at: Option<u64>,
+}
+
+impl StreamParams {
+    /// The options a master playlist passes on to its media playlists, rebuilt
+    /// from the parsed values so nothing else ends up in the playlist.
+    fn variant_query(&self, skip: Option<&[SkipKind]>) -> String {
+        let encode = |value: &str| {
+            percent_encoding::utf8_percent_encode(value, percent_encoding::NON_ALPHANUMERIC)
+                .to_string()
+        };
+        let mut query = vec![];
+        if let Some(skip) = skip {
+            let skip: Vec<String> = skip.iter().map(ToString::to_string).collect();
+            query.push(format!("skip={}", skip.join(",")));
+        }
+        if let Some(t) = &self.t {
+            query.push(format!("t={}", encode(t)));
+        }
+        if let Some(token) = self.token {
+            query.push(format!("token={token}"));
+        }
+        query.join("&")
+    }
+}
+
+/// Applies the stream options to a playlist. Master playlists pass them on to their
+/// media playlists, which get trimmed to the start offset, cut around skip ranges
+/// and have their segments marked for resume tracking.
+async fn rewrite_playlist(
+    ctx: &AppContext,
+    path: &std::path::Path,
+    body: &[u8],
+    params: &StreamParams,
+) -> Result<Vec<u8>> {
+    let kinds = params
+        .skip
+        .as_deref()
+        .map(skips::parse_kinds)
+        .transpose()
+        .map_err(Error::BadRequest)?;
+    let playlist = m3u8_rs::parse_playlist_res(body)
+        .map_err(|e| Error::Message(format!("Invalid playlist: {e}")))?;
+    let mut out = vec![];
+    let mut media = match playlist {
+        m3u8_rs::Playlist::MasterPlaylist(mut master) => {
+            let query = params.variant_query(kinds.as_deref());
+            for variant in &mut master.variants {
+                variant.uri = format!("{}?{query}", variant.uri);
+            }
+            master.write_to(&mut out)?;
+            return Ok(out);
+        }
+        m3u8_rs::Playlist::MediaPlaylist(media) => media,
+    };
+    let dir = path.parent().unwrap_or(path);
+
+    // Positions are marked first so they stay relative to the whole content.
+    if let Some(token) = params.token {
+        let connection = token_connection(ctx, token).await?;
+        let content_id = content_downloads::Model::content_for_storage_dir(
+            &ctx.db,
+            connection.id,
+            &dir.to_string_lossy(),
+        )
+        .await?;
+        if let Some(content_id) = content_id {
+            let query = format!(
+                "token={token}&c={}",
+                percent_encoding::utf8_percent_encode(
+                    &content_id,
+                    percent_encoding::NON_ALPHANUMERIC
+                )
+            );
+            resume::mark_positions(&mut media, &query);
+            let duration: f64 = media.segments.iter().map(|s| f64::from(s.duration)).sum();
+            #[allow(clippy::cast_possible_truncation)]
+            let duration_ms = (duration * 1000.0).round() as i64;
+            contents::Model::record_duration(&ctx.db, connection.id, &content_id, duration_ms)
+                .await?;
+        }
+    }
+
+    let mut dropped_ms = 0;
+    if let Some(t) = &params.t {
+        let offset =
+            resume::parse_offset(t).ok_or_else(|| Error::BadRequest(format!("Invalid t: {t}")))?;
+        (media, dropped_ms) = resume::start_at(&media, offset);
+    }
+
+    if let Some(kinds) = kinds {
+        let settings = SETTINGS
+            .get()
+            .ok_or_else(|| Error::Message("Settings not initialized".to_string()))?;
+        // Skip ranges are in content time, the playlist may now start later.
+        let ranges: Vec<SkipRange> = skips::load(&settings.transcoding_dir.join(dir))
+            .await
+            .into_iter()
+            .map(|range| SkipRange {
+                start_ms: range.start_ms.saturating_sub(dropped_ms),
+                end_ms: range.end_ms.saturating_sub(dropped_ms),
+                ..range
+            })
+            .collect();
+        media.segments = skips::cut(&media.segments, &ranges, &kinds);
+    }
+
+    media.write_to(&mut out)?;
+    Ok(out)
+}
+
+/// The connection a stream token belongs to, for endpoints players call without
+/// a session.
+pub async fn token_connection(ctx: &AppContext, token: Uuid) -> Result<player_connections::Model> {
+    player_connections::Model::find_by_stream_token(&ctx.db, token)
+        .await
+        .map_err(|e| match e {
+            ModelError::EntityNotFound => Error::Unauthorized("Invalid stream token".to_string()),
+            e => e.into(),
+        })
+}
+
+/// Moves the watch progress of the token's connection to a fetched segment.
+async fn record_position(ctx: &AppContext, token: Uuid, content_id: &str, at: u64) {
+    match player_connections::Model::find_by_stream_token(&ctx.db, token).await {
+        Ok(connection) => playback::track(&ctx.db, connection, content_id, at, false).await,
+        Err(e) => tracing::warn!(error = ?e, content_id, "Unknown stream token"),
+    }
+}
+
+/// Rewrites `single/<connection>/<content>/default/...` to the dir of the most recent
+/// finished variant, so links stay valid when a content is downloaded again.
+async fn resolve_default_variant(ctx: &AppContext, path: &str) -> Result<Option<String>> {
+    let mut parts = path.splitn(5, '/');
+    let (Some("single"), Some(connection_id), Some(content_id), Some(DEFAULT_VARIANT), rest) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return Ok(None);
+    };
+    let connection_id = connection_id
+        .parse()
+        .map_err(|e: std::num::ParseIntError| Error::BadRequest(e.to_string()))?;
+    let base =
+        content_downloads::Model::variant_path(&ctx.db, connection_id, content_id, None).await?;
+    Ok(Some(format!("{base}/{}", rest.unwrap_or("main.m3u8"))))
+}
+
+/// Whether a file in the transcoding dir is only there for us, like downloads still
+/// being written (`.part`) or the json kept next to playlists.
+fn is_sidecar(path: &str) -> bool {
+    std::path::Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("part") || ext.eq_ignore_ascii_case("json"))
+}
+
+/// Serves large files straight from the transcoding dir, `ServeDir` takes care of
+/// range requests (so players can seek) and keeps paths inside the dir.
+async fn serve_file(path: &str, request: Request) -> Result<Response> {
+    if is_sidecar(path) {
+        return Err(Error::NotFound);
+    }
+    let settings = SETTINGS
+        .get()
+        .ok_or_else(|| Error::Message("Settings not initialized".to_string()))?;
+    let (mut parts, body) = request.into_parts();
+    parts.uri = format!("/{path}")
+        .parse()
+        .map_err(|e: axum::http::uri::InvalidUri| Error::BadRequest(e.to_string()))?;
+    let response = ServeDir::new(&settings.transcoding_dir)
+        .oneshot(Request::from_parts(parts, body))
+        .await
+        .map_err(|e| Error::Message(e.to_string()))?;
+    Ok(response.map(Body::new))
 }
 
 pub fn routes() -> Routes {
@@ -287,6 +761,12 @@ pub fn routes() -> Routes {
         .add("/:id", get(show))
         .add("/:id/:library", get(show_library))
         .add("/:id/transcode", get(transcode).post(transcode_start))
+        .add("/:id/sync", post(sync))
+        .add("/:id/search", get(search))
+        .add("/:id/downloads/:download", delete(download_remove))
+        .add("/:id/:content/clips", post(clip_create))
+        .add("/:id/:content/layout", post(layout_update))
+        .add("/:id/:library/export", get(export))
         .add("/setup", post(setup))
         .add("/", post(add))
         .add("/stream/*path", get(stream))
@@ -1,115 +1,186 @@
 use std::collections::HashMap;
 
 use axum::debug_handler;
+use axum_extra::extract::Query;
 use loco_rs::prelude::*;
+use players::types::SkipKind;
 use serde::{Deserialize, Serialize};
 
-use crate::common::settings::SETTINGS;
+use crate::{
+    common::{
+        settings::SETTINGS,
+        skips,
+        splice::{self, SpliceItem},
+    },
+    controllers::extractors::{auth::JWTWithUser, ProtoHost},
+    models::{
+        _entities::{content_downloads, contents, users},
+        contents::CatalogScope,
+    },
+    views::exports::{self, ExportOptions},
+};
+
+/// Contents of a spliced playlist, stored next to its `main.m3u8`.
+const CONTENTS_FILE: &str = "contents.json";
 
 #[derive(Debug, Deserialize, Serialize)]
 struct PlaylistCreateParams {
     name: String,
     contents: Vec<SingleContent>,
+    /// Parts to leave out of every content, e.g. `["intro", "outro"]`.
+    #[serde(default)]
+    skip: Vec<SkipKind>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 struct SingleContent {
     connection: i32,
     content_id: String,
+    /// Variant id, defaults to the most recent finished one.
+    #[serde(default)]
+    variant: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct SpliceResponse {
+    /// Compatibility issues between the contents that didn't stop the splice.
+    warnings: Vec<String>,
 }
 
 #[debug_handler]
 async fn splice(
     // auth: auth::JWTWithUser<users::Model>,
-    State(_ctx): State<AppContext>,
+    State(ctx): State<AppContext>,
     Json(params): Json<PlaylistCreateParams>,
 ) -> Result<impl IntoResponse> {
     // validate the playlists exist...
     let transcoding_base_path = &SETTINGS.get().unwrap().transcoding_dir;
-    let single_base_path = transcoding_base_path.join("single");
+    let mut content_paths = vec![];
     for content in &params.contents {
-        let dir = tokio::fs::try_exists(
-            single_base_path
-                .join(format!("{}", content.connection))
-                .join(&content.content_id)
-                .join("main.m3u8"),
+        let path = content_downloads::Model::variant_path(
+            &ctx.db,
+            content.connection,
+            &content.content_id,
+            content.variant.as_deref(),
         )
-        .await?;
+        .await
+        .map_err(|e| match e {
+            ModelError::EntityNotFound => Error::BadRequest("Content does not exist".to_string()),
+            e => e.into(),
+        })?;
+        let dir =
+            tokio::fs::try_exists(transcoding_base_path.join(&path).join("main.m3u8")).await?;
         if !dir {
             return Err(Error::BadRequest("Content does not exist".to_string()));
         }
+        content_paths.push(path);
     }
 
-    let mut spliced_master: Option<m3u8_rs::MasterPlaylist> = None;
-    let mut spliced_media: Option<HashMap<String, m3u8_rs::MediaPlaylist>> = None;
-
-    for content in params.contents {
-        let single_content_dir = single_base_path
-            .join(format!("{}", content.connection))
-            .join(&content.content_id);
+    let mut items = vec![];
+    for (content, content_path) in params.contents.iter().zip(content_paths) {
+        let single_content_dir = transcoding_base_path.join(&content_path);
         let playlist = tokio::fs::read(single_content_dir.join("main.m3u8")).await?;
-        let manifest = m3u8_rs::parse_master_playlist_res(&playlist).unwrap();
-        spliced_master = match spliced_master {
-            Some(spliced_master) => {
-                if spliced_master.variants.len() != manifest.variants.len() {
-                    return Err(Error::BadRequest("Media count mismatch".to_string()));
-                }
-                Some(spliced_master)
-            }
-            None => Some(manifest.clone()),
-        };
-
-        for variant in manifest.variants {
+        let master = m3u8_rs::parse_master_playlist_res(&playlist)
+            .map_err(|e| Error::Message(format!("Invalid playlist for {content_path}: {e}")))?;
+        let mut media = HashMap::new();
+        for variant in master.variants.iter().filter(|v| !v.is_i_frame) {
             let playlist = tokio::fs::read(single_content_dir.join(&variant.uri)).await?;
-            let mut media = m3u8_rs::parse_media_playlist_res(&playlist).unwrap();
-            let name = variant.uri.strip_suffix(".m3u8").unwrap();
-
-            for segment in &mut media.segments {
-                segment.uri = format!(
-                    "../../single/{}/{}/{}",
-                    content.connection, content.content_id, segment.uri
-                );
-                segment.discontinuity = true;
-            }
-
-            spliced_media = match spliced_media {
-                Some(mut spliced_media) => {
-                    if let Some(spliced_media) = spliced_media.get_mut(name) {
-                        // media.segments.first_mut().unwrap().discontinuity = true;
-                        spliced_media.segments.extend(media.segments);
-                    } else {
-                        return Err(Error::BadRequest("Missing media playlist".to_string()));
-                    }
-                    Some(spliced_media)
-                }
-                None => {
-                    let mut map = HashMap::new();
-                    map.insert(name.to_string(), media);
-                    Some(map)
-                }
-            };
+            let playlist = m3u8_rs::parse_media_playlist_res(&playlist)
+                .map_err(|e| Error::Message(format!("Invalid playlist for {content_path}: {e}")))?;
+            media.insert(variant.uri.clone(), playlist);
         }
+        let title = contents::Entity::find_by_id((content.connection, content.content_id.clone()))
+            .one(&ctx.db)
+            .await?
+            .and_then(|c| c.cached_data)
+            .and_then(|data| data.get("name")?.as_str().map(ToString::to_string));
+        items.push(SpliceItem {
+            content_id: content.content_id.clone(),
+            title,
+            uri_prefix: format!("../../{content_path}"),
+            master,
+            media,
+            skip_ranges: skips::load(&single_content_dir).await,
+        });
     }
 
+    let spliced = splice::splice(&items, &params.skip, chrono::Utc::now().fixed_offset())
+        .map_err(|e| Error::BadRequest(e.to_string()))?;
+
     let playlist_base_path = transcoding_base_path.join("playlist").join(params.name);
     tokio::fs::create_dir_all(&playlist_base_path).await?;
     let mut v: Vec<u8> = Vec::new();
-    spliced_master
-        .expect("Missing spliced main file")
-        .write_to(&mut v)
-        .unwrap();
+    spliced.master.write_to(&mut v)?;
     tokio::fs::write(playlist_base_path.join("main.m3u8"), v).await?;
 
-    for (name, media) in &mut spliced_media.expect("Missing spliced media files") {
+    for (uri, media) in &spliced.media {
         let mut v: Vec<u8> = Vec::new();
-        media.write_to(&mut v).unwrap();
-        tokio::fs::write(playlist_base_path.join(format!("{name}.m3u8")), v).await?;
+        media.write_to(&mut v)?;
+        tokio::fs::write(playlist_base_path.join(uri), v).await?;
+    }
+    // Kept so the playlist can be exported entry by entry later on.
+    tokio::fs::write(
+        playlist_base_path.join(CONTENTS_FILE),
+        serde_json::to_vec(&params.contents)?,
+    )
+    .await?;
+    format::json(SpliceResponse {
+        warnings: spliced.warnings,
+    })
+}
+
+/// Exports a saved playlist's contents as separate entries for world video
+/// players. Contents on connections of other users are left out.
+#[debug_handler]
+async fn export(
+    Path(name): Path<String>,
+    State(ctx): State<AppContext>,
+    ProtoHost(host): ProtoHost,
+    auth: JWTWithUser<users::Model>,
+    Query(options): Query<ExportOptions>,
+) -> Result<Response> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(Error::BadRequest("Invalid playlist name".to_string()));
+    }
+    let transcoding_base_path = &SETTINGS
+        .get()
+        .ok_or_else(|| Error::Message("Settings not initialized".to_string()))?
+        .transcoding_dir;
+    let saved = transcoding_base_path
+        .join("playlist")
+        .join(&name)
+        .join(CONTENTS_FILE);
+    if !tokio::fs::try_exists(&saved).await? {
+        return Err(Error::NotFound);
+    }
+    let contents: Vec<SingleContent> = serde_json::from_slice(&tokio::fs::read(saved).await?)?;
+
+    let mut catalog = vec![];
+    for content in &contents {
+        let Some(mut entry) = contents::Model::downloaded_by_user(
+            &ctx.db,
+            auth.user.id,
+            CatalogScope::Content(content.connection, &content.content_id),
+        )
+        .await?
+        .pop() else {
+            continue;
+        };
+        // The variant the playlist was spliced from goes first.
+        if let Some(variant) = &content.variant {
+            entry
+                .downloads
+                .sort_by_key(|download| download.variant_id.as_ref() != Some(variant));
+        }
+        catalog.push(entry);
     }
-    Ok(())
+    let entries = exports::entries(&host, &catalog, options.mp4);
+    exports::render(options.format, &entries)
 }
 
 pub fn routes() -> Routes {
     Routes::new()
         .prefix("/playlist")
         .add("/splice", post(splice))
+        .add("/:name/export", get(export))
 }
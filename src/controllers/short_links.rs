@@ -0,0 +1,140 @@
+//! Short links (`/s/k7f2`) to stream urls, easier to type on a VR keyboard than
+//! the full `/p/stream/...` url.
+
+use axum::{debug_handler, response::Redirect};
+use axum_extra::extract::Form;
+use loco_rs::prelude::*;
+use qrcode::{render::svg, QrCode};
+use serde::Deserialize;
+
+use crate::{
+    controllers::extractors::{auth::JWTWithUser, ProtoHost},
+    initializers::view_engine::BetterTeraView,
+    models::_entities::{short_links, users},
+    views,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateParams {
+    /// Path under `/p/stream/`, with its query, see [`check_target`].
+    target: String,
+    /// Days until the link expires, empty for never.
+    #[serde(default)]
+    expires: String,
+}
+
+/// Longest expiry a link can be created with.
+const MAX_EXPIRY_DAYS: i64 = 3650;
+
+/// Targets stay under `/p/stream/`, also once decoded, and don't carry a stream
+/// token: codes are short enough to guess, following one must not hand out
+/// access to the connection.
+fn check_target(target: &str) -> Result<()> {
+    let decoded = percent_encoding::percent_decode_str(target)
+        .decode_utf8()
+        .map_err(|e| Error::BadRequest(format!("Invalid target: {e}")))?;
+    let (path, query) = decoded.split_once('?').unwrap_or((&decoded, ""));
+    if path.is_empty() || path.split(['/', '\\']).any(|part| part == "..") {
+        return Err(Error::BadRequest("Invalid target".to_string()));
+    }
+    if query
+        .split('&')
+        .any(|pair| pair.split('=').next() == Some("token"))
+    {
+        return Err(Error::BadRequest(
+            "Short links can't carry stream tokens".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn not_found(e: ModelError) -> Error {
+    match e {
+        ModelError::EntityNotFound => Error::NotFound,
+        e => e.into(),
+    }
+}
+
+#[debug_handler]
+async fn follow(Path(code): Path<String>, State(ctx): State<AppContext>) -> Result<Response> {
+    let link = short_links::Model::follow(&ctx.db, &code)
+        .await
+        .map_err(not_found)?;
+    Ok(Redirect::temporary(&format!("/p/stream/{}", link.target)).into_response())
+}
+
+/// QR code of the short url, for phones and headsets with a passthrough camera.
+#[debug_handler]
+async fn qr(
+    Path(code): Path<String>,
+    State(ctx): State<AppContext>,
+    ProtoHost(host): ProtoHost,
+) -> Result<Response> {
+    let link = short_links::Model::find_by_code(&ctx.db, &code)
+        .await
+        .map_err(not_found)?;
+    let svg = QrCode::new(format!("{host}/s/{}", link.code))
+        .map_err(|e| Error::Message(e.to_string()))?
+        .render::<svg::Color<'_>>()
+        .min_dimensions(200, 200)
+        .build();
+    format::render()
+        .header("content-type", "image/svg+xml")
+        .text(&svg)
+}
+
+#[debug_handler]
+async fn create(
+    State(ctx): State<AppContext>,
+    ProtoHost(host): ProtoHost,
+    ViewEngine(v): ViewEngine<BetterTeraView>,
+    auth: JWTWithUser<users::Model>,
+    Form(params): Form<CreateParams>,
+) -> Result<Response> {
+    let target = params.target.trim().trim_start_matches('/');
+    check_target(target)?;
+    let expires_at = match params.expires.trim() {
+        "" => None,
+        days => {
+            let days: i64 = days
+                .parse()
+                .ok()
+                .filter(|days| (1..=MAX_EXPIRY_DAYS).contains(days))
+                .ok_or_else(|| Error::BadRequest(format!("Invalid expiry: {days}")))?;
+            Some((chrono::Utc::now() + chrono::Duration::days(days)).naive_utc())
+        }
+    };
+    let link = short_links::Model::create(&ctx.db, auth.user.id, target, expires_at).await?;
+    views::short_links::link(&v, &host, &link)
+}
+
+#[debug_handler]
+async fn list(
+    State(ctx): State<AppContext>,
+    ProtoHost(host): ProtoHost,
+    ViewEngine(v): ViewEngine<BetterTeraView>,
+    auth: JWTWithUser<users::Model>,
+) -> Result<Response> {
+    let links = short_links::Model::by_user(&ctx.db, auth.user.id).await?;
+    views::short_links::list(&v, &host, &links)
+}
+
+#[debug_handler]
+async fn remove(
+    Path(code): Path<String>,
+    State(ctx): State<AppContext>,
+    auth: JWTWithUser<users::Model>,
+) -> Result<Response> {
+    short_links::Model::delete_by_user(&ctx.db, auth.user.id, &code)
+        .await
+        .map_err(not_found)?;
+    format::empty()
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("s")
+        .add("/", get(list).post(create))
+        .add("/:code", get(follow).delete(remove))
+        .add("/:code/qr.svg", get(qr))
+}
@@ -0,0 +1,104 @@
+//! Subscriptions to shows and seasons, whose new episodes download on their own
+//! after library syncs.
+
+use axum::debug_handler;
+use axum_extra::extract::Form;
+use loco_rs::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    common::subscriptions,
+    controllers::extractors::auth::JWTWithUser,
+    initializers::view_engine::BetterTeraView,
+    models::{
+        _entities::{player_connections, subscriptions as subscription_models, users},
+        subscriptions::Rules,
+    },
+    views,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateParams {
+    connection: i32,
+    library: String,
+    #[serde(default)]
+    profile: Option<String>,
+    #[serde(default)]
+    audio_language: Option<String>,
+    #[serde(default)]
+    subtitle_language: Option<String>,
+}
+
+fn not_found(e: ModelError) -> Error {
+    match e {
+        ModelError::EntityNotFound => Error::NotFound,
+        e => e.into(),
+    }
+}
+
+#[debug_handler]
+async fn list(
+    State(ctx): State<AppContext>,
+    ViewEngine(v): ViewEngine<BetterTeraView>,
+    auth: JWTWithUser<users::Model>,
+) -> Result<Response> {
+    let items = subscription_models::Model::by_user(&ctx.db, auth.user.id).await?;
+    views::subscriptions::list(&v, &items)
+}
+
+#[debug_handler]
+async fn create(
+    State(ctx): State<AppContext>,
+    auth: JWTWithUser<users::Model>,
+    Form(params): Form<CreateParams>,
+) -> Result<Response> {
+    let connection =
+        player_connections::Model::find_by_user_and_id(&ctx.db, auth.user.id, params.connection)
+            .await
+            .map_err(not_found)?;
+    let rules = Rules {
+        profile: params.profile,
+        audio_language: params.audio_language,
+        subtitle_language: params.subtitle_language,
+    };
+    let subscription =
+        subscriptions::subscribe(&ctx.db, &connection, &params.library, &rules).await?;
+    format::text(&format!(
+        "Subscribed to {}, new episodes will download",
+        subscription.name
+    ))
+}
+
+#[debug_handler]
+async fn update(
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+    ViewEngine(v): ViewEngine<BetterTeraView>,
+    auth: JWTWithUser<users::Model>,
+    Form(rules): Form<Rules>,
+) -> Result<Response> {
+    let (subscription, connection) =
+        subscription_models::Model::update_rules(&ctx.db, auth.user.id, id, &rules)
+            .await
+            .map_err(not_found)?;
+    views::subscriptions::item(&v, &subscription, &connection)
+}
+
+#[debug_handler]
+async fn remove(
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+    auth: JWTWithUser<users::Model>,
+) -> Result<Response> {
+    subscription_models::Model::delete_by_user(&ctx.db, auth.user.id, id)
+        .await
+        .map_err(not_found)?;
+    format::empty()
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("subscriptions")
+        .add("/", get(list).post(create))
+        .add("/:id", post(update).delete(remove))
+}
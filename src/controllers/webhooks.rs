@@ -0,0 +1,79 @@
+//! Webhooks of media providers, at `/webhooks/<provider id>?token=<webhook_token>`.
+
+use axum::{body::Bytes, debug_handler, http::HeaderMap};
+use axum_extra::extract::Query;
+use loco_rs::prelude::*;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    common::webhooks::{self, JellyfinEvent},
+    initializers::media_provider::{MediaProviderType, CELL},
+};
+
+/// Header carrying the token, for webhook clients that can add headers.
+const TOKEN_HEADER: &str = "x-webhook-token";
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookQuery {
+    token: Option<String>,
+}
+
+/// Compared as digests, so how long it takes doesn't tell how much of the token
+/// was right.
+fn token_matches(expected: &str, given: &str) -> bool {
+    Sha256::digest(expected.as_bytes()) == Sha256::digest(given.as_bytes())
+}
+
+#[debug_handler]
+async fn receive(
+    Path(provider_id): Path<String>,
+    State(ctx): State<AppContext>,
+    Query(query): Query<WebhookQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response> {
+    let provider = CELL
+        .get()
+        .and_then(|providers| providers.get(&provider_id))
+        .ok_or(Error::NotFound)?;
+    let expected = provider
+        .webhook_token
+        .as_deref()
+        .filter(|token| !token.is_empty())
+        .ok_or(Error::NotFound)?;
+    let given = headers
+        .get(TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .or(query.token.as_deref())
+        .unwrap_or_default();
+    if !token_matches(expected, given) {
+        return Err(Error::Unauthorized("Invalid webhook token".to_string()));
+    }
+
+    match provider.type_field {
+        MediaProviderType::Jellyfin => {
+            // The plugin doesn't always say it's sending JSON.
+            let event: JellyfinEvent = serde_json::from_slice(&body)
+                .map_err(|e| Error::BadRequest(format!("Invalid webhook payload: {e}")))?;
+            if event.fetches_items() {
+                tokio::task::spawn(async move {
+                    if let Err(e) = webhooks::handle_jellyfin(&ctx.db, &provider_id, &event).await {
+                        tracing::error!(error = ?e, provider_id, "Failed to handle webhook");
+                    }
+                });
+                return format::render()
+                    .status(axum::http::StatusCode::ACCEPTED)
+                    .empty();
+            }
+            let outcome = webhooks::handle_jellyfin(&ctx.db, &provider_id, &event).await?;
+            format::json(outcome)
+        }
+    }
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("webhooks")
+        .add("/:provider", post(receive))
+}
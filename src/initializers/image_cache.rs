@@ -0,0 +1,33 @@
+use axum::async_trait;
+use loco_rs::{
+    app::{AppContext, Initializer},
+    Result,
+};
+use tokio::time::MissedTickBehavior;
+
+use crate::common::images;
+
+/// How often to look for cached images past their age.
+const SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Removes cached images older than [`images::MAX_AGE`], so the cache only
+/// holds what's still being served.
+pub struct ImageCacheInitializer;
+#[async_trait]
+impl Initializer for ImageCacheInitializer {
+    fn name(&self) -> String {
+        "image-cache".to_string()
+    }
+
+    async fn before_run(&self, _ctx: &AppContext) -> Result<()> {
+        tokio::task::spawn(async move {
+            let mut ticks = tokio::time::interval(SWEEP_INTERVAL);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                images::remove_stale_cached().await;
+            }
+        });
+        Ok(())
+    }
+}
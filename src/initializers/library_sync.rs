@@ -0,0 +1,64 @@
+use axum::async_trait;
+use loco_rs::{
+    app::{AppContext, Initializer},
+    Error, Result,
+};
+use tokio::time::MissedTickBehavior;
+
+use crate::{
+    common::{library_sync, subscriptions},
+    initializers::media_provider::CELL,
+    models::player_connections,
+};
+
+/// Syncs the libraries of every provider's connections on the provider's
+/// `sync_interval_minutes`, starting right away, then downloads the new
+/// episodes of their subscriptions.
+pub struct LibrarySyncInitializer;
+#[async_trait]
+impl Initializer for LibrarySyncInitializer {
+    fn name(&self) -> String {
+        "library-sync".to_string()
+    }
+
+    async fn before_run(&self, ctx: &AppContext) -> Result<()> {
+        let providers = CELL
+            .get()
+            .ok_or_else(|| Error::Message("Media Providers not configured".to_string()))?;
+        for provider in providers.values() {
+            let Some(interval) = provider.sync_interval() else {
+                continue;
+            };
+            let db = ctx.db.clone();
+            let provider_id = provider.id.clone();
+            tokio::task::spawn(async move {
+                let mut ticks = tokio::time::interval(interval);
+                ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                loop {
+                    ticks.tick().await;
+                    let connections = match player_connections::Model::by_media_provider(
+                        &db,
+                        Some(&provider_id),
+                    )
+                    .await
+                    {
+                        Ok(connections) => connections,
+                        Err(e) => {
+                            tracing::error!(error = ?e, provider_id, "Failed to list connections to sync");
+                            continue;
+                        }
+                    };
+                    for connection in &connections {
+                        if let Err(e) = library_sync::sync_connection(&db, connection).await {
+                            tracing::error!(error = ?e, connection_id = connection.id, "Failed to record library sync");
+                        }
+                        if let Err(e) = subscriptions::download_new(&db, connection).await {
+                            tracing::error!(error = ?e, connection_id = connection.id, "Failed to download new episodes");
+                        }
+                    }
+                }
+            });
+        }
+        Ok(())
+    }
+}
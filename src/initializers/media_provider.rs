@@ -7,7 +7,10 @@ use loco_rs::{
     worker::AppWorker,
     Error, Result,
 };
-use players::types::{Content, Item, Library, MediaStream, TranscodeJob};
+use players::types::{
+    Content, Item, ItemsPage, ItemsQuery, Library, MediaStream, PlaybackEvent, Shelf, SkipRange,
+    TranscodeJob, UserData,
+};
 use serde::{Deserialize, Serialize};
 use sidekiq::Worker;
 use tokio::sync::OnceCell;
@@ -17,6 +20,9 @@ use crate::{models::player_connections, workers::downloader::DownloadWorkerArgs}
 pub static CELL: OnceCell<Box<MediaProviders>> = OnceCell::const_new();
 
 pub type MediaProviders = BTreeMap<String, MediaProvider>;
+
+/// Minutes between library syncs of a provider's connections when not configured.
+const DEFAULT_SYNC_INTERVAL_MINUTES: u64 = 6 * 60;
 pub type MediaProviderList = Vec<MediaProvider>;
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +35,10 @@ pub struct MediaProvider {
     pub profiles: Vec<Profile>,
     pub exclude_library_ids: Vec<String>,
     pub download_workers: Option<usize>,
+    /// Minutes between library syncs, `0` turns them off.
+    pub sync_interval_minutes: Option<u64>,
+    /// Token the provider's webhooks have to carry, webhooks are refused without one.
+    pub webhook_token: Option<String>,
     #[serde(skip)]
     pub worker_ingress: OnceCell<flume::Sender<crate::workers::downloader::DownloadWorkerArgs>>,
 }
@@ -139,6 +149,19 @@ impl MediaProvider {
         }
     }
 
+    /// How often the provider's connections get their libraries synced, `None`
+    /// when turned off.
+    #[must_use]
+    pub fn sync_interval(&self) -> Option<std::time::Duration> {
+        match self
+            .sync_interval_minutes
+            .unwrap_or(DEFAULT_SYNC_INTERVAL_MINUTES)
+        {
+            0 => None,
+            minutes => Some(std::time::Duration::from_secs(minutes * 60)),
+        }
+    }
+
     pub async fn queue_download(&self, args: DownloadWorkerArgs) -> Result<()> {
         self.worker_ingress
             .get()
@@ -192,6 +215,19 @@ impl ConnectedMediaProvider {
         }
     }
 
+    /// The provider's id of the connected user.
+    #[must_use]
+    pub fn provider_user_id(&self) -> Option<String> {
+        match self.provider.type_field {
+            MediaProviderType::Jellyfin => {
+                match serde_json::from_value(self.identity.clone()).ok()? {
+                    players::jellyfin::SetupStep::Auth { id, .. } => Some(id),
+                    _ => None,
+                }
+            }
+        }
+    }
+
     pub async fn preferences(
         &self,
         _ctx: &AppContext,
@@ -235,16 +271,7 @@ impl ConnectedMediaProvider {
             }
         };
         match items {
-            Ok(items) => Ok(items
-                .into_iter()
-                .filter(|item| {
-                    if let Item::Library(library) = &item {
-                        !self.provider.exclude_library_ids.contains(&library.id)
-                    } else {
-                        true
-                    }
-                })
-                .collect()),
+            Ok(items) => Ok(self.without_excluded(items)),
             Err(e) => {
                 tracing::error!("{:?}", e);
                 Err(Error::Anyhow(e))
@@ -252,6 +279,83 @@ impl ConnectedMediaProvider {
         }
     }
 
+    /// Up to `limit` items of a library, starting at `start_index`. Excluded
+    /// libraries are left out afterwards, so pages can come out shorter.
+    pub async fn items_page(
+        &self,
+        library: &Library,
+        start_index: usize,
+        limit: usize,
+        filter: ItemsQuery,
+    ) -> Result<ItemsPage> {
+        match self.provider.type_field {
+            MediaProviderType::Jellyfin => {
+                let jellyfin =
+                    players::jellyfin::Jellyfin::new(&self.provider.url, &self.preferences);
+                let user = jellyfin
+                    .user_from_identity(&self.identity)
+                    .await
+                    .map_err(Error::Anyhow)?;
+                let page = user
+                    .items_page(library, start_index, limit, filter)
+                    .await
+                    .map_err(|e| Error::Anyhow(eyre::eyre!(e)))?;
+                Ok(ItemsPage {
+                    items: self.without_excluded(page.items),
+                    total: page.total,
+                })
+            }
+        }
+    }
+
+    pub async fn shelf(&self, shelf: Shelf, limit: usize) -> Result<Vec<Content>> {
+        match self.provider.type_field {
+            MediaProviderType::Jellyfin => {
+                let jellyfin =
+                    players::jellyfin::Jellyfin::new(&self.provider.url, &self.preferences);
+                let user = jellyfin
+                    .user_from_identity(&self.identity)
+                    .await
+                    .map_err(Error::Anyhow)?;
+                user.shelf(shelf, limit)
+                    .await
+                    .map_err(|e| Error::Anyhow(eyre::eyre!(e)))
+            }
+        }
+    }
+
+    /// Up to `limit` items matching `term` according to the provider, best first.
+    pub async fn search(&self, term: &str, limit: usize) -> Result<Vec<Item>> {
+        match self.provider.type_field {
+            MediaProviderType::Jellyfin => {
+                let jellyfin =
+                    players::jellyfin::Jellyfin::new(&self.provider.url, &self.preferences);
+                let user = jellyfin
+                    .user_from_identity(&self.identity)
+                    .await
+                    .map_err(Error::Anyhow)?;
+                let items = user
+                    .search(term, limit)
+                    .await
+                    .map_err(|e| Error::Anyhow(eyre::eyre!(e)))?;
+                Ok(self.without_excluded(items))
+            }
+        }
+    }
+
+    fn without_excluded(&self, items: Vec<Item>) -> Vec<Item> {
+        items
+            .into_iter()
+            .filter(|item| {
+                if let Item::Library(library) = &item {
+                    !self.provider.exclude_library_ids.contains(&library.id)
+                } else {
+                    true
+                }
+            })
+            .collect()
+    }
+
     pub async fn item(&self, id: &str) -> Result<Item> {
         match self.provider.type_field {
             MediaProviderType::Jellyfin => {
@@ -266,14 +370,102 @@ impl ConnectedMediaProvider {
         }
     }
 
-    pub async fn transcode(
+    pub async fn skip_ranges(&self, id: &str) -> Result<Vec<SkipRange>> {
+        match self.provider.type_field {
+            MediaProviderType::Jellyfin => {
+                let jellyfin =
+                    players::jellyfin::Jellyfin::new(&self.provider.url, &self.preferences);
+                let user = jellyfin
+                    .user_from_identity(&self.identity)
+                    .await
+                    .map_err(Error::Anyhow)?;
+                user.skip_ranges(id).await.map_err(Error::Anyhow)
+            }
+        }
+    }
+
+    pub async fn report_playback(
         &self,
-        _ctx: &AppContext,
-        content: &Content,
-        profile: Option<&str>,
-        preferred_media_streams: &[MediaStream],
-    ) -> Result<TranscodeJob> {
-        let preferred_profile = profile
+        id: &str,
+        event: PlaybackEvent,
+        position_ms: u64,
+    ) -> Result<()> {
+        match self.provider.type_field {
+            MediaProviderType::Jellyfin => {
+                let jellyfin =
+                    players::jellyfin::Jellyfin::new(&self.provider.url, &self.preferences);
+                let user = jellyfin
+                    .user_from_identity(&self.identity)
+                    .await
+                    .map_err(Error::Anyhow)?;
+                user.report_playback(id, event, position_ms)
+                    .await
+                    .map_err(Error::Anyhow)
+            }
+        }
+    }
+
+    /// Image at a provider relative path, fetched with the connection's credentials.
+    pub async fn image(&self, path: &str) -> Result<Vec<u8>> {
+        match self.provider.type_field {
+            MediaProviderType::Jellyfin => {
+                let jellyfin =
+                    players::jellyfin::Jellyfin::new(&self.provider.url, &self.preferences);
+                let user = jellyfin
+                    .user_from_identity(&self.identity)
+                    .await
+                    .map_err(Error::Anyhow)?;
+                user.image(path).await.map_err(Error::Anyhow)
+            }
+        }
+    }
+
+    pub async fn user_data(&self, id: &str) -> Result<UserData> {
+        match self.provider.type_field {
+            MediaProviderType::Jellyfin => {
+                let jellyfin =
+                    players::jellyfin::Jellyfin::new(&self.provider.url, &self.preferences);
+                let user = jellyfin
+                    .user_from_identity(&self.identity)
+                    .await
+                    .map_err(Error::Anyhow)?;
+                user.user_data(id).await.map_err(Error::Anyhow)
+            }
+        }
+    }
+
+    pub async fn set_favorite(&self, id: &str, favorite: bool) -> Result<()> {
+        match self.provider.type_field {
+            MediaProviderType::Jellyfin => {
+                let jellyfin =
+                    players::jellyfin::Jellyfin::new(&self.provider.url, &self.preferences);
+                let user = jellyfin
+                    .user_from_identity(&self.identity)
+                    .await
+                    .map_err(Error::Anyhow)?;
+                user.set_favorite(id, favorite).await.map_err(Error::Anyhow)
+            }
+        }
+    }
+
+    pub async fn mark_played(&self, id: &str) -> Result<()> {
+        match self.provider.type_field {
+            MediaProviderType::Jellyfin => {
+                let jellyfin =
+                    players::jellyfin::Jellyfin::new(&self.provider.url, &self.preferences);
+                let user = jellyfin
+                    .user_from_identity(&self.identity)
+                    .await
+                    .map_err(Error::Anyhow)?;
+                user.mark_played(id).await.map_err(Error::Anyhow)
+            }
+        }
+    }
+
+    /// Name of the profile a download with the requested `profile` ends up using,
+    /// falling back to the connection's preferred one and then the provider's first.
+    pub fn resolve_profile(&self, profile: Option<&str>) -> Result<String> {
+        profile
             .map(std::string::ToString::to_string)
             .or(self.preferred_profile.clone())
             .or_else(|| {
@@ -286,7 +478,18 @@ impl ConnectedMediaProvider {
                         .unwrap(),
                 )
             })
-            .ok_or_else(|| loco_rs::Error::string("Unknown profile"))?;
+            .ok_or_else(|| loco_rs::Error::string("Unknown profile"))
+    }
+
+    pub async fn transcode(
+        &self,
+        _ctx: &AppContext,
+        content: &Content,
+        profile: Option<&str>,
+        preferred_media_streams: &[MediaStream],
+        media_source_id: Option<&str>,
+    ) -> Result<TranscodeJob> {
+        let preferred_profile = self.resolve_profile(profile)?;
         let profile: &serde_json::Value = &self
             .provider
             .profiles
@@ -302,9 +505,14 @@ impl ConnectedMediaProvider {
                     .user_from_identity(&self.identity)
                     .await
                     .map_err(Error::Anyhow)?;
-                user.transcode(content, profile.clone(), preferred_media_streams)
-                    .await
-                    .map_err(Error::Anyhow)
+                user.transcode(
+                    content,
+                    profile.clone(),
+                    preferred_media_streams,
+                    media_source_id,
+                )
+                .await
+                .map_err(Error::Anyhow)
             }
         }
     }
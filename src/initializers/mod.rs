@@ -1,4 +1,7 @@
 #![allow(clippy::module_name_repetitions)]
+pub mod image_cache;
 pub mod layers;
+pub mod library_sync;
 pub mod media_provider;
+pub mod playback_sessions;
 pub mod view_engine;
@@ -0,0 +1,34 @@
+use axum::async_trait;
+use loco_rs::{
+    app::{AppContext, Initializer},
+    Result,
+};
+use tokio::time::MissedTickBehavior;
+
+use crate::common::playback;
+
+/// How often to look for playback sessions viewers left.
+const SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Reports the playback sessions that went quiet for longer than
+/// [`crate::models::contents::SESSION_GAP`] as stopped to their providers.
+pub struct PlaybackSessionsInitializer;
+#[async_trait]
+impl Initializer for PlaybackSessionsInitializer {
+    fn name(&self) -> String {
+        "playback-sessions".to_string()
+    }
+
+    async fn before_run(&self, ctx: &AppContext) -> Result<()> {
+        let db = ctx.db.clone();
+        tokio::task::spawn(async move {
+            let mut ticks = tokio::time::interval(SWEEP_INTERVAL);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                playback::stop_stale_sessions(&db).await;
+            }
+        });
+        Ok(())
+    }
+}
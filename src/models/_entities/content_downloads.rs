@@ -15,6 +15,10 @@ pub struct Model {
     pub content_id: String,
     pub status_info: Option<Json>,
     pub status: StatusName,
+    pub variant_id: Option<String>,
+    pub variant: Option<Json>,
+    pub outputs: Option<Json>,
+    pub shared_key: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -18,6 +18,17 @@ pub struct Model {
     pub status: Option<StatusName>,
     pub status_last_updated_at: DateTime,
     pub sort_key: i64,
+    pub resume_position_ms: Option<i64>,
+    pub resume_updated_at: Option<DateTime>,
+    pub duration_ms: Option<i64>,
+    pub played_at: Option<DateTime>,
+    pub layout_override: Option<Json>,
+    pub removed_at: Option<DateTime>,
+    pub sort_name: String,
+    pub production_year: i32,
+    pub date_added: DateTime,
+    pub runtime_ms: i64,
+    pub playing: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
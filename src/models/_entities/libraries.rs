@@ -15,6 +15,8 @@ pub struct Model {
     pub parent_id: Option<String>,
     pub cached_data: Option<Json>,
     pub sort_key: i64,
+    pub removed_at: Option<DateTime>,
+    pub sort_name: String,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -2,9 +2,13 @@
 
 pub mod prelude;
 
+pub mod clips;
 pub mod content_downloads;
 pub mod contents;
 pub mod libraries;
 pub mod player_connections;
 pub mod sea_orm_active_enums;
+pub mod shared_transcodes;
+pub mod short_links;
+pub mod subscriptions;
 pub mod users;
@@ -18,6 +18,9 @@ pub struct Model {
     pub preferences: Option<Json>,
     pub preferred_profile: Option<String>,
     pub root_libraries: Option<Json>,
+    #[sea_orm(unique)]
+    pub stream_token: Uuid,
+    pub syncing_since: Option<DateTime>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -26,6 +29,8 @@ pub enum Relation {
     Contents,
     #[sea_orm(has_many = "super::libraries::Entity")]
     Libraries,
+    #[sea_orm(has_many = "super::subscriptions::Entity")]
+    Subscriptions,
     #[sea_orm(
         belongs_to = "super::users::Entity",
         from = "Column::UserId",
@@ -48,6 +53,12 @@ impl Related<super::libraries::Entity> for Entity {
     }
 }
 
+impl Related<super::subscriptions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Subscriptions.def()
+    }
+}
+
 impl Related<super::users::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Users.def()
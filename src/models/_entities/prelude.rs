@@ -1,7 +1,11 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.10
 
+pub use super::clips::Entity as Clips;
 pub use super::content_downloads::Entity as ContentDownloads;
 pub use super::contents::Entity as Contents;
 pub use super::libraries::Entity as Libraries;
 pub use super::player_connections::Entity as PlayerConnections;
+pub use super::shared_transcodes::Entity as SharedTranscodes;
+pub use super::short_links::Entity as ShortLinks;
+pub use super::subscriptions::Entity as Subscriptions;
 pub use super::users::Entity as Users;
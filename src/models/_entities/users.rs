@@ -22,12 +22,16 @@ pub struct Model {
     pub email_verification_token: Option<String>,
     pub email_verification_sent_at: Option<DateTime>,
     pub email_verified_at: Option<DateTime>,
+    pub prefetch_episodes: i32,
+    pub library_sort: String,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_many = "super::player_connections::Entity")]
     PlayerConnections,
+    #[sea_orm(has_many = "super::short_links::Entity")]
+    ShortLinks,
 }
 
 impl Related<super::player_connections::Entity> for Entity {
@@ -35,3 +39,9 @@ impl Related<super::player_connections::Entity> for Entity {
         Relation::PlayerConnections.def()
     }
 }
+
+impl Related<super::short_links::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ShortLinks.def()
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use super::_entities::{
+    clips::{self, ActiveModel, Model},
+    content_downloads, player_connections,
+    sea_orm_active_enums::StatusName,
+};
+use loco_rs::model::ModelResult;
+use sea_orm::{entity::prelude::*, ActiveValue, QueryOrder, QuerySelect};
+
+impl ActiveModelBehavior for ActiveModel {
+    // extend activemodel below (keep comment for generators)
+}
+
+impl Model {
+    /// Records a clip of `download`, its playlists still have to be built.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn create(
+        db: &DatabaseConnection,
+        download: &content_downloads::Model,
+        name: &str,
+        start_ms: i64,
+        end_ms: i64,
+    ) -> ModelResult<Self> {
+        let clip = ActiveModel {
+            player_connection_id: ActiveValue::Set(download.player_connection_id),
+            content_id: ActiveValue::Set(download.content_id.clone()),
+            content_download_id: ActiveValue::Set(download.id),
+            name: ActiveValue::Set(name.to_string()),
+            start_ms: ActiveValue::Set(start_ms),
+            end_ms: ActiveValue::Set(end_ms),
+            status: ActiveValue::Set(StatusName::InProgress),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+        Ok(clip)
+    }
+
+    /// # Errors
+    ///
+    /// When the clip doesn't exist or the database can't be reached.
+    pub async fn set_status(
+        db: &DatabaseConnection,
+        id: Uuid,
+        status: StatusName,
+    ) -> ModelResult<Self> {
+        let clip = ActiveModel {
+            id: ActiveValue::Set(id),
+            status: ActiveValue::Set(status),
+            updated_at: ActiveValue::Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .update(db)
+        .await?;
+        Ok(clip)
+    }
+
+    /// Clips of the given contents, keyed by content id, the newest first.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn by_contents(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        content_ids: &[String],
+    ) -> ModelResult<HashMap<String, Vec<Self>>> {
+        let clips = clips::Entity::find()
+            .filter(clips::Column::PlayerConnectionId.eq(connection_id))
+            .filter(clips::Column::ContentId.is_in(content_ids.iter().cloned()))
+            .order_by_desc(clips::Column::CreatedAt)
+            .all(db)
+            .await?;
+        let mut by_content: HashMap<String, Vec<Self>> = HashMap::new();
+        for clip in clips {
+            by_content
+                .entry(clip.content_id.clone())
+                .or_default()
+                .push(clip);
+        }
+        Ok(by_content)
+    }
+
+    /// The user's latest clips on any connection, the newest first.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn recent_by_user(
+        db: &DatabaseConnection,
+        user_id: i32,
+        limit: u64,
+    ) -> ModelResult<Vec<Self>> {
+        let connections: Vec<i32> = player_connections::Entity::find()
+            .select_only()
+            .column(player_connections::Column::Id)
+            .filter(player_connections::Column::UserId.eq(user_id))
+            .into_tuple()
+            .all(db)
+            .await?;
+        Ok(clips::Entity::find()
+            .filter(clips::Column::PlayerConnectionId.is_in(connections))
+            .order_by_desc(clips::Column::CreatedAt)
+            .limit(limit)
+            .all(db)
+            .await?)
+    }
+
+    /// Where the clip's playlists live, relative to the transcoding dir.
+    #[must_use]
+    pub fn storage_path(&self) -> String {
+        format!("clips/{}", self.id)
+    }
+}
@@ -1,10 +1,22 @@
-use super::_entities::{
-    content_downloads::{ActiveModel, Model},
-    contents,
-    sea_orm_active_enums::StatusName,
+use std::collections::HashMap;
+
+use super::{
+    _entities::{
+        content_downloads::{self, ActiveModel, Model},
+        contents, player_connections,
+        sea_orm_active_enums::StatusName,
+    },
+    shared_transcodes,
+};
+use crate::common::{
+    outputs::Output,
+    variants::{Variant, DEFAULT_VARIANT},
+};
+use loco_rs::model::{self, ModelError, ModelResult};
+use sea_orm::{
+    entity::prelude::*, ActiveValue, IntoActiveModel, QueryOrder, QuerySelect, Statement,
+    TransactionTrait,
 };
-use loco_rs::model::{ModelError, ModelResult};
-use sea_orm::{entity::prelude::*, ActiveValue, Statement, TransactionTrait};
 use serde::Serialize;
 
 impl ActiveModelBehavior for ActiveModel {
@@ -25,6 +37,19 @@ impl ActiveModel {
         Ok(self)
     }
 
+    pub fn variant(mut self, variant: &Variant) -> ModelResult<Self> {
+        let data = serde_json::to_value(variant).map_err(|e| ModelError::Any(e.into()))?;
+        self.variant_id = ActiveValue::Set(Some(variant.id()));
+        self.variant = ActiveValue::Set(Some(data));
+        Ok(self)
+    }
+
+    pub fn outputs(mut self, outputs: &[Output]) -> ModelResult<Self> {
+        let outputs = serde_json::to_value(outputs).map_err(|e| ModelError::Any(e.into()))?;
+        self.outputs = ActiveValue::Set(Some(outputs));
+        Ok(self)
+    }
+
     pub fn id(mut self, id: Uuid) -> Self {
         self.id = ActiveValue::Set(id);
         self
@@ -32,6 +57,246 @@ impl ActiveModel {
 }
 
 impl Model {
+    /// Records a playable output of the download, replacing any previous output
+    /// with the same path.
+    ///
+    /// # Errors
+    ///
+    /// When the download doesn't exist or the database can't be reached.
+    pub async fn add_output(
+        db: &DatabaseConnection,
+        id: Uuid,
+        output: Output,
+    ) -> ModelResult<Self> {
+        let txn = db.begin().await?;
+
+        let download = content_downloads::Entity::find_by_id(id)
+            .one(&txn)
+            .await?
+            .ok_or(ModelError::EntityNotFound)?;
+
+        let mut outputs = download.outputs()?;
+        outputs.retain(|existing| existing.path() != output.path());
+        outputs.push(output);
+
+        let download = download
+            .into_active_model()
+            .outputs(&outputs)?
+            .update(&txn)
+            .await?;
+
+        txn.commit().await?;
+
+        Ok(download)
+    }
+
+    pub fn outputs(&self) -> ModelResult<Vec<Output>> {
+        self.outputs
+            .clone()
+            .map(serde_json::from_value)
+            .transpose()
+            .map(Option::unwrap_or_default)
+            .map_err(|e| ModelError::Any(e.into()))
+    }
+
+    /// Storage dir of a content's variant, relative to the transcoding dir.
+    /// `None` or [`DEFAULT_VARIANT`] picks the most recent finished variant.
+    ///
+    /// # Errors
+    ///
+    /// When there is no such download or the database can't be reached.
+    pub async fn variant_path(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        content_id: &str,
+        variant_id: Option<&str>,
+    ) -> ModelResult<String> {
+        Self::find_variant(db, connection_id, content_id, variant_id)
+            .await
+            .map(|download| download.storage_path())
+    }
+
+    /// Latest download of a content's variant, see [`Self::variant_path`].
+    ///
+    /// # Errors
+    ///
+    /// When there is no such download or the database can't be reached.
+    pub async fn find_variant(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        content_id: &str,
+        variant_id: Option<&str>,
+    ) -> ModelResult<Self> {
+        let mut query = content_downloads::Entity::find().filter(
+            model::query::condition()
+                .eq(content_downloads::Column::PlayerConnectionId, connection_id)
+                .eq(content_downloads::Column::ContentId, content_id)
+                .build(),
+        );
+        query = match variant_id.filter(|id| *id != DEFAULT_VARIANT) {
+            Some(variant_id) => query.filter(content_downloads::Column::VariantId.eq(variant_id)),
+            None => query.filter(content_downloads::Column::Status.eq(StatusName::Success)),
+        };
+        query
+            .order_by_desc(content_downloads::Column::UpdatedAt)
+            .one(db)
+            .await?
+            .ok_or(ModelError::EntityNotFound)
+    }
+
+    /// Where the download lives, relative to the transcoding dir. Downloads made
+    /// before variants were tracked sit directly in the content dir, downloads
+    /// from the shared cache in the cache entry's dir.
+    #[must_use]
+    pub fn storage_path(&self) -> String {
+        if let Some(key) = &self.shared_key {
+            return shared_transcodes::storage_path(key);
+        }
+        let base = format!("single/{}/{}", self.player_connection_id, self.content_id);
+        match &self.variant_id {
+            Some(variant_id) => format!("{base}/{variant_id}"),
+            None => base,
+        }
+    }
+
+    /// The content a storage dir (see [`Self::storage_path`]) holds for the given
+    /// connection, `None` when the dir isn't one of the connection's downloads.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn content_for_storage_dir(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        dir: &str,
+    ) -> ModelResult<Option<String>> {
+        let mut parts = dir.split('/');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("single"), Some(connection), Some(content_id))
+                if connection == connection_id.to_string() =>
+            {
+                Ok(Some(content_id.to_string()))
+            }
+            (Some("shared"), Some(key), None) => Ok(content_downloads::Entity::find()
+                .filter(content_downloads::Column::PlayerConnectionId.eq(connection_id))
+                .filter(content_downloads::Column::SharedKey.eq(key))
+                .one(db)
+                .await?
+                .map(|download| download.content_id)),
+            _ => Ok(None),
+        }
+    }
+
+    /// How many of the user's downloads are still transcoding, on any connection.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn in_progress_by_user(db: &DatabaseConnection, user_id: i32) -> ModelResult<u64> {
+        let connections: Vec<i32> = player_connections::Entity::find()
+            .select_only()
+            .column(player_connections::Column::Id)
+            .filter(player_connections::Column::UserId.eq(user_id))
+            .into_tuple()
+            .all(db)
+            .await?;
+        Ok(content_downloads::Entity::find()
+            .filter(content_downloads::Column::PlayerConnectionId.is_in(connections))
+            .filter(content_downloads::Column::Status.eq(StatusName::InProgress))
+            .count(db)
+            .await?)
+    }
+
+    /// Latest download of every variant for the given contents, keyed by content id.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached or stored data is invalid.
+    pub async fn variants_by_contents(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        content_ids: &[String],
+    ) -> ModelResult<HashMap<String, Vec<VariantDownload>>> {
+        let downloads = content_downloads::Entity::find()
+            .filter(content_downloads::Column::PlayerConnectionId.eq(connection_id))
+            .filter(content_downloads::Column::ContentId.is_in(content_ids.iter().cloned()))
+            .order_by_desc(content_downloads::Column::UpdatedAt)
+            .all(db)
+            .await?;
+
+        let mut variants: HashMap<String, Vec<VariantDownload>> = HashMap::new();
+        for download in downloads {
+            let entry = variants.entry(download.content_id.clone()).or_default();
+            if entry.iter().any(|v| v.variant_id == download.variant_id) {
+                continue;
+            }
+            entry.push(download.try_into()?);
+        }
+        Ok(variants)
+    }
+
+    /// Removes a variant of a content: the download and the older ones of the
+    /// same variant. Shared transcodes lose a reference each, the dirs of the
+    /// others are returned so the caller can delete them.
+    ///
+    /// # Errors
+    ///
+    /// When the connection has no such download or the database can't be reached.
+    pub async fn remove(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        id: Uuid,
+    ) -> ModelResult<Vec<String>> {
+        let txn = db.begin().await?;
+
+        let download = content_downloads::Entity::find_by_id(id)
+            .filter(content_downloads::Column::PlayerConnectionId.eq(connection_id))
+            .one(&txn)
+            .await?
+            .ok_or(ModelError::EntityNotFound)?;
+        let removed = content_downloads::Entity::find()
+            .filter(content_downloads::Column::PlayerConnectionId.eq(connection_id))
+            .filter(content_downloads::Column::ContentId.eq(download.content_id.clone()))
+            .filter(match &download.variant_id {
+                Some(variant_id) => content_downloads::Column::VariantId.eq(variant_id.clone()),
+                None => content_downloads::Column::VariantId.is_null(),
+            })
+            .all(&txn)
+            .await?;
+
+        let mut dirs = vec![];
+        for download in removed {
+            content_downloads::Entity::delete_by_id(download.id)
+                .exec(&txn)
+                .await?;
+            match &download.shared_key {
+                Some(key) => super::_entities::shared_transcodes::Model::release(&txn, key).await?,
+                None => dirs.push(download.storage_path()),
+            }
+        }
+        dirs.dedup();
+
+        // The content shows the status of its latest remaining download.
+        let latest = content_downloads::Entity::find()
+            .filter(content_downloads::Column::PlayerConnectionId.eq(connection_id))
+            .filter(content_downloads::Column::ContentId.eq(download.content_id.clone()))
+            .order_by_desc(content_downloads::Column::UpdatedAt)
+            .one(&txn)
+            .await?;
+        contents::ActiveModel {
+            player_connection_id: ActiveValue::Set(connection_id),
+            content_id: ActiveValue::Set(download.content_id.clone()),
+            ..Default::default()
+        }
+        .status(latest.map(|latest| latest.status))
+        .update(&txn)
+        .await?;
+
+        txn.commit().await?;
+
+        Ok(dirs)
+    }
+
     pub async fn notify_status<T: Serialize>(
         db: &DatabaseConnection,
         id: Uuid,
@@ -95,3 +360,35 @@ impl From<Model> for Notification {
         }
     }
 }
+
+/// A downloaded variant of a content, as shown on its card.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct VariantDownload {
+    pub download_id: Uuid,
+    pub variant_id: Option<String>,
+    pub label: String,
+    pub path: String,
+    pub status: StatusName,
+    pub outputs: Vec<Output>,
+}
+
+impl TryFrom<Model> for VariantDownload {
+    type Error = ModelError;
+
+    fn try_from(download: Model) -> Result<Self, Self::Error> {
+        let variant: Option<Variant> = download
+            .variant
+            .clone()
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|e| ModelError::Any(e.into()))?;
+        Ok(Self {
+            download_id: download.id,
+            variant_id: download.variant_id.clone(),
+            label: variant.map_or_else(|| "Original download".to_string(), |v| v.label()),
+            path: download.storage_path(),
+            outputs: download.outputs()?,
+            status: download.status,
+        })
+    }
+}
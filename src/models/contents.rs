@@ -1,13 +1,24 @@
-use super::_entities::{
-    content_downloads,
-    contents::{self, ActiveModel, Model},
-    sea_orm_active_enums::StatusName,
+use std::collections::{BTreeMap, HashMap};
+
+use super::{
+    _entities::{
+        clips, content_downloads,
+        contents::{self, ActiveModel, Model},
+        libraries, player_connections,
+        sea_orm_active_enums::StatusName,
+    },
+    content_downloads::VariantDownload,
+    libraries::LibraryWithModel,
 };
+use crate::common::{pagination::SortOrder, resume, variants::Variant};
 use futures_util::TryFutureExt;
 use loco_rs::model::{self, ModelError, ModelResult};
 use migration::OnConflict;
-use players::types::Content;
-use sea_orm::{entity::prelude::*, ActiveValue, IntoActiveModel, QueryOrder, TransactionTrait};
+use players::types::{Content, LibraryKind, VrLayout};
+use sea_orm::{
+    entity::prelude::*, ActiveValue, IntoActiveModel, Order, QueryOrder, QuerySelect,
+    TransactionTrait,
+};
 
 impl ActiveModelBehavior for ActiveModel {
     // extend activemodel below (keep comment for generators)
@@ -33,6 +44,47 @@ impl ActiveModel {
         }
         self
     }
+
+    /// Fills the columns each [`SortOrder`] lists by.
+    pub fn sort_columns(mut self, content: &Content) -> Self {
+        let metadata = &content.metadata;
+        self.sort_key = ActiveValue::Set(content.sort_key());
+        self.sort_name = ActiveValue::Set(
+            metadata
+                .sort_name
+                .as_deref()
+                .unwrap_or(&content.name)
+                .to_lowercase(),
+        );
+        self.production_year = ActiveValue::Set(metadata.production_year.unwrap_or(0));
+        // Providers that don't know stay at when the content was first cached.
+        self.date_added = ActiveValue::Set(
+            metadata
+                .date_added
+                .unwrap_or_else(chrono::Utc::now)
+                .naive_utc(),
+        );
+        self.runtime_ms = ActiveValue::Set(
+            metadata
+                .runtime_ms
+                .and_then(|runtime| i64::try_from(runtime).ok())
+                .unwrap_or(0),
+        );
+        self
+    }
+}
+
+/// Columns contents are listed by in an order, the id last so ties keep still
+/// between pages.
+const fn order_columns(order: SortOrder) -> (&'static [contents::Column], Order) {
+    use contents::Column::{ContentId, DateAdded, ProductionYear, RuntimeMs, SortKey, SortName};
+    match order {
+        SortOrder::Episode => (&[SortKey, SortName, ContentId], Order::Asc),
+        SortOrder::Name => (&[SortName, ContentId], Order::Asc),
+        SortOrder::Year => (&[ProductionYear, SortName, ContentId], Order::Asc),
+        SortOrder::DateAdded => (&[DateAdded, ContentId], Order::Desc),
+        SortOrder::Runtime => (&[RuntimeMs, ContentId], Order::Asc),
+    }
 }
 
 impl super::_entities::contents::Model {
@@ -54,10 +106,11 @@ impl super::_entities::contents::Model {
                 ActiveModel {
                     player_connection_id: ActiveValue::Set(connection_id),
                     content_id: ActiveValue::Set(content.id.clone()),
-                    sort_key: ActiveValue::Set(content.sort_key() as i64),
+                    removed_at: ActiveValue::Set(None),
                     ..Default::default()
                 }
                 .parent_id(true_parent_id.or(content.parent_id.as_deref()).as_deref())
+                .sort_columns(content)
                 .cache_data(content)
             })
             .collect::<ModelResult<Vec<_>>>()?;
@@ -71,8 +124,18 @@ impl super::_entities::contents::Model {
                 .update_columns([
                     contents::Column::CachedData,
                     contents::Column::SortKey,
+                    contents::Column::SortName,
+                    contents::Column::ProductionYear,
+                    contents::Column::RuntimeMs,
                     contents::Column::ParentId,
+                    contents::Column::RemovedAt,
                 ])
+                // The earliest date seen sticks, whether from the provider or
+                // the first sync.
+                .value(
+                    contents::Column::DateAdded,
+                    Expr::cust("LEAST(contents.date_added, excluded.date_added)"),
+                )
                 .to_owned(),
             )
             .exec(&txn)
@@ -87,6 +150,7 @@ impl super::_entities::contents::Model {
         db: &DatabaseConnection,
         connection_id: i32,
         content_id: &str,
+        variant: &Variant,
     ) -> ModelResult<(Self, content_downloads::Model)> {
         let txn = db.begin().await?;
 
@@ -102,6 +166,7 @@ impl super::_entities::contents::Model {
             ..Default::default()
         }
         .content(&content_db)
+        .variant(variant)?
         .insert(&txn)
         .await?;
 
@@ -116,10 +181,16 @@ impl super::_entities::contents::Model {
         Ok((content_db, download))
     }
 
+    /// A library's contents in `order`.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
     pub async fn by_connection_and_parent_id(
         db: &DatabaseConnection,
         connection_id: i32,
         parent_id: Option<&str>,
+        order: SortOrder,
     ) -> ModelResult<Vec<Model>> {
         let contents = contents::Entity::find()
             .filter(
@@ -128,10 +199,228 @@ impl super::_entities::contents::Model {
                     .eq(contents::Column::ParentId, parent_id)
                     .build(),
             )
+            .filter(contents::Column::RemovedAt.is_null());
+        Ok(Self::ordered(contents, order).all(db).await?)
+    }
+
+    /// Up to `limit` children of a library in `order`, after the content
+    /// `after` when given.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn page_by_connection_and_parent_id(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        parent_id: &str,
+        order: SortOrder,
+        after: Option<&str>,
+        limit: u64,
+    ) -> ModelResult<Vec<Model>> {
+        let mut contents = contents::Entity::find()
+            .filter(contents::Column::PlayerConnectionId.eq(connection_id))
+            .filter(contents::Column::ParentId.eq(parent_id))
+            .filter(contents::Column::RemovedAt.is_null());
+        if let Some(content_id) = after {
+            // Compares whole rows, so ties on the first columns fall through
+            // to the next ones like in the ORDER BY.
+            let (columns, direction) = order_columns(order);
+            let columns = columns
+                .iter()
+                .map(|column| column.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let operator = if direction == Order::Desc { "<" } else { ">" };
+            contents = contents.filter(Expr::cust_with_values(
+                format!(
+                    "({columns}) {operator} (SELECT {columns} FROM contents \
+                     WHERE player_connection_id = $1 AND content_id = $2)"
+                ),
+                [Value::from(connection_id), Value::from(content_id)],
+            ));
+        }
+        Ok(Self::ordered(contents, order).limit(limit).all(db).await?)
+    }
+
+    fn ordered(query: Select<contents::Entity>, order: SortOrder) -> Select<contents::Entity> {
+        let (columns, direction) = order_columns(order);
+        columns.iter().fold(query, |query, column| {
+            query.order_by(*column, direction.clone())
+        })
+    }
+
+    /// Cached contents matching a [`prefix_query`](crate::common::search::prefix_query),
+    /// the best matches first.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn search(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        query: &str,
+        limit: u64,
+    ) -> ModelResult<Vec<Model>> {
+        Ok(contents::Entity::find()
+            .filter(contents::Column::PlayerConnectionId.eq(connection_id))
+            .filter(contents::Column::RemovedAt.is_null())
+            .filter(Expr::cust_with_values(
+                "search @@ to_tsquery('simple', $1)",
+                [query],
+            ))
+            .order_by(
+                Expr::cust_with_values("ts_rank(search, to_tsquery('simple', $1))", [query]),
+                Order::Desc,
+            )
             .order_by_asc(contents::Column::SortKey)
+            .limit(limit)
+            .all(db)
+            .await?)
+    }
+
+    /// The cached ones of the given contents, in no particular order.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn by_connection_and_ids(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        content_ids: &[String],
+    ) -> ModelResult<Vec<Model>> {
+        Ok(contents::Entity::find()
+            .filter(contents::Column::PlayerConnectionId.eq(connection_id))
+            .filter(contents::Column::ContentId.is_in(content_ids.iter().cloned()))
             .all(db)
+            .await?)
+    }
+
+    /// The contents following `after` in its library, in library order.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn next_in_parent(
+        db: &DatabaseConnection,
+        after: &Model,
+        limit: u64,
+    ) -> ModelResult<Vec<Model>> {
+        let Some(parent_id) = &after.parent_id else {
+            return Ok(vec![]);
+        };
+        Ok(contents::Entity::find()
+            .filter(contents::Column::PlayerConnectionId.eq(after.player_connection_id))
+            .filter(contents::Column::ParentId.eq(parent_id))
+            .filter(contents::Column::RemovedAt.is_null())
+            .filter(contents::Column::SortKey.gt(after.sort_key))
+            .order_by_asc(contents::Column::SortKey)
+            .limit(limit)
+            .all(db)
+            .await?)
+    }
+
+    /// Ids of a library's cached contents that weren't removed.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn ids_by_connection_and_parent_id(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        parent_id: &str,
+    ) -> ModelResult<Vec<String>> {
+        Ok(contents::Entity::find()
+            .select_only()
+            .column(contents::Column::ContentId)
+            .filter(contents::Column::PlayerConnectionId.eq(connection_id))
+            .filter(contents::Column::ParentId.eq(parent_id))
+            .filter(contents::Column::RemovedAt.is_null())
+            .into_tuple()
+            .all(db)
+            .await?)
+    }
+
+    /// Marks a content as removed on the given connections, returning on how
+    /// many it was cached.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn tombstone(
+        db: &DatabaseConnection,
+        connection_ids: &[i32],
+        content_id: &str,
+    ) -> ModelResult<u64> {
+        let removed = contents::Entity::update_many()
+            .col_expr(
+                contents::Column::RemovedAt,
+                Expr::value(Some(chrono::Utc::now().naive_utc())),
+            )
+            .filter(contents::Column::PlayerConnectionId.is_in(connection_ids.iter().copied()))
+            .filter(contents::Column::ContentId.eq(content_id))
+            .filter(contents::Column::RemovedAt.is_null())
+            .exec(db)
             .await?;
-        Ok(contents)
+        Ok(removed.rows_affected)
+    }
+
+    /// Takes over watch progress the provider saw elsewhere, like another
+    /// client. Returns whether the content is cached.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn apply_user_data(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        content_id: &str,
+        position_ms: Option<i64>,
+        played: bool,
+    ) -> ModelResult<bool> {
+        let Some(content) = contents::Entity::find()
+            .filter(contents::Column::PlayerConnectionId.eq(connection_id))
+            .filter(contents::Column::ContentId.eq(content_id))
+            .one(db)
+            .await?
+        else {
+            return Ok(false);
+        };
+        let mut content = content.into_active_model();
+        content.resume_position_ms = ActiveValue::Set(position_ms.filter(|at| *at > 0));
+        if !played {
+            content.played_at = ActiveValue::Set(None);
+        } else if content.played_at.as_ref().is_none() {
+            content.played_at = ActiveValue::Set(Some(chrono::Utc::now().naive_utc()));
+        }
+        content.update(db).await?;
+        Ok(true)
+    }
+
+    /// Marks a library's contents that aren't in `present` as removed. Their
+    /// downloads stay playable, they just drop out of library listings.
+    /// Returns how many were.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn tombstone_missing(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        parent_id: &str,
+        present: &[String],
+    ) -> ModelResult<u64> {
+        let removed = contents::Entity::update_many()
+            .col_expr(
+                contents::Column::RemovedAt,
+                Expr::value(Some(chrono::Utc::now().naive_utc())),
+            )
+            .filter(contents::Column::PlayerConnectionId.eq(connection_id))
+            .filter(contents::Column::ParentId.eq(parent_id))
+            .filter(contents::Column::RemovedAt.is_null())
+            .filter(contents::Column::ContentId.is_not_in(present.iter().cloned()))
+            .exec(db)
+            .await?;
+        Ok(removed.rows_affected)
     }
 
     pub async fn by_connection_and_id(
@@ -151,6 +440,168 @@ impl super::_entities::contents::Model {
         content.ok_or_else(|| ModelError::EntityNotFound)
     }
 
+    /// Remembers how far into a content the viewer got. Fetches after a pause of
+    /// [`SESSION_GAP`] start a new playback session, getting past
+    /// [`WATCHED_THRESHOLD`] of the content marks it played and clears the resume
+    /// point. Within a session positions are written every [`POSITION_INTERVAL`],
+    /// unless `force`d. `None` when the content isn't known or nothing was written.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn record_position(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        content_id: &str,
+        position_ms: i64,
+        force: bool,
+    ) -> ModelResult<Option<Progress>> {
+        let txn = db.begin().await?;
+
+        let Some(content) = contents::Entity::find()
+            .filter(contents::Column::PlayerConnectionId.eq(connection_id))
+            .filter(contents::Column::ContentId.eq(content_id))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let now = chrono::Utc::now().naive_utc();
+        let Some(progress) = content.progress_at(position_ms, now, force) else {
+            return Ok(None);
+        };
+
+        let mut content = content.into_active_model();
+        content.resume_position_ms = ActiveValue::Set((!progress.watched).then_some(position_ms));
+        content.resume_updated_at = ActiveValue::Set(Some(now));
+        if progress.played {
+            content.played_at = ActiveValue::Set(Some(now));
+        }
+        // Stopped and played positions are reported as the end of the session.
+        content.playing = ActiveValue::Set(!force && !progress.played);
+        content.update(&txn).await?;
+
+        txn.commit().await?;
+
+        Ok(Some(progress))
+    }
+
+    /// What a fetch at `position_ms` means for the watch progress, `None` when
+    /// it's too soon after the last recorded one to write, see
+    /// [`Self::record_position`].
+    #[must_use]
+    pub fn progress_at(
+        &self,
+        position_ms: i64,
+        now: chrono::NaiveDateTime,
+        force: bool,
+    ) -> Option<Progress> {
+        let since = self.resume_updated_at.map(|updated| now - updated);
+        let session_started = since.is_none_or(|since| since > SESSION_GAP);
+        #[allow(clippy::cast_precision_loss)]
+        let watched = self
+            .duration_ms
+            .is_some_and(|duration| position_ms as f64 >= duration as f64 * WATCHED_THRESHOLD);
+        // Crossing the threshold clears the resume point, so only the first fetch
+        // past it (of every rewatch) counts.
+        let played = watched && (self.resume_position_ms.is_some() || self.played_at.is_none());
+        let throttled = since.is_some_and(|since| since < POSITION_INTERVAL);
+        if throttled && !force && !session_started && !played {
+            return None;
+        }
+        Some(Progress {
+            session_started,
+            played,
+            watched,
+        })
+    }
+
+    /// Contents still playing as far as the provider knows, with no position
+    /// recorded for longer than [`SESSION_GAP`]. The viewer left without their
+    /// player saying so.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn stale_sessions(
+        db: &DatabaseConnection,
+        now: chrono::NaiveDateTime,
+    ) -> ModelResult<Vec<Self>> {
+        Ok(contents::Entity::find()
+            .filter(contents::Column::Playing.eq(true))
+            .filter(contents::Column::ResumeUpdatedAt.lt(now - SESSION_GAP))
+            .all(db)
+            .await?)
+    }
+
+    /// Marks the playback session of a content as reported stopped.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn end_session(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        content_id: &str,
+    ) -> ModelResult<()> {
+        contents::Entity::update_many()
+            .col_expr(contents::Column::Playing, Expr::value(false))
+            .filter(contents::Column::PlayerConnectionId.eq(connection_id))
+            .filter(contents::Column::ContentId.eq(content_id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Stores the length of a content as seen in its media playlist.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn record_duration(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        content_id: &str,
+        duration_ms: i64,
+    ) -> ModelResult<()> {
+        contents::Entity::update_many()
+            .col_expr(contents::Column::DurationMs, Expr::value(Some(duration_ms)))
+            .filter(contents::Column::PlayerConnectionId.eq(connection_id))
+            .filter(contents::Column::ContentId.eq(content_id))
+            .filter(
+                contents::Column::DurationMs
+                    .is_null()
+                    .or(contents::Column::DurationMs.ne(duration_ms)),
+            )
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Overrides the detected layout of a content, `None` goes back to detection.
+    ///
+    /// # Errors
+    ///
+    /// When the content isn't known or the database can't be reached.
+    pub async fn set_layout_override(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        content_id: &str,
+        layout: Option<VrLayout>,
+    ) -> ModelResult<ContentWithModel> {
+        let layout = layout
+            .map(serde_json::to_value)
+            .transpose()
+            .map_err(|e| ModelError::Any(e.into()))?;
+        let mut content = Self::by_connection_and_id(db, connection_id, content_id)
+            .await?
+            .into_active_model();
+        content.layout_override = ActiveValue::Set(layout);
+        content.update(db).await?.try_into()
+    }
+
     pub async fn content_by_connection_and_id(
         db: &DatabaseConnection,
         connection_id: i32,
@@ -162,27 +613,382 @@ impl super::_entities::contents::Model {
     }
 }
 
+impl super::_entities::contents::Model {
+    /// Contents with a finished download on the user's connections, for player
+    /// catalogs.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached or cached data is invalid.
+    pub async fn downloaded_by_user(
+        db: &DatabaseConnection,
+        user_id: i32,
+        scope: CatalogScope<'_>,
+    ) -> ModelResult<Vec<CatalogEntry>> {
+        let mut connections = player_connections::Entity::find()
+            .filter(player_connections::Column::UserId.eq(user_id))
+            .order_by_asc(player_connections::Column::Id);
+        if let Some(connection_id) = scope.connection() {
+            connections = connections.filter(player_connections::Column::Id.eq(connection_id));
+        }
+        let connections: HashMap<i32, player_connections::Model> = connections
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|connection| (connection.id, connection))
+            .collect();
+
+        let mut downloads = content_downloads::Entity::find()
+            .filter(
+                content_downloads::Column::PlayerConnectionId.is_in(connections.keys().copied()),
+            )
+            .filter(content_downloads::Column::Status.eq(StatusName::Success))
+            .order_by_desc(content_downloads::Column::UpdatedAt);
+        if let CatalogScope::Content(_, content_id) = scope {
+            downloads = downloads.filter(content_downloads::Column::ContentId.eq(content_id));
+        }
+        let mut by_content: BTreeMap<(i32, String), Vec<VariantDownload>> = BTreeMap::new();
+        for download in downloads.all(db).await? {
+            let entry = by_content
+                .entry((download.player_connection_id, download.content_id.clone()))
+                .or_default();
+            if entry.iter().any(|v| v.variant_id == download.variant_id) {
+                continue;
+            }
+            entry.push(download.try_into()?);
+        }
+        if by_content.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let libraries: HashMap<(i32, String), LibraryWithModel> = libraries::Entity::find()
+            .filter(libraries::Column::PlayerConnectionId.is_in(connections.keys().copied()))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|library| {
+                let key = (library.player_connection_id, library.library_id.clone());
+                library.try_into().map(|library| (key, library))
+            })
+            .collect::<ModelResult<_>>()?;
+
+        let mut contents = contents::Entity::find()
+            .filter(contents::Column::PlayerConnectionId.is_in(connections.keys().copied()))
+            .filter(
+                contents::Column::ContentId
+                    .is_in(by_content.keys().map(|(_, content_id)| content_id.clone())),
+            )
+            .order_by_asc(contents::Column::PlayerConnectionId)
+            .order_by_asc(contents::Column::SortKey)
+            .order_by_asc(contents::Column::SortName);
+        if let CatalogScope::Library(_, library_id) = scope {
+            contents = contents.filter(contents::Column::ParentId.eq(library_id));
+        }
+        let contents = contents.all(db).await?;
+
+        let mut entries = Vec::with_capacity(contents.len());
+        for content in contents {
+            let key = (content.player_connection_id, content.content_id.clone());
+            let (Some(downloads), Some(connection)) = (
+                by_content.remove(&key),
+                connections.get(&content.player_connection_id),
+            ) else {
+                continue;
+            };
+            let library = content.parent_id.as_ref().and_then(|parent_id| {
+                let parent = libraries.get(&(content.player_connection_id, parent_id.clone()))?;
+                let show = parent
+                    .parent_id
+                    .as_ref()
+                    .and_then(|id| libraries.get(&(content.player_connection_id, id.clone())));
+                Some(match (&parent.library.kind, show) {
+                    (LibraryKind::Season { .. }, Some(show)) => {
+                        format!("{} · {}", show.library.name, parent.library.name)
+                    }
+                    _ => parent.library.name.clone(),
+                })
+            });
+            entries.push(CatalogEntry {
+                connection: connection.clone(),
+                duration_ms: content.duration_ms,
+                content: content.try_into()?,
+                library,
+                downloads,
+            });
+        }
+        Ok(entries)
+    }
+}
+
+/// Which downloads a catalog lists.
+#[derive(Debug, Clone, Copy)]
+pub enum CatalogScope<'a> {
+    /// Everything on every connection of the user.
+    All,
+    /// A single content of a connection.
+    Content(i32, &'a str),
+    /// The contents of a connection's library or season.
+    Library(i32, &'a str),
+}
+
+impl CatalogScope<'_> {
+    const fn connection(self) -> Option<i32> {
+        match self {
+            Self::All => None,
+            Self::Content(connection_id, _) | Self::Library(connection_id, _) => {
+                Some(connection_id)
+            }
+        }
+    }
+}
+
+/// A downloaded content as listed in player catalogs.
+#[derive(Debug, Clone)]
+pub struct CatalogEntry {
+    pub connection: player_connections::Model,
+    pub content: ContentWithModel,
+    /// Name of the library the content sits in, with the show for seasons.
+    pub library: Option<String>,
+    pub duration_ms: Option<i64>,
+    /// Finished downloads, the most recent first.
+    pub downloads: Vec<VariantDownload>,
+}
+
+/// Fetches further apart than this belong to different playback sessions.
+pub const SESSION_GAP: chrono::Duration = chrono::Duration::minutes(10);
+
+/// Least time between two positions written for a playback session, players
+/// fetch a segment every few seconds.
+pub const POSITION_INTERVAL: chrono::Duration = chrono::Duration::seconds(15);
+
+/// Share of a content that has to be fetched for it to count as watched.
+pub const WATCHED_THRESHOLD: f64 = 0.9;
+
+/// What a recorded position meant for the viewer's playback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// The first fetch in a while, playback (re)started.
+    pub session_started: bool,
+    /// The viewer just got past [`WATCHED_THRESHOLD`].
+    pub played: bool,
+    /// The position is past [`WATCHED_THRESHOLD`], there's nothing to resume.
+    pub watched: bool,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct ContentWithModel {
     #[serde(flatten)]
     pub content: Content,
     pub status: Option<StatusName>,
+    /// Every variant downloaded for this content, the most recent first.
+    #[serde(default)]
+    pub variants: Vec<VariantDownload>,
+    #[serde(default)]
+    pub clips: Vec<clips::Model>,
+    /// `?t=` offset of the last segment fetched through a stream token.
+    #[serde(default)]
+    pub resume_from: Option<String>,
+    /// Share of the content watched, for the progress bar.
+    #[serde(default)]
+    pub progress: Option<f64>,
+    #[serde(default)]
+    pub played: bool,
+    /// Whether `layout` was set by hand rather than detected.
+    #[serde(default)]
+    pub layout_overridden: bool,
+    /// [`VrLayout::label`] of `layout`, for badges.
+    #[serde(default)]
+    pub layout_label: String,
+}
+
+/// A content the provider knows about but that isn't cached, like search hits.
+impl From<Content> for ContentWithModel {
+    fn from(content: Content) -> Self {
+        Self {
+            layout_label: content.layout.label(),
+            content,
+            status: None,
+            variants: vec![],
+            clips: vec![],
+            resume_from: None,
+            progress: None,
+            played: false,
+            layout_overridden: false,
+        }
+    }
 }
 
 impl TryFrom<Model> for ContentWithModel {
     type Error = ModelError;
 
     fn try_from(value: Model) -> Result<Self, Self::Error> {
-        let content = serde_json::from_value(
+        let mut content: Content = serde_json::from_value(
             value
                 .cached_data
                 .clone()
                 .ok_or(ModelError::EntityNotFound)?,
         )
         .map_err(|e| ModelError::Any(e.into()))?;
+        let layout_override: Option<VrLayout> = value
+            .layout_override
+            .clone()
+            .and_then(|layout| serde_json::from_value(layout).ok());
+        if let Some(layout) = layout_override {
+            content.layout = layout;
+        }
         Ok(Self {
+            layout_label: content.layout.label(),
             content,
+            layout_overridden: layout_override.is_some(),
+            variants: vec![],
+            clips: vec![],
+            resume_from: value
+                .resume_position_ms
+                .and_then(|ms| u64::try_from(ms).ok())
+                .filter(|ms| *ms > 0)
+                .map(resume::format_offset),
+            #[allow(clippy::cast_precision_loss)]
+            progress: value
+                .resume_position_ms
+                .zip(value.duration_ms)
+                .filter(|(_, duration)| *duration > 0)
+                .map(|(position, duration)| (position as f64 / duration as f64).clamp(0.0, 1.0)),
+            played: value.played_at.is_some(),
             status: value.status,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content(
+        resume_position_ms: Option<i64>,
+        resume_updated_at: Option<chrono::NaiveDateTime>,
+        played_at: Option<chrono::NaiveDateTime>,
+    ) -> Model {
+        let now = chrono::Utc::now().naive_utc();
+        Model {
+            created_at: now,
+            updated_at: now,
+            player_connection_id: 1,
+            content_id: "episode".to_string(),
+            parent_id: None,
+            cached_data: None,
+            status: None,
+            status_last_updated_at: now,
+            sort_key: 0,
+            resume_position_ms,
+            resume_updated_at,
+            duration_ms: Some(100_000),
+            played_at,
+            layout_override: None,
+            removed_at: None,
+            sort_name: String::new(),
+            production_year: 0,
+            date_added: now,
+            runtime_ms: 0,
+            playing: resume_updated_at.is_some(),
+        }
+    }
+
+    #[test]
+    fn starts_sessions_after_a_gap() {
+        let now = chrono::Utc::now().naive_utc();
+        let first = content(None, None, None)
+            .progress_at(0, now, false)
+            .unwrap();
+        assert!(first.session_started);
+
+        let paused = content(Some(10_000), Some(now - SESSION_GAP), None);
+        let progress = paused.progress_at(12_000, now, false).unwrap();
+        assert!(!progress.session_started);
+
+        let left = content(
+            Some(10_000),
+            Some(now - SESSION_GAP - chrono::Duration::seconds(1)),
+            None,
+        );
+        let progress = left.progress_at(12_000, now, false).unwrap();
+        assert!(progress.session_started);
+    }
+
+    #[test]
+    fn throttles_positions_within_a_session() {
+        let now = chrono::Utc::now().naive_utc();
+        let playing = content(Some(10_000), Some(now - chrono::Duration::seconds(4)), None);
+        assert_eq!(playing.progress_at(14_000, now, false), None);
+        assert!(playing.progress_at(14_000, now, true).is_some());
+
+        let later = content(Some(10_000), Some(now - POSITION_INTERVAL), None);
+        assert!(later.progress_at(25_000, now, false).is_some());
+    }
+
+    #[test]
+    fn marks_played_once_past_the_threshold() {
+        let now = chrono::Utc::now().naive_utc();
+        let recent = Some(now - chrono::Duration::seconds(4));
+        let below = content(Some(80_000), recent, None);
+        assert_eq!(
+            below.progress_at(89_000, now, true).map(|p| p.played),
+            Some(false)
+        );
+
+        // Crossing the threshold is written even when throttled.
+        let progress = below.progress_at(90_000, now, false).unwrap();
+        assert!(progress.played && progress.watched);
+
+        // Fetches after it have nothing left to mark.
+        let watched = content(None, recent, Some(now));
+        assert_eq!(watched.progress_at(95_000, now, false), None);
+        let progress = watched.progress_at(95_000, now, true).unwrap();
+        assert!(progress.watched && !progress.played);
+
+        // Rewatching from a resume point counts again.
+        let rewatch = content(Some(50_000), recent, Some(now));
+        assert!(rewatch.progress_at(92_000, now, false).unwrap().played);
+    }
+
+    #[test]
+    fn labels_the_overridden_layout() {
+        use players::types::{ContentKind, Metadata, Projection, Stereo};
+
+        let cached = Content {
+            id: "episode".to_string(),
+            parent_id: None,
+            name: "Episode".to_string(),
+            description: None,
+            icon_url: None,
+            series_name: None,
+            season_name: None,
+            media_streams: vec![],
+            kind: ContentKind::Movie,
+            media_sources: vec![],
+            layout: VrLayout::default(),
+            chapters: vec![],
+            airs: None,
+            metadata: Metadata::default(),
+        };
+        let detected = Model {
+            cached_data: Some(serde_json::to_value(&cached).unwrap()),
+            ..content(None, None, None)
+        };
+        let item = ContentWithModel::try_from(detected.clone()).unwrap();
+        assert_eq!(item.layout_label, "Flat");
+        assert!(!item.layout_overridden);
+
+        let layout = VrLayout {
+            projection: Projection::Dome,
+            stereo: Stereo::Sbs,
+        };
+        let overridden = Model {
+            layout_override: Some(serde_json::to_value(layout).unwrap()),
+            ..detected
+        };
+        let item = ContentWithModel::try_from(overridden).unwrap();
+        assert_eq!(item.layout_label, "180° SBS");
+        assert!(item.layout_overridden);
+        assert_eq!(ContentWithModel::from(cached).layout_label, "Flat");
+    }
+}
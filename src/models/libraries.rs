@@ -1,8 +1,14 @@
-use super::_entities::libraries::{self, ActiveModel, Model};
+use super::_entities::{
+    contents,
+    libraries::{self, ActiveModel, Model},
+};
 use loco_rs::model::{self, ModelError, ModelResult};
 use migration::OnConflict;
-use players::types::Library;
-use sea_orm::{entity::prelude::*, ActiveValue, QueryTrait, TransactionTrait};
+use players::types::{Library, LibraryKind};
+use sea_orm::{
+    entity::prelude::*, ActiveValue, Condition, Order, QueryOrder, QuerySelect, QueryTrait,
+    TransactionTrait,
+};
 use sqlx_postgres::Postgres;
 
 impl ActiveModelBehavior for ActiveModel {
@@ -22,6 +28,23 @@ impl ActiveModel {
         }
         self
     }
+
+    /// Seasons sort by their number, everything else by name.
+    pub fn sort_columns(mut self, library: &Library) -> Self {
+        self.sort_key = ActiveValue::Set(match library.kind {
+            LibraryKind::Season { season } => i64::from(season.max(0)),
+            _ => 0,
+        });
+        self.sort_name = ActiveValue::Set(
+            library
+                .metadata
+                .sort_name
+                .as_deref()
+                .unwrap_or(&library.name)
+                .to_lowercase(),
+        );
+        self
+    }
 }
 
 impl Model {
@@ -43,9 +66,11 @@ impl Model {
                 ActiveModel {
                     player_connection_id: ActiveValue::Set(connection_id),
                     library_id: ActiveValue::Set(library.id.clone()),
+                    removed_at: ActiveValue::Set(None),
                     ..Default::default()
                 }
                 .parent_id(true_parent_id.or(library.parent_id.as_deref()).as_deref())
+                .sort_columns(library)
                 .cache_data(library)
             })
             .collect::<ModelResult<Vec<_>>>()?;
@@ -56,7 +81,12 @@ impl Model {
                     libraries::Column::PlayerConnectionId,
                     libraries::Column::LibraryId,
                 ])
-                .update_column(libraries::Column::CachedData)
+                .update_columns([
+                    libraries::Column::CachedData,
+                    libraries::Column::SortKey,
+                    libraries::Column::SortName,
+                    libraries::Column::RemovedAt,
+                ])
                 .to_owned(),
             )
             .exec(&txn)
@@ -79,11 +109,48 @@ impl Model {
                     .eq(libraries::Column::ParentId, parent_id)
                     .build(),
             )
+            .filter(libraries::Column::RemovedAt.is_null())
+            .order_by_asc(libraries::Column::SortKey)
+            .order_by_asc(libraries::Column::SortName)
+            .order_by_asc(libraries::Column::LibraryId)
             .all(db)
             .await?;
         Ok(libraries)
     }
 
+    /// Up to `limit` children of a library by `(sort_key, sort_name, library_id)`,
+    /// after the library `after` when given.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn page_by_connection_and_parent_id(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        parent_id: &str,
+        after: Option<&str>,
+        limit: u64,
+    ) -> ModelResult<Vec<Model>> {
+        let mut libraries = libraries::Entity::find()
+            .filter(libraries::Column::PlayerConnectionId.eq(connection_id))
+            .filter(libraries::Column::ParentId.eq(parent_id))
+            .filter(libraries::Column::RemovedAt.is_null());
+        if let Some(library_id) = after {
+            libraries = libraries.filter(Expr::cust_with_values(
+                "(sort_key, sort_name, library_id) > (SELECT sort_key, sort_name, library_id \
+                 FROM libraries WHERE player_connection_id = $1 AND library_id = $2)",
+                [Value::from(connection_id), Value::from(library_id)],
+            ));
+        }
+        Ok(libraries
+            .order_by_asc(libraries::Column::SortKey)
+            .order_by_asc(libraries::Column::SortName)
+            .order_by_asc(libraries::Column::LibraryId)
+            .limit(limit)
+            .all(db)
+            .await?)
+    }
+
     pub async fn find_by_connection_and_id(
         db: &DatabaseConnection,
         connection_id: i32,
@@ -96,10 +163,165 @@ impl Model {
                     .eq(libraries::Column::LibraryId, library_id)
                     .build(),
             )
+            .filter(libraries::Column::RemovedAt.is_null())
             .one(db)
             .await?;
         library.ok_or_else(|| ModelError::EntityNotFound)
     }
+
+    /// Cached libraries matching a [`prefix_query`](crate::common::search::prefix_query),
+    /// the best matches first.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn search(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        query: &str,
+        limit: u64,
+    ) -> ModelResult<Vec<Model>> {
+        Ok(libraries::Entity::find()
+            .filter(libraries::Column::PlayerConnectionId.eq(connection_id))
+            .filter(libraries::Column::RemovedAt.is_null())
+            .filter(Expr::cust_with_values(
+                "search @@ to_tsquery('simple', $1)",
+                [query],
+            ))
+            .order_by(
+                Expr::cust_with_values("ts_rank(search, to_tsquery('simple', $1))", [query]),
+                Order::Desc,
+            )
+            .order_by_asc(libraries::Column::SortKey)
+            .limit(limit)
+            .all(db)
+            .await?)
+    }
+
+    /// Ids of a library's cached children that weren't removed.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn ids_by_connection_and_parent_id(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        parent_id: &str,
+    ) -> ModelResult<Vec<String>> {
+        Ok(libraries::Entity::find()
+            .select_only()
+            .column(libraries::Column::LibraryId)
+            .filter(libraries::Column::PlayerConnectionId.eq(connection_id))
+            .filter(libraries::Column::ParentId.eq(parent_id))
+            .filter(libraries::Column::RemovedAt.is_null())
+            .into_tuple()
+            .all(db)
+            .await?)
+    }
+
+    /// Marks a library and everything under it as removed on the given
+    /// connections, returning how many items were cached.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn tombstone(
+        db: &DatabaseConnection,
+        connection_ids: &[i32],
+        library_id: &str,
+    ) -> ModelResult<u64> {
+        let removed = libraries::Entity::update_many()
+            .col_expr(
+                libraries::Column::RemovedAt,
+                Expr::value(Some(chrono::Utc::now().naive_utc())),
+            )
+            .filter(libraries::Column::PlayerConnectionId.is_in(connection_ids.iter().copied()))
+            .filter(libraries::Column::LibraryId.eq(library_id))
+            .filter(libraries::Column::RemovedAt.is_null())
+            .exec_with_returning(db)
+            .await?;
+        Ok(removed.len() as u64 + Self::tombstone_children(db, removed).await?)
+    }
+
+    /// Marks a library's children that aren't in `present` as removed, along
+    /// with everything under them, keeping the rows around. Returns how many
+    /// items were.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn tombstone_missing(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        parent_id: &str,
+        present: &[String],
+    ) -> ModelResult<u64> {
+        let removed = libraries::Entity::update_many()
+            .col_expr(
+                libraries::Column::RemovedAt,
+                Expr::value(Some(chrono::Utc::now().naive_utc())),
+            )
+            .filter(libraries::Column::PlayerConnectionId.eq(connection_id))
+            .filter(libraries::Column::ParentId.eq(parent_id))
+            .filter(libraries::Column::RemovedAt.is_null())
+            .filter(libraries::Column::LibraryId.is_not_in(present.iter().cloned()))
+            .exec_with_returning(db)
+            .await?;
+        Ok(removed.len() as u64 + Self::tombstone_children(db, removed).await?)
+    }
+
+    /// Tombstones what's cached under the removed libraries, level by level, so
+    /// a removed show doesn't leave its seasons and episodes listed in search
+    /// and subscriptions.
+    async fn tombstone_children(
+        db: &DatabaseConnection,
+        mut removed: Vec<Model>,
+    ) -> ModelResult<u64> {
+        let mut count = 0;
+        while !removed.is_empty() {
+            let now = chrono::Utc::now().naive_utc();
+            count += contents::Entity::update_many()
+                .col_expr(contents::Column::RemovedAt, Expr::value(Some(now)))
+                .filter(children_of(
+                    &removed,
+                    contents::Column::PlayerConnectionId,
+                    contents::Column::ParentId,
+                ))
+                .filter(contents::Column::RemovedAt.is_null())
+                .exec(db)
+                .await?
+                .rows_affected;
+            removed = libraries::Entity::update_many()
+                .col_expr(libraries::Column::RemovedAt, Expr::value(Some(now)))
+                .filter(children_of(
+                    &removed,
+                    libraries::Column::PlayerConnectionId,
+                    libraries::Column::ParentId,
+                ))
+                .filter(libraries::Column::RemovedAt.is_null())
+                .exec_with_returning(db)
+                .await?;
+            count += removed.len() as u64;
+        }
+        Ok(count)
+    }
+}
+
+/// Matches the rows whose parent is one of the libraries.
+fn children_of(
+    libraries: &[Model],
+    connection_column: impl ColumnTrait,
+    parent_column: impl ColumnTrait,
+) -> Condition {
+    libraries
+        .iter()
+        .fold(Condition::any(), |condition, library| {
+            condition.add(
+                Condition::all()
+                    .add(connection_column.eq(library.player_connection_id))
+                    .add(parent_column.eq(&library.library_id)),
+            )
+        })
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
@@ -1,6 +1,10 @@
 pub mod _entities;
+pub mod clips;
 pub mod content_downloads;
 pub mod contents;
 pub mod libraries;
 pub mod player_connections;
+pub mod shared_transcodes;
+pub mod short_links;
+pub mod subscriptions;
 pub mod users;
@@ -1,11 +1,21 @@
+use std::collections::{HashMap, HashSet};
+
 use loco_rs::{
     db,
     model::{self, ModelError, ModelResult},
 };
-use players::types::{Content, Item, Library};
-use sea_orm::{entity::prelude::*, ActiveValue, TransactionTrait};
+use players::types::{Content, Item, ItemsQuery, Library, Shelf};
+use sea_orm::{entity::prelude::*, ActiveValue, Condition, TransactionTrait};
 
-use crate::{initializers::media_provider::ConnectedMediaProvider, models::_entities::libraries};
+use crate::{
+    common::{
+        library_sync::SyncStatus,
+        pagination::{Cursor, Section, SortOrder, FETCH_PAGE_SIZE, PAGE_SIZE},
+        search::{self, SEARCH_LIMIT},
+    },
+    initializers::media_provider::ConnectedMediaProvider,
+    models::_entities::libraries,
+};
 
 pub use super::_entities::player_connections::{self, ActiveModel, Entity, Model};
 use super::{contents::ContentWithModel, libraries::LibraryWithModel};
@@ -45,46 +55,390 @@ impl super::_entities::player_connections::Model {
         user.ok_or_else(|| ModelError::EntityNotFound)
     }
 
+    /// Finds the connection a `/p/stream` token belongs to.
+    ///
+    /// # Errors
+    ///
+    /// When there is no such connection or DB query error
+    pub async fn find_by_stream_token(db: &DatabaseConnection, token: Uuid) -> ModelResult<Self> {
+        player_connections::Entity::find()
+            .filter(player_connections::Column::StreamToken.eq(token))
+            .one(db)
+            .await?
+            .ok_or(ModelError::EntityNotFound)
+    }
+
+    /// Connections to a media provider, or to any when `None`.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn by_media_provider(
+        db: &DatabaseConnection,
+        media_provider_id: Option<&str>,
+    ) -> ModelResult<Vec<Self>> {
+        let mut connections = player_connections::Entity::find();
+        if let Some(media_provider_id) = media_provider_id {
+            connections = connections
+                .filter(player_connections::Column::MediaProviderId.eq(media_provider_id));
+        }
+        Ok(connections.all(db).await?)
+    }
+
+    /// How the last library sync went, the default before the first one.
+    #[must_use]
+    pub fn sync_status(&self) -> SyncStatus {
+        self.status
+            .clone()
+            .and_then(|status| serde_json::from_value(status).ok())
+            .unwrap_or_default()
+    }
+
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn set_sync_status(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        status: &SyncStatus,
+    ) -> ModelResult<()> {
+        let status = serde_json::to_value(status).map_err(|e| ModelError::Any(e.into()))?;
+        ActiveModel {
+            id: ActiveValue::Set(connection_id),
+            status: ActiveValue::Set(Some(status)),
+            ..Default::default()
+        }
+        .update(db)
+        .await?;
+        Ok(())
+    }
+
+    /// Whether a library sync of the connection is underway, in any process.
+    #[must_use]
+    pub fn is_syncing(&self, now: chrono::NaiveDateTime) -> bool {
+        self.syncing_since
+            .is_some_and(|since| now - since < SYNC_LOCK_TIMEOUT)
+    }
+
+    /// Claims the connection for a library sync, `false` when another one holds
+    /// it. Claims older than [`SYNC_LOCK_TIMEOUT`] are taken over, they belong
+    /// to syncs that died without releasing them.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn start_sync(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        now: chrono::NaiveDateTime,
+    ) -> ModelResult<bool> {
+        let claimed = player_connections::Entity::update_many()
+            .col_expr(
+                player_connections::Column::SyncingSince,
+                Expr::value(Some(now)),
+            )
+            .filter(player_connections::Column::Id.eq(connection_id))
+            .filter(
+                Condition::any()
+                    .add(player_connections::Column::SyncingSince.is_null())
+                    .add(player_connections::Column::SyncingSince.lt(now - SYNC_LOCK_TIMEOUT)),
+            )
+            .exec(db)
+            .await?;
+        Ok(claimed.rows_affected == 1)
+    }
+
+    /// Releases the claim taken by [`Self::start_sync`].
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn end_sync(db: &DatabaseConnection, connection_id: i32) -> ModelResult<()> {
+        player_connections::Entity::update_many()
+            .col_expr(
+                player_connections::Column::SyncingSince,
+                Expr::value(Option::<chrono::NaiveDateTime>::None),
+            )
+            .filter(player_connections::Column::Id.eq(connection_id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// A page of a library's children, libraries first, then contents.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached or cached data is invalid.
     pub async fn items(
         db: &DatabaseConnection,
         connection_id: i32,
         parent_id: &str,
-    ) -> ModelResult<Vec<WrappedItem>> {
-        let libraries = super::_entities::libraries::Model::find_by_connection_and_parent_id(
+        order: SortOrder,
+        after: Option<&Cursor>,
+        limit: u64,
+    ) -> ModelResult<Page> {
+        let mut items = vec![];
+        let mut last_library = None;
+        if after.is_none_or(|cursor| cursor.section == Section::Libraries) {
+            let mut libraries = libraries::Model::page_by_connection_and_parent_id(
+                db,
+                connection_id,
+                parent_id,
+                Cursor::after_in(after, Section::Libraries),
+                limit + 1,
+            )
+            .await?;
+            let more = libraries.len() as u64 > limit;
+            libraries.truncate(usize::try_from(limit).unwrap_or(usize::MAX));
+            last_library = libraries
+                .last()
+                .map(|library| Cursor::new(Section::Libraries, &library.library_id));
+            for library in libraries {
+                items.push(WrappedItem::Library(library.try_into()?));
+            }
+            if more {
+                return Ok(Page {
+                    items,
+                    next: last_library,
+                });
+            }
+        }
+
+        let remaining = limit - items.len() as u64;
+        let mut contents = super::_entities::contents::Model::page_by_connection_and_parent_id(
             db,
             connection_id,
-            Some(parent_id),
+            parent_id,
+            order,
+            Cursor::after_in(after, Section::Contents),
+            remaining + 1,
         )
         .await?;
-        let contents = super::_entities::contents::Model::by_connection_and_parent_id(
+        let mut next = None;
+        if contents.len() as u64 > remaining {
+            contents.truncate(usize::try_from(remaining).unwrap_or(usize::MAX));
+            // A page filled up by libraries continues with the contents.
+            next = contents.last().map_or(last_library, |content| {
+                Some(Cursor::new(Section::Contents, &content.content_id))
+            });
+        }
+        items.extend(Self::with_downloads(db, connection_id, contents).await?);
+        Ok(Page { items, next })
+    }
+
+    /// Cached contents along with their downloads and clips.
+    async fn with_downloads(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        contents: Vec<super::_entities::contents::Model>,
+    ) -> ModelResult<Vec<WrappedItem>> {
+        let content_ids: Vec<String> = contents.iter().map(|c| c.content_id.clone()).collect();
+        let mut variants = super::_entities::content_downloads::Model::variants_by_contents(
             db,
             connection_id,
-            Some(parent_id),
+            &content_ids,
         )
         .await?;
-        Ok(libraries
+        let mut clips =
+            super::_entities::clips::Model::by_contents(db, connection_id, &content_ids).await?;
+        let mut items = vec![];
+        for content in contents {
+            let mut content: ContentWithModel = content.try_into()?;
+            content.variants = variants.remove(&content.content.id).unwrap_or_default();
+            content.clips = clips.remove(&content.content.id).unwrap_or_default();
+            items.push(WrappedItem::Content(content));
+        }
+        Ok(items)
+    }
+
+    /// Up to `limit` contents the provider suggests on a shelf. Cached ones come
+    /// with their downloads, so ones already transcoded can be played right away.
+    ///
+    /// # Errors
+    ///
+    /// When the provider can't be reached or the database can't be reached.
+    pub async fn shelf(
+        db: &DatabaseConnection,
+        provider: &ConnectedMediaProvider,
+        connection_id: i32,
+        shelf: Shelf,
+        limit: usize,
+    ) -> ModelResult<Vec<WrappedItem>> {
+        let contents = provider
+            .shelf(shelf, limit)
+            .await
+            .map_err(|e| ModelError::Any(e.into()))?;
+        let ids: Vec<String> = contents.iter().map(|content| content.id.clone()).collect();
+        let cached =
+            super::_entities::contents::Model::by_connection_and_ids(db, connection_id, &ids)
+                .await?;
+        let mut cached: HashMap<String, WrappedItem> =
+            Self::with_downloads(db, connection_id, cached)
+                .await?
+                .into_iter()
+                .map(|item| (item.id().to_string(), item))
+                .collect();
+        Ok(contents
             .into_iter()
-            .map(|library| WrappedItem::Library(library.try_into().unwrap()))
-            .chain(
-                contents
-                    .into_iter()
-                    .map(|content| WrappedItem::Content(content.try_into().unwrap())),
-            )
+            .map(|content| {
+                cached
+                    .remove(&content.id)
+                    .unwrap_or_else(|| WrappedItem::Content(content.into()))
+            })
             .collect())
     }
 
+    /// A library or content of the connection by id, from the cache or else the
+    /// provider, which knows about the shelves and search results not cached.
+    ///
+    /// # Errors
+    ///
+    /// When neither has the item or the database can't be reached.
+    pub async fn item(
+        db: &DatabaseConnection,
+        provider: &ConnectedMediaProvider,
+        connection: &Model,
+        item_id: &str,
+    ) -> ModelResult<WrappedItem> {
+        match super::_entities::contents::Model::by_connection_and_id(db, connection.id, item_id)
+            .await
+        {
+            Ok(content) => return content.try_into(),
+            Err(ModelError::EntityNotFound) => {}
+            Err(e) => return Err(e),
+        }
+        match libraries::Model::find_by_connection_and_id(db, connection.id, item_id).await {
+            Ok(library) => return library.try_into(),
+            Err(ModelError::EntityNotFound) => {}
+            Err(e) => return Err(e),
+        }
+        if connection.root_libraries.is_some() {
+            let roots: Vec<WrappedItem> = connection.clone().try_into()?;
+            if let Some(root) = roots.into_iter().find(|root| root.id() == item_id) {
+                return Ok(root);
+            }
+        }
+        provider.item(item_id).await.map(Into::into).map_err(|e| {
+            tracing::debug!(error = ?e, item_id, "Item not found on the provider");
+            ModelError::EntityNotFound
+        })
+    }
+
+    /// Libraries and contents matching `term`, cached ones first. When the cache
+    /// doesn't come up with a full page, the provider is searched too, for items
+    /// that weren't cached yet. The provider being unreachable only leaves out its
+    /// results.
+    ///
+    /// # Errors
+    ///
+    /// When the user has no such connection or the database can't be reached.
+    pub async fn search(
+        db: &DatabaseConnection,
+        user_id: i32,
+        connection_id: i32,
+        term: &str,
+    ) -> ModelResult<(Model, ConnectedMediaProvider, Vec<WrappedItem>)> {
+        let connection = Self::find_by_user_and_id(db, user_id, connection_id).await?;
+        let provider: ConnectedMediaProvider = connection
+            .clone()
+            .try_into()
+            .map_err(|e: loco_rs::Error| ModelError::Any(e.into()))?;
+        let Some(query) = search::prefix_query(term) else {
+            return Ok((connection, provider, vec![]));
+        };
+
+        let mut items = vec![];
+        for library in libraries::Model::search(db, connection_id, &query, SEARCH_LIMIT).await? {
+            items.push(WrappedItem::Library(library.try_into()?));
+        }
+        let contents =
+            super::_entities::contents::Model::search(db, connection_id, &query, SEARCH_LIMIT)
+                .await?;
+        items.extend(Self::with_downloads(db, connection_id, contents).await?);
+
+        if (items.len() as u64) < SEARCH_LIMIT {
+            let limit = usize::try_from(SEARCH_LIMIT).unwrap_or(usize::MAX);
+            match provider.search(term, limit).await {
+                Ok(found) => {
+                    let known: HashSet<String> =
+                        items.iter().map(|item| item.id().to_string()).collect();
+                    items.extend(
+                        found
+                            .into_iter()
+                            .map(WrappedItem::from)
+                            .filter(|item| !known.contains(item.id())),
+                    );
+                }
+                Err(e) => {
+                    tracing::warn!(error = ?e, connection_id, "provider search failed, only showing cached results");
+                }
+            }
+        }
+        Ok((connection, provider, items))
+    }
+
+    /// Caches a library's children, fetching them from the provider page by page
+    /// so big libraries neither time out nor end up in one huge transaction.
+    async fn refresh_items(
+        db: &DatabaseConnection,
+        provider: &ConnectedMediaProvider,
+        connection_id: i32,
+        library_id: &str,
+    ) -> ModelResult<()> {
+        let start = std::time::Instant::now();
+        let library = Library::from_path(library_id);
+        let mut fetched = 0;
+        loop {
+            let page = provider
+                .items_page(&library, fetched, FETCH_PAGE_SIZE, ItemsQuery::default())
+                .await
+                .map_err(|e: loco_rs::Error| ModelError::Any(e.into()))?;
+            let mut libraries = vec![];
+            let mut contents = vec![];
+            for item in &page.items {
+                match item {
+                    Item::Library(library) => libraries.push(library),
+                    Item::Content(content) => contents.push(content),
+                }
+            }
+            super::_entities::libraries::Model::upsert_cache_data(
+                db,
+                connection_id,
+                &libraries,
+                Some(library_id),
+            )
+            .await?;
+            super::_entities::contents::Model::upsert_cache_data(
+                db,
+                connection_id,
+                &contents,
+                Some(library_id),
+            )
+            .await?;
+            // Excluded libraries make pages shorter, the provider's total is what counts.
+            fetched += FETCH_PAGE_SIZE;
+            let done = match page.total {
+                Some(total) => fetched >= total,
+                None => page.items.len() < FETCH_PAGE_SIZE,
+            };
+            if done {
+                break;
+            }
+        }
+        tracing::debug!(elapsed = ?start.elapsed(), library_id, "fetched fresh items from provider");
+        Ok(())
+    }
+
     pub async fn library_and_items(
         db: &DatabaseConnection,
         user_id: i32,
         connection_id: i32,
         library_id: Option<&str>,
+        order: SortOrder,
+        after: Option<&Cursor>,
         force_update: bool,
-    ) -> ModelResult<(
-        Model,
-        ConnectedMediaProvider,
-        Option<WrappedItem>,
-        Vec<WrappedItem>,
-    )> {
+    ) -> ModelResult<(Model, ConnectedMediaProvider, Option<WrappedItem>, Page)> {
         // find the player connection, this also makes sure the user owns the connection
         let connection = Self::find_by_user_and_id(db, user_id, connection_id).await?;
         let provider: ConnectedMediaProvider = connection
@@ -131,63 +485,25 @@ impl super::_entities::player_connections::Model {
                     }
                     .try_into()?;
 
-                let items: Vec<WrappedItem> =
-                    match Self::items(
-                        db,
-                        connection_id,
-                        library_id,
-                    )
-                    .await
-                    {
-                        Ok(items) if !force_update && !items.is_empty() => items,
-                        Ok(_) | Err(ModelError::EntityNotFound) => {
-                            let start = std::time::Instant::now();
-                            let items = provider.items(Some(Library::from_path(library_id)))
-                                .await
-                                .map_err(|e: loco_rs::Error| ModelError::Any(e.into()))?;
-                            tracing::debug!(elapsed = ?start.elapsed(), items = items.len(), "fetched fresh items from provider");
-                            let mut libraries = vec![];
-                            let mut contents = vec![];
-                            for item in &items {
-                                match item {
-                                    Item::Library(library) => {
-                                        libraries.push(library);
-                                    }
-                                    Item::Content(content) => {
-                                        contents.push(content);
-                                    }
-                                }
-                            }
-                            super::_entities::libraries::Model::upsert_cache_data(
-                                db,
-                                connection_id,
-                                &libraries,
-                                Some(library_id),
-                            )
-                            .await?;
-                            super::_entities::contents::Model::upsert_cache_data(
-                                db,
-                                connection_id,
-                                &contents,
-                                Some(library_id),
-                            ).await?;
-                            Self::items(
-                                db,
-                                connection_id,
-                                library_id,
-                            ).await?
-                        }
-                        Err(e) => return Err(e),
-                    }
-                    .into_iter()
-                    .collect();
+                // Later pages come from the cache the first one filled.
+                let refresh = after.is_none() && force_update;
+                let mut page = if refresh {
+                    Page::default()
+                } else {
+                    Self::items(db, connection_id, library_id, order, after, PAGE_SIZE).await?
+                };
+                if refresh || (after.is_none() && page.items.is_empty()) {
+                    Self::refresh_items(db, &provider, connection_id, library_id).await?;
+                    page =
+                        Self::items(db, connection_id, library_id, order, None, PAGE_SIZE).await?;
+                }
 
-                Ok((connection, provider, Some(library), items))
+                Ok((connection, provider, Some(library), page))
             }
             None => match &connection.root_libraries {
                 Some(_) if !force_update => {
                     let libraries: Vec<WrappedItem> = connection.clone().try_into()?;
-                    Ok((connection, provider, None, libraries))
+                    Ok((connection, provider, None, Page::from(libraries)))
                 }
                 _ => {
                     let items = provider
@@ -219,7 +535,7 @@ impl super::_entities::player_connections::Model {
                             })
                         })
                         .collect();
-                    Ok((connection, provider, None, libraries))
+                    Ok((connection, provider, None, Page::from(libraries)))
                 }
             },
         }
@@ -242,6 +558,22 @@ impl super::_entities::player_connections::Model {
     }
 }
 
+/// A page of a library listing.
+#[derive(Debug, Clone, Default)]
+pub struct Page {
+    pub items: Vec<WrappedItem>,
+    /// Where the next page starts, `None` on the last one.
+    pub next: Option<Cursor>,
+}
+
+impl From<Vec<WrappedItem>> for Page {
+    fn from(items: Vec<WrappedItem>) -> Self {
+        Self { items, next: None }
+    }
+}
+
+// Items only live for a page render, boxing contents isn't worth it.
+#[allow(clippy::large_enum_variant)]
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 #[serde(tag = "type")]
 pub enum WrappedItem {
@@ -249,6 +581,36 @@ pub enum WrappedItem {
     Library(LibraryWithModel),
 }
 
+impl WrappedItem {
+    #[must_use]
+    pub fn id(&self) -> &str {
+        match self {
+            WrappedItem::Content(content) => &content.content.id,
+            WrappedItem::Library(library) => &library.library.id,
+        }
+    }
+
+    #[must_use]
+    pub fn icon_url(&self) -> Option<&str> {
+        match self {
+            WrappedItem::Content(content) => content.content.icon_url.as_deref(),
+            WrappedItem::Library(library) => library.library.icon_url.as_deref(),
+        }
+    }
+}
+
+impl From<Item> for WrappedItem {
+    fn from(item: Item) -> Self {
+        match item {
+            Item::Content(content) => WrappedItem::Content(content.into()),
+            Item::Library(library) => WrappedItem::Library(LibraryWithModel {
+                parent_id: library.parent_id.clone(),
+                library,
+            }),
+        }
+    }
+}
+
 impl TryFrom<super::_entities::contents::Model> for WrappedItem {
     type Error = ModelError;
 
@@ -288,3 +650,6 @@ impl TryFrom<super::_entities::player_connections::Model> for Vec<WrappedItem> {
             .collect())
     }
 }
+
+/// How long a library sync may hold its connection before others take over.
+pub const SYNC_LOCK_TIMEOUT: chrono::Duration = chrono::Duration::hours(1);
@@ -0,0 +1,224 @@
+use super::_entities::{
+    content_downloads, contents,
+    sea_orm_active_enums::StatusName,
+    shared_transcodes::{self, ActiveModel, Model},
+};
+use crate::common::variants::shared_key;
+use loco_rs::model::{ModelError, ModelResult};
+use sea_orm::{
+    entity::prelude::*, sea_query::OnConflict, ActiveValue, IntoActiveModel, QuerySelect,
+    TransactionTrait,
+};
+
+impl ActiveModelBehavior for ActiveModel {
+    // extend activemodel below (keep comment for generators)
+}
+
+/// What a download has to do after joining a shared transcode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lease {
+    /// Nobody has the transcode (or the last attempt failed), the download has to
+    /// fetch it.
+    Owner,
+    /// Another download is fetching it, this one finishes along with it.
+    Waiting,
+    /// Already in the cache, the download is done.
+    Ready,
+}
+
+impl Model {
+    /// Links a download to the shared transcode of its variant, creating the entry
+    /// when there is none. The caller is responsible for checking that the user can
+    /// access the content on the provider.
+    ///
+    /// # Errors
+    ///
+    /// When the download doesn't exist or the database can't be reached.
+    pub async fn acquire(
+        db: &DatabaseConnection,
+        media_provider_id: &str,
+        download: &content_downloads::Model,
+    ) -> ModelResult<(Self, Lease)> {
+        let variant_id = download
+            .variant_id
+            .clone()
+            .ok_or_else(|| ModelError::Any(eyre::eyre!("Download has no variant").into()))?;
+        let key = shared_key(media_provider_id, &download.content_id, &variant_id);
+        let txn = db.begin().await?;
+
+        let inserted = shared_transcodes::Entity::insert(ActiveModel {
+            key: ActiveValue::Set(key.clone()),
+            media_provider_id: ActiveValue::Set(media_provider_id.to_string()),
+            content_id: ActiveValue::Set(download.content_id.clone()),
+            variant_id: ActiveValue::Set(variant_id),
+            status: ActiveValue::Set(StatusName::InProgress),
+            ref_count: ActiveValue::Set(0),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::column(shared_transcodes::Column::Key)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(&txn)
+        .await?;
+
+        let shared = shared_transcodes::Entity::find_by_id(key.clone())
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(ModelError::EntityNotFound)?;
+
+        let lease = match shared.status {
+            StatusName::Success if inserted == 0 => Lease::Ready,
+            StatusName::InProgress if inserted == 0 => Lease::Waiting,
+            _ => Lease::Owner,
+        };
+
+        let ref_count = shared.ref_count + 1;
+        let outputs = shared.outputs.clone();
+        let mut shared = shared.into_active_model();
+        shared.ref_count = ActiveValue::Set(ref_count);
+        shared.updated_at = ActiveValue::Set(chrono::Utc::now().naive_utc());
+        if lease == Lease::Owner {
+            shared.status = ActiveValue::Set(StatusName::InProgress);
+        }
+        let shared = shared.update(&txn).await?;
+
+        let mut linked = download.clone().into_active_model();
+        linked.shared_key = ActiveValue::Set(Some(key));
+        if lease == Lease::Ready {
+            linked.status = ActiveValue::Set(StatusName::Success);
+            linked.outputs = ActiveValue::Set(outputs);
+            contents::ActiveModel {
+                player_connection_id: ActiveValue::Set(download.player_connection_id),
+                content_id: ActiveValue::Set(download.content_id.clone()),
+                ..Default::default()
+            }
+            .status(Some(StatusName::Success))
+            .update(&txn)
+            .await?;
+        }
+        linked.update(&txn).await?;
+
+        txn.commit().await?;
+
+        Ok((shared, lease))
+    }
+
+    /// Marks the shared transcode as done with the outcome of the download that
+    /// fetched it and hands its outputs to the downloads waiting on it, which are
+    /// returned so their users can be notified.
+    ///
+    /// # Errors
+    ///
+    /// When the download doesn't exist or the database can't be reached.
+    pub async fn complete(
+        db: &DatabaseConnection,
+        owner_download_id: Uuid,
+        status: StatusName,
+    ) -> ModelResult<Vec<content_downloads::Model>> {
+        let txn = db.begin().await?;
+
+        let owner = content_downloads::Entity::find_by_id(owner_download_id)
+            .one(&txn)
+            .await?
+            .ok_or(ModelError::EntityNotFound)?;
+        let Some(key) = owner.shared_key.clone() else {
+            return Ok(vec![]);
+        };
+
+        let mut shared = shared_transcodes::Entity::find_by_id(key.clone())
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(ModelError::EntityNotFound)?
+            .into_active_model();
+        shared.status = ActiveValue::Set(status);
+        shared.outputs = ActiveValue::Set(owner.outputs.clone());
+        shared.updated_at = ActiveValue::Set(chrono::Utc::now().naive_utc());
+        shared.update(&txn).await?;
+
+        let waiting = content_downloads::Entity::find()
+            .filter(content_downloads::Column::SharedKey.eq(key))
+            .filter(content_downloads::Column::Status.eq(StatusName::InProgress))
+            .filter(content_downloads::Column::Id.ne(owner_download_id))
+            .all(&txn)
+            .await?;
+        let mut done = Vec::with_capacity(waiting.len());
+        for download in waiting {
+            let mut download = download.into_active_model();
+            download.outputs = ActiveValue::Set(owner.outputs.clone());
+            done.push(download.update(&txn).await?);
+        }
+
+        txn.commit().await?;
+
+        Ok(done)
+    }
+
+    /// Drops a download's reference to the shared transcode, so it can be
+    /// collected once nobody uses it.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn release<C: ConnectionTrait>(db: &C, key: &str) -> ModelResult<()> {
+        let Some(shared) = shared_transcodes::Entity::find_by_id(key.to_string())
+            .lock_exclusive()
+            .one(db)
+            .await?
+        else {
+            return Ok(());
+        };
+        let ref_count = (shared.ref_count - 1).max(0);
+        let mut shared = shared.into_active_model();
+        shared.ref_count = ActiveValue::Set(ref_count);
+        shared.updated_at = ActiveValue::Set(chrono::Utc::now().naive_utc());
+        shared.update(db).await?;
+        Ok(())
+    }
+
+    /// Recounts the downloads linked to every shared transcode and drops the
+    /// finished ones nobody references anymore. Returns the dropped entries so
+    /// the caller can remove their files.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn collect_garbage(db: &DatabaseConnection) -> ModelResult<Vec<Self>> {
+        let mut dropped = vec![];
+        for shared in shared_transcodes::Entity::find().all(db).await? {
+            let txn = db.begin().await?;
+            let ref_count = content_downloads::Entity::find()
+                .filter(content_downloads::Column::SharedKey.eq(shared.key.clone()))
+                .count(&txn)
+                .await?;
+            let ref_count = i32::try_from(ref_count).map_err(|e| ModelError::Any(e.into()))?;
+            if ref_count == 0 && shared.status != StatusName::InProgress {
+                shared_transcodes::Entity::delete_by_id(shared.key.clone())
+                    .exec(&txn)
+                    .await?;
+                dropped.push(shared);
+            } else if ref_count != shared.ref_count {
+                let mut shared = shared.into_active_model();
+                shared.ref_count = ActiveValue::Set(ref_count);
+                shared.update(&txn).await?;
+            }
+            txn.commit().await?;
+        }
+        Ok(dropped)
+    }
+
+    /// Where the transcode lives, relative to the transcoding dir.
+    #[must_use]
+    pub fn storage_path(&self) -> String {
+        storage_path(&self.key)
+    }
+}
+
+/// Storage dir of a shared transcode, relative to the transcoding dir.
+#[must_use]
+pub fn storage_path(key: &str) -> String {
+    format!("shared/{key}")
+}
@@ -0,0 +1,139 @@
+use rand::Rng;
+use sea_orm::{entity::prelude::*, ActiveValue, QueryOrder};
+
+use super::_entities::short_links::{self, ActiveModel, Model};
+use loco_rs::model::{ModelError, ModelResult};
+
+/// Characters of generated codes, without look-alikes like `0`/`o` or `1`/`l`
+/// that are hard to tell apart on a VR keyboard.
+const CODE_ALPHABET: &[u8] = b"23456789abcdefghjkmnpqrstuvwxyz";
+const CODE_LENGTH: usize = 4;
+/// Tries at finding an unused code before making codes longer.
+const CODE_ATTEMPTS: usize = 5;
+
+impl ActiveModelBehavior for ActiveModel {
+    // extend activemodel below (keep comment for generators)
+}
+
+fn generate_code(length: usize) -> String {
+    let mut rng = rand::thread_rng();
+    (0..length)
+        .map(|_| char::from(CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())]))
+        .collect()
+}
+
+impl Model {
+    /// Creates a link to `target`, a path under `/p/stream/` with its query.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn create(
+        db: &DatabaseConnection,
+        user_id: i32,
+        target: &str,
+        expires_at: Option<DateTime>,
+    ) -> ModelResult<Self> {
+        let mut length = CODE_LENGTH;
+        let code = 'found: loop {
+            for _ in 0..CODE_ATTEMPTS {
+                let code = generate_code(length);
+                let taken = short_links::Entity::find()
+                    .filter(short_links::Column::Code.eq(&code))
+                    .one(db)
+                    .await?
+                    .is_some();
+                if !taken {
+                    break 'found code;
+                }
+            }
+            length += 1;
+        };
+        let link = ActiveModel {
+            code: ActiveValue::Set(code),
+            user_id: ActiveValue::Set(user_id),
+            target: ActiveValue::Set(target.to_string()),
+            expires_at: ActiveValue::Set(expires_at),
+            uses: ActiveValue::Set(0),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+        Ok(link)
+    }
+
+    /// The link behind `code`, counting the use.
+    ///
+    /// # Errors
+    ///
+    /// When there is no such link, it expired or the database can't be reached.
+    pub async fn follow(db: &DatabaseConnection, code: &str) -> ModelResult<Self> {
+        let mut link = Self::find_by_code(db, code).await?;
+        let now = chrono::Utc::now().naive_utc();
+        if link.is_expired(now) {
+            return Err(ModelError::EntityNotFound);
+        }
+        short_links::Entity::update_many()
+            .col_expr(
+                short_links::Column::Uses,
+                Expr::col(short_links::Column::Uses).add(1),
+            )
+            .col_expr(short_links::Column::LastUsedAt, Expr::value(Some(now)))
+            .filter(short_links::Column::Id.eq(link.id))
+            .exec(db)
+            .await?;
+        link.uses += 1;
+        link.last_used_at = Some(now);
+        Ok(link)
+    }
+
+    /// The link behind `code` without counting a use, expired ones included.
+    ///
+    /// # Errors
+    ///
+    /// When there is no such link or the database can't be reached.
+    pub async fn find_by_code(db: &DatabaseConnection, code: &str) -> ModelResult<Self> {
+        short_links::Entity::find()
+            .filter(short_links::Column::Code.eq(code))
+            .one(db)
+            .await?
+            .ok_or(ModelError::EntityNotFound)
+    }
+
+    /// The user's links, the newest first.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn by_user(db: &DatabaseConnection, user_id: i32) -> ModelResult<Vec<Self>> {
+        Ok(short_links::Entity::find()
+            .filter(short_links::Column::UserId.eq(user_id))
+            .order_by_desc(short_links::Column::CreatedAt)
+            .all(db)
+            .await?)
+    }
+
+    /// # Errors
+    ///
+    /// When the user has no such link or the database can't be reached.
+    pub async fn delete_by_user(
+        db: &DatabaseConnection,
+        user_id: i32,
+        code: &str,
+    ) -> ModelResult<()> {
+        let deleted = short_links::Entity::delete_many()
+            .filter(short_links::Column::UserId.eq(user_id))
+            .filter(short_links::Column::Code.eq(code))
+            .exec(db)
+            .await?;
+        if deleted.rows_affected == 0 {
+            return Err(ModelError::EntityNotFound);
+        }
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn is_expired(&self, now: DateTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
@@ -0,0 +1,224 @@
+use sea_orm::{entity::prelude::*, sea_query::OnConflict, ActiveValue, QueryOrder};
+use serde::{Deserialize, Serialize};
+
+use super::_entities::{
+    contents, libraries, player_connections,
+    subscriptions::{self, ActiveModel, Model},
+};
+use loco_rs::model::{ModelError, ModelResult};
+
+impl ActiveModelBehavior for ActiveModel {
+    // extend activemodel below (keep comment for generators)
+}
+
+/// How a subscription's new episodes get downloaded. Empty values leave the
+/// choice to the provider, or to the connection's preferred profile.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rules {
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Language of the audio stream to keep, like `eng`.
+    #[serde(default)]
+    pub audio_language: Option<String>,
+    /// Language of the subtitles to burn in, none when empty.
+    #[serde(default)]
+    pub subtitle_language: Option<String>,
+}
+
+impl Rules {
+    fn normalized(value: Option<&String>) -> Option<String> {
+        value
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+}
+
+impl Model {
+    /// Subscribes to a connection's show or season, or updates the rules of an
+    /// existing subscription to it. Episodes cached before the first
+    /// subscription aren't downloaded.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn create(
+        db: &DatabaseConnection,
+        connection_id: i32,
+        library_id: &str,
+        name: &str,
+        rules: &Rules,
+    ) -> ModelResult<Self> {
+        let subscription = ActiveModel {
+            player_connection_id: ActiveValue::Set(connection_id),
+            library_id: ActiveValue::Set(library_id.to_string()),
+            name: ActiveValue::Set(name.to_string()),
+            profile: ActiveValue::Set(Rules::normalized(rules.profile.as_ref())),
+            audio_language: ActiveValue::Set(Rules::normalized(rules.audio_language.as_ref())),
+            subtitle_language: ActiveValue::Set(Rules::normalized(
+                rules.subtitle_language.as_ref(),
+            )),
+            downloads: ActiveValue::Set(0),
+            created_at: ActiveValue::Set(chrono::Utc::now().naive_utc()),
+            updated_at: ActiveValue::Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        };
+        Ok(subscriptions::Entity::insert(subscription)
+            .on_conflict(
+                OnConflict::columns([
+                    subscriptions::Column::PlayerConnectionId,
+                    subscriptions::Column::LibraryId,
+                ])
+                .update_columns([
+                    subscriptions::Column::Name,
+                    subscriptions::Column::Profile,
+                    subscriptions::Column::AudioLanguage,
+                    subscriptions::Column::SubtitleLanguage,
+                    subscriptions::Column::UpdatedAt,
+                ])
+                .to_owned(),
+            )
+            .exec_with_returning(db)
+            .await?)
+    }
+
+    /// The user's subscriptions with their connections, by name.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn by_user(
+        db: &DatabaseConnection,
+        user_id: i32,
+    ) -> ModelResult<Vec<(Self, player_connections::Model)>> {
+        Ok(subscriptions::Entity::find()
+            .find_also_related(player_connections::Entity)
+            .filter(player_connections::Column::UserId.eq(user_id))
+            .order_by_asc(subscriptions::Column::Name)
+            .all(db)
+            .await?
+            .into_iter()
+            .filter_map(|(subscription, connection)| Some((subscription, connection?)))
+            .collect())
+    }
+
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn by_connection(
+        db: &DatabaseConnection,
+        connection_id: i32,
+    ) -> ModelResult<Vec<Self>> {
+        Ok(subscriptions::Entity::find()
+            .filter(subscriptions::Column::PlayerConnectionId.eq(connection_id))
+            .all(db)
+            .await?)
+    }
+
+    /// # Errors
+    ///
+    /// When the user has no such subscription or the database can't be reached.
+    pub async fn find_by_user_and_id(
+        db: &DatabaseConnection,
+        user_id: i32,
+        id: i32,
+    ) -> ModelResult<(Self, player_connections::Model)> {
+        subscriptions::Entity::find_by_id(id)
+            .find_also_related(player_connections::Entity)
+            .filter(player_connections::Column::UserId.eq(user_id))
+            .one(db)
+            .await?
+            .and_then(|(subscription, connection)| Some((subscription, connection?)))
+            .ok_or(ModelError::EntityNotFound)
+    }
+
+    /// # Errors
+    ///
+    /// When the user has no such subscription or the database can't be reached.
+    pub async fn update_rules(
+        db: &DatabaseConnection,
+        user_id: i32,
+        id: i32,
+        rules: &Rules,
+    ) -> ModelResult<(Self, player_connections::Model)> {
+        let (subscription, connection) = Self::find_by_user_and_id(db, user_id, id).await?;
+        let subscription = ActiveModel {
+            id: ActiveValue::Unchanged(subscription.id),
+            profile: ActiveValue::Set(Rules::normalized(rules.profile.as_ref())),
+            audio_language: ActiveValue::Set(Rules::normalized(rules.audio_language.as_ref())),
+            subtitle_language: ActiveValue::Set(Rules::normalized(
+                rules.subtitle_language.as_ref(),
+            )),
+            updated_at: ActiveValue::Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .update(db)
+        .await?;
+        Ok((subscription, connection))
+    }
+
+    /// # Errors
+    ///
+    /// When the user has no such subscription or the database can't be reached.
+    pub async fn delete_by_user(db: &DatabaseConnection, user_id: i32, id: i32) -> ModelResult<()> {
+        let (subscription, _) = Self::find_by_user_and_id(db, user_id, id).await?;
+        subscriptions::Entity::delete_by_id(subscription.id)
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Remembers a check for new episodes and how many downloads it started.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn record_check(db: &DatabaseConnection, id: i32, started: i64) -> ModelResult<()> {
+        subscriptions::Entity::update_many()
+            .col_expr(
+                subscriptions::Column::LastCheckedAt,
+                Expr::value(Some(chrono::Utc::now().naive_utc())),
+            )
+            .col_expr(
+                subscriptions::Column::Downloads,
+                Expr::col(subscriptions::Column::Downloads).add(started),
+            )
+            .filter(subscriptions::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn rules(&self) -> Rules {
+        Rules {
+            profile: self.profile.clone(),
+            audio_language: self.audio_language.clone(),
+            subtitle_language: self.subtitle_language.clone(),
+        }
+    }
+
+    /// Contents cached under the subscribed library, or its seasons, since it
+    /// was subscribed to and not downloaded yet, in library order.
+    ///
+    /// # Errors
+    ///
+    /// When the database can't be reached.
+    pub async fn new_contents(&self, db: &DatabaseConnection) -> ModelResult<Vec<contents::Model>> {
+        let mut parents = libraries::Model::ids_by_connection_and_parent_id(
+            db,
+            self.player_connection_id,
+            &self.library_id,
+        )
+        .await?;
+        parents.push(self.library_id.clone());
+        Ok(contents::Entity::find()
+            .filter(contents::Column::PlayerConnectionId.eq(self.player_connection_id))
+            .filter(contents::Column::ParentId.is_in(parents))
+            .filter(contents::Column::CreatedAt.gt(self.created_at))
+            .filter(contents::Column::RemovedAt.is_null())
+            .filter(contents::Column::Status.is_null())
+            .order_by_asc(contents::Column::SortKey)
+            .all(db)
+            .await?)
+    }
+}
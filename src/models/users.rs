@@ -4,6 +4,8 @@ use loco_rs::{auth::jwt, hash, prelude::*};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::common::pagination::SortOrder;
+
 pub use super::_entities::users::{self, ActiveModel, Entity, Model};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -173,6 +175,12 @@ impl super::_entities::users::Model {
         hash::verify_password(password, &self.password)
     }
 
+    /// How the user sorts library listings, the default when unset or unknown.
+    #[must_use]
+    pub fn library_sort(&self) -> SortOrder {
+        self.library_sort.parse().unwrap_or_default()
+    }
+
     /// Asynchronously creates a user with a password and saves it to the
     /// database.
     ///
@@ -261,6 +269,35 @@ impl super::_entities::users::ActiveModel {
         Ok(self.update(db).await?)
     }
 
+    /// Sets how many of the next episodes to download when the user starts
+    /// watching one, `0` for none.
+    ///
+    /// # Errors
+    ///
+    /// when has DB query error
+    pub async fn set_prefetch_episodes(
+        mut self,
+        db: &DatabaseConnection,
+        episodes: i32,
+    ) -> ModelResult<Model> {
+        self.prefetch_episodes = ActiveValue::set(episodes);
+        Ok(self.update(db).await?)
+    }
+
+    /// Sets how the user's library listings are sorted.
+    ///
+    /// # Errors
+    ///
+    /// when has DB query error
+    pub async fn set_library_sort(
+        mut self,
+        db: &DatabaseConnection,
+        order: SortOrder,
+    ) -> ModelResult<Model> {
+        self.library_sort = ActiveValue::set(order.as_str().to_string());
+        Ok(self.update(db).await?)
+    }
+
     /// Records the verification time when a user verifies their
     /// email and updates it in the database.
     ///
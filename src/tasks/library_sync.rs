@@ -0,0 +1,59 @@
+//! Syncs the cached libraries of every connection, or of one with
+//! `connection:<id>`, with their providers, outside of the schedule.
+//!
+//! # Example
+//!
+//! ```sh
+//! cargo run task library_sync connection:1
+//! ```
+
+use loco_rs::prelude::*;
+
+use crate::{
+    common::library_sync,
+    initializers::media_provider::MediaProviderInitializer,
+    models::{_entities::player_connections, player_connections::Model},
+};
+
+#[allow(clippy::module_name_repetitions)]
+pub struct LibrarySync;
+#[async_trait]
+impl Task for LibrarySync {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "library_sync".to_string(),
+            detail: "Task for syncing cached libraries with media providers".to_string(),
+        }
+    }
+
+    async fn run(&self, app_context: &AppContext, vars: &task::Vars) -> Result<()> {
+        // Tasks don't run initializers, the providers are needed to sync.
+        MediaProviderInitializer.before_run(app_context).await?;
+        let connections = match vars.cli_arg("connection") {
+            Ok(id) => {
+                let id: i32 = id
+                    .parse()
+                    .map_err(|e| Error::BadRequest(format!("Invalid connection id {id}: {e}")))?;
+                vec![player_connections::Entity::find_by_id(id)
+                    .one(&app_context.db)
+                    .await?
+                    .ok_or(Error::NotFound)?]
+            }
+            Err(_) => Model::by_media_provider(&app_context.db, None).await?,
+        };
+        for connection in &connections {
+            let status = library_sync::sync_connection(&app_context.db, connection).await?;
+            if let Some(error) = status.last_error {
+                tracing::error!(connection_id = connection.id, error, "Sync failed");
+            } else {
+                tracing::info!(
+                    connection_id = connection.id,
+                    updated = status.updated,
+                    removed = status.removed,
+                    "Synced"
+                );
+            }
+        }
+        Ok(())
+    }
+}
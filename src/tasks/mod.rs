@@ -1 +1,3 @@
+pub mod library_sync;
 pub mod seed;
+pub mod shared_gc;
@@ -0,0 +1,42 @@
+//! Drops shared transcodes that no download references anymore, along with
+//! their files.
+//!
+//! # Example
+//!
+//! ```sh
+//! cargo run task shared_gc
+//! ```
+
+use loco_rs::prelude::*;
+
+use crate::{common::settings::SETTINGS, models::_entities::shared_transcodes};
+
+#[allow(clippy::module_name_repetitions)]
+pub struct SharedGc;
+#[async_trait]
+impl Task for SharedGc {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "shared_gc".to_string(),
+            detail: "Task for removing unreferenced shared transcodes".to_string(),
+        }
+    }
+
+    async fn run(&self, app_context: &AppContext, _vars: &task::Vars) -> Result<()> {
+        let transcoding_dir = &SETTINGS
+            .get()
+            .ok_or_else(|| Error::Message("Settings not initialized".to_string()))?
+            .transcoding_dir;
+        let dropped = shared_transcodes::Model::collect_garbage(&app_context.db).await?;
+        for shared in &dropped {
+            let dir = transcoding_dir.join(shared.storage_path());
+            match tokio::fs::remove_dir_all(&dir).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => tracing::error!(error = ?e, ?dir, "Failed to remove shared transcode"),
+            }
+        }
+        tracing::info!(dropped = dropped.len(), "Collected shared transcodes");
+        Ok(())
+    }
+}
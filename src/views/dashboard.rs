@@ -2,20 +2,25 @@ use loco_rs::prelude::*;
 use serde::Serialize;
 use serde_json::json;
 
-use crate::models::_entities::player_connections;
+use crate::{
+    common::prefetch,
+    models::_entities::{clips, player_connections, users},
+};
 
 use super::Format;
 
 /// Home view
 pub fn home<V: ViewRenderer>(
     f: Format<V>,
+    user: &users::Model,
     connections: &[player_connections::Model],
+    clips: &[clips::Model],
 ) -> Result<Response> {
     f.render(
         None,
         "dashboard",
         "home",
-        &json!({"connections": &connections}),
+        &json!({"connections": &connections, "clips": clips, "prefetch_episodes": user.prefetch_episodes, "max_prefetch_episodes": prefetch::MAX_PREFETCH_EPISODES}),
     )
 }
 
@@ -0,0 +1,166 @@
+//! Responses of the [DeoVR](https://deovr.com) remote selection format.
+
+use std::collections::BTreeMap;
+
+use players::types::{Projection, Stereo};
+use serde::{Serialize, Serializer};
+
+use crate::{common::outputs::Output, models::contents::CatalogEntry};
+
+/// `authorized` of a response, the player asks for a login when it's `0`.
+#[derive(Debug, Clone, Copy)]
+pub struct Authorized(pub bool);
+
+impl Serialize for Authorized {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(if self.0 { "1" } else { "0" })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Selection {
+    pub authorized: Authorized,
+    pub scenes: Vec<Scene>,
+}
+
+impl Selection {
+    #[must_use]
+    pub fn unauthorized() -> Self {
+        Self {
+            authorized: Authorized(false),
+            scenes: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Scene {
+    pub name: String,
+    pub list: Vec<SceneItem>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SceneItem {
+    pub title: String,
+    /// Seconds.
+    pub video_length: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+    /// Where the player fetches the [`Video`] from.
+    #[serde(rename = "video_url")]
+    pub video_url: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Video {
+    pub authorized: Authorized,
+    pub title: String,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+    /// Seconds.
+    pub video_length: i64,
+    pub is_3d: bool,
+    pub screen_type: &'static str,
+    pub stereo_mode: &'static str,
+    pub encodings: Vec<Encoding>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Encoding {
+    pub name: String,
+    pub video_sources: Vec<VideoSource>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VideoSource {
+    pub url: String,
+}
+
+fn video_length(entry: &CatalogEntry) -> i64 {
+    super::duration_ms(entry).unwrap_or_default() / 1000
+}
+
+/// Scenes per library, prefixed with the provider when the user has several
+/// connections.
+#[must_use]
+pub fn selection(host: &str, entries: &[CatalogEntry]) -> Selection {
+    let several_connections = entries
+        .iter()
+        .any(|entry| entry.connection.id != entries[0].connection.id);
+    let mut scenes: BTreeMap<String, Vec<SceneItem>> = BTreeMap::new();
+    for entry in entries {
+        let library = entry.library.as_deref().unwrap_or("Other");
+        let name = if several_connections {
+            format!("{} · {library}", entry.connection.media_provider_id)
+        } else {
+            library.to_string()
+        };
+        scenes.entry(name).or_default().push(SceneItem {
+            title: entry.content.content.name.clone(),
+            video_length: video_length(entry),
+            thumbnail_url: super::thumbnail_url(host, entry),
+            video_url: format!(
+                "{host}/deovr/{}/{}",
+                entry.connection.id, entry.content.content.id
+            ),
+        });
+    }
+    Selection {
+        authorized: Authorized(true),
+        scenes: scenes
+            .into_iter()
+            .map(|(name, list)| Scene { name, list })
+            .collect(),
+    }
+}
+
+/// Details of a video, with an encoding per output of every downloaded variant.
+#[must_use]
+pub fn video(host: &str, entry: &CatalogEntry) -> Video {
+    let content = &entry.content.content;
+    let encodings = entry
+        .downloads
+        .iter()
+        .flat_map(|download| {
+            download.outputs.iter().map(move |output| {
+                let kind = match output {
+                    Output::Hls { .. } => "HLS",
+                    Output::Mp4 { .. } => "MP4",
+                    Output::File { container, .. } => container.as_str(),
+                };
+                Encoding {
+                    name: format!("{} ({kind})", download.label),
+                    video_sources: vec![VideoSource {
+                        url: format!("{host}/p/stream/{}", output.path()),
+                    }],
+                }
+            })
+        })
+        .collect();
+    let screen_type = match content.layout.projection {
+        Projection::Flat => "flat",
+        Projection::Dome => "dome",
+        Projection::Sphere => "sphere",
+        Projection::Fisheye => "fisheye",
+    };
+    let stereo_mode = match content.layout.stereo {
+        Stereo::Mono => "off",
+        Stereo::Sbs => "sbs",
+        Stereo::Tb => "tb",
+    };
+    Video {
+        authorized: Authorized(true),
+        title: content.name.clone(),
+        description: content.description.clone().unwrap_or_default(),
+        thumbnail_url: super::thumbnail_url(host, entry),
+        video_length: video_length(entry),
+        is_3d: content.layout.stereo != Stereo::Mono,
+        screen_type,
+        stereo_mode,
+        encodings,
+    }
+}
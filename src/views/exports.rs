@@ -0,0 +1,120 @@
+//! Playlists of finished downloads for world video players: `ProTV` playlist text,
+//! M3U with titles and plain JSON.
+//!
+//! Entries link `/p/stream/...` like the `DeoVR` and `HereSphere` catalogs, without
+//! a signed token. Stream urls are served to anyone holding them, so a signature
+//! on exported links wouldn't protect anything.
+
+use std::fmt::Write;
+
+use loco_rs::prelude::*;
+use players::types::ContentKind;
+use serde::{Deserialize, Serialize};
+
+use crate::{common::outputs::Output, models::contents::CatalogEntry};
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Protv,
+    M3u,
+    Json,
+}
+
+/// Query of the export endpoints.
+#[derive(Debug, Default, Deserialize)]
+pub struct ExportOptions {
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// Link MP4s instead of HLS playlists where possible.
+    #[serde(default)]
+    pub mp4: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportEntry {
+    pub title: String,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<i64>,
+}
+
+fn title(entry: &CatalogEntry) -> String {
+    let content = &entry.content.content;
+    match content.kind {
+        ContentKind::Episode { season, episode } => format!(
+            "S{:02}E{episode:02} {}",
+            season.unwrap_or_default(),
+            content.name
+        ),
+        _ => content.name.clone(),
+    }
+}
+
+/// An entry per content, pointing at the most recent download's HLS playlist, or
+/// its MP4 when `prefer_mp4` and there is one. Contents without either are left out.
+#[must_use]
+pub fn entries(host: &str, catalog: &[CatalogEntry], prefer_mp4: bool) -> Vec<ExportEntry> {
+    catalog
+        .iter()
+        .filter_map(|entry| {
+            let download = entry.downloads.first()?;
+            let hls = download
+                .outputs
+                .iter()
+                .find(|output| matches!(output, Output::Hls { .. }));
+            let mp4 = download
+                .outputs
+                .iter()
+                .find(|output| matches!(output, Output::Mp4 { .. }));
+            let output = if prefer_mp4 { mp4.or(hls) } else { hls.or(mp4) }?;
+            Some(ExportEntry {
+                title: title(entry),
+                url: format!("{host}/p/stream/{}", output.path()),
+                duration_secs: super::duration_ms(entry).map(|ms| ms / 1000),
+            })
+        })
+        .collect()
+}
+
+/// `ProTV` playlist text, an `@url` line followed by the title for every entry.
+#[must_use]
+pub fn protv(entries: &[ExportEntry]) -> String {
+    let mut out = String::new();
+    for entry in entries {
+        let _ = writeln!(out, "@{}\n{}\n", entry.url, entry.title);
+    }
+    out
+}
+
+/// Extended M3U, players without `#EXTINF` support just see the urls.
+#[must_use]
+pub fn m3u(entries: &[ExportEntry]) -> String {
+    let mut out = "#EXTM3U\n".to_string();
+    for entry in entries {
+        let _ = writeln!(
+            out,
+            "#EXTINF:{},{}\n{}",
+            entry.duration_secs.unwrap_or(-1),
+            entry.title,
+            entry.url
+        );
+    }
+    out
+}
+
+/// Responds with the entries in the requested format.
+///
+/// # Errors
+///
+/// When the entries can't be serialized.
+pub fn render(format: ExportFormat, entries: &[ExportEntry]) -> Result<Response> {
+    match format {
+        ExportFormat::Protv => format::text(&protv(entries)),
+        ExportFormat::M3u => format::render()
+            .header("content-type", "audio/x-mpegurl")
+            .text(&m3u(entries)),
+        ExportFormat::Json => format::json(entries),
+    }
+}
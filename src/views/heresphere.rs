@@ -0,0 +1,224 @@
+//! Responses of the [HereSphere](https://heresphere.com) JSON API.
+
+use std::collections::BTreeMap;
+
+use players::types::{ContentKind, Projection, Stereo, UserData, VrLayout};
+use serde::Serialize;
+
+use crate::{common::outputs::Output, models::contents::CatalogEntry};
+
+/// Sent with every response, the player ignores the body without it.
+pub const VERSION_HEADER: &str = "HereSphere-JSON-Version";
+pub const VERSION: &str = "1";
+
+/// `access` of a response.
+#[derive(Debug, Clone, Copy)]
+pub enum Access {
+    Unauthorized,
+    Member,
+}
+
+impl Serialize for Access {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i8(match self {
+            Self::Unauthorized => -1,
+            Self::Member => 1,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Unauthorized {
+    pub access: Access,
+}
+
+impl Default for Unauthorized {
+    fn default() -> Self {
+        Self {
+            access: Access::Unauthorized,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Index {
+    pub access: Access,
+    pub library: Vec<Section>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Section {
+    pub name: String,
+    /// Urls of the section's videos.
+    pub list: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Video {
+    pub access: Access,
+    pub title: String,
+    pub description: String,
+    pub thumbnail_image: String,
+    /// Milliseconds.
+    pub duration: i64,
+    pub is_favorite: bool,
+    pub projection: &'static str,
+    pub stereo: &'static str,
+    pub event_server: String,
+    pub tags: Vec<Tag>,
+    pub media: Vec<Media>,
+    pub write_favorite: bool,
+    pub write_rating: bool,
+    pub write_tags: bool,
+    pub write_hsp: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Tag {
+    /// `Category:Value`
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Media {
+    pub name: String,
+    pub sources: Vec<Source>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Source {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+/// `projection` and `stereo` of a video.
+fn layout(layout: VrLayout) -> (&'static str, &'static str) {
+    let projection = match layout.projection {
+        Projection::Flat => "perspective",
+        Projection::Dome => "equirectangular",
+        Projection::Sphere => "equirectangular360",
+        Projection::Fisheye => "fisheye",
+    };
+    let stereo = match layout.stereo {
+        Stereo::Mono => "mono",
+        Stereo::Sbs => "sbs",
+        Stereo::Tb => "tb",
+    };
+    (projection, stereo)
+}
+
+/// Url the player fetches the details of a video from.
+#[must_use]
+pub fn video_url(host: &str, entry: &CatalogEntry) -> String {
+    format!(
+        "{host}/heresphere/{}/{}",
+        entry.connection.id, entry.content.content.id
+    )
+}
+
+/// Groups the entries by library, keeping their order within each.
+#[must_use]
+pub fn index(host: &str, entries: &[CatalogEntry]) -> Index {
+    let mut sections: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for entry in entries {
+        let name = entry.library.clone().unwrap_or_else(|| "Other".to_string());
+        sections
+            .entry(name)
+            .or_default()
+            .push(video_url(host, entry));
+    }
+    Index {
+        access: Access::Member,
+        library: sections
+            .into_iter()
+            .map(|(name, list)| Section { name, list })
+            .collect(),
+    }
+}
+
+/// Details of a video, with a media entry per downloaded variant.
+#[must_use]
+pub fn video(host: &str, entry: &CatalogEntry, user_data: &UserData) -> Video {
+    let content = &entry.content.content;
+    let mut tags = vec![];
+    if let Some(library) = &entry.library {
+        tags.push(Tag {
+            name: format!("Library:{library}"),
+        });
+    }
+    if let ContentKind::Episode { season, episode } = content.kind {
+        tags.push(Tag {
+            name: format!("Episode:S{}E{episode}", season.unwrap_or_default()),
+        });
+    }
+    if let Some(year) = content.metadata.production_year {
+        tags.push(Tag {
+            name: format!("Year:{year}"),
+        });
+    }
+    for genre in &content.metadata.genres {
+        tags.push(Tag {
+            name: format!("Genre:{genre}"),
+        });
+    }
+    for person in &content.metadata.people {
+        let category = match person.kind.as_deref() {
+            Some("Actor" | "GuestStar") => "Cast",
+            Some("Director") => "Director",
+            _ => continue,
+        };
+        tags.push(Tag {
+            name: format!("{category}:{}", person.name),
+        });
+    }
+    if entry.content.played || user_data.played {
+        tags.push(Tag {
+            name: "Status:Watched".to_string(),
+        });
+    }
+
+    let media = entry
+        .downloads
+        .iter()
+        .flat_map(|download| {
+            download.outputs.iter().map(move |output| {
+                let url = format!("{host}/p/stream/{}", output.path());
+                let (kind, size) = match output {
+                    Output::Hls { .. } => ("HLS", None),
+                    Output::Mp4 { size, .. } => ("MP4", Some(*size)),
+                    Output::File {
+                        container, size, ..
+                    } => (container.as_str(), Some(*size)),
+                };
+                Media {
+                    name: format!("{} ({kind})", download.label),
+                    sources: vec![Source { url, size }],
+                }
+            })
+        })
+        .collect();
+
+    let (projection, stereo) = layout(content.layout);
+    Video {
+        access: Access::Member,
+        title: content.name.clone(),
+        description: content.description.clone().unwrap_or_default(),
+        thumbnail_image: super::thumbnail_url(host, entry).unwrap_or_default(),
+        duration: super::duration_ms(entry).unwrap_or_default(),
+        is_favorite: user_data.favorite,
+        projection,
+        stereo,
+        event_server: format!(
+            "{host}/heresphere/event?token={}",
+            entry.connection.stream_token
+        ),
+        tags,
+        media,
+        write_favorite: true,
+        write_rating: false,
+        write_tags: false,
+        write_hsp: false,
+    }
+}
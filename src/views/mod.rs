@@ -2,11 +2,41 @@ use axum::response::Response;
 use loco_rs::{controller::format::RenderBuilder, prelude::ViewRenderer, Result};
 use serde::Serialize;
 
+use crate::{common::images, models::contents::CatalogEntry};
+
 pub mod auth;
 pub mod dashboard;
+pub mod deovr;
+pub mod exports;
+pub mod heresphere;
 pub mod user;
 
 pub mod player_connections;
+pub mod short_links;
+pub mod subscriptions;
+
+/// Proxied image of a catalog entry, see [`crate::common::images`].
+#[must_use]
+pub fn thumbnail_url(host: &str, entry: &CatalogEntry) -> Option<String> {
+    entry.content.content.icon_url.as_ref().map(|_| {
+        format!(
+            "{host}/images/{}/{}?width={}&token={}",
+            entry.connection.id,
+            entry.content.content.id,
+            images::CATALOG_WIDTH,
+            entry.connection.stream_token
+        )
+    })
+}
+
+/// Length of a catalog entry as seen in its playlist, or as the provider says.
+#[must_use]
+pub fn duration_ms(entry: &CatalogEntry) -> Option<i64> {
+    entry.duration_ms.or_else(|| {
+        let runtime = entry.content.content.metadata.runtime_ms?;
+        i64::try_from(runtime).ok()
+    })
+}
 
 pub enum Format<V: ViewRenderer> {
     Json,
@@ -62,3 +92,71 @@ pub struct HtmxPartial<'a, T: Serialize> {
     #[serde(flatten)]
     pub ctx: &'a T,
 }
+
+#[cfg(test)]
+mod tests {
+    use players::types::{Content, ContentKind, Metadata, VrLayout};
+
+    use super::*;
+    use crate::models::{_entities::player_connections, contents::ContentWithModel};
+
+    fn entry(duration_ms: Option<i64>, runtime_ms: Option<u64>) -> CatalogEntry {
+        let now = chrono::Utc::now().naive_utc();
+        CatalogEntry {
+            connection: player_connections::Model {
+                created_at: now,
+                updated_at: now,
+                id: 1,
+                media_provider_id: "jellyfin".to_string(),
+                user_id: 1,
+                identity: None,
+                status: None,
+                preferences: None,
+                preferred_profile: None,
+                root_libraries: None,
+                stream_token: uuid::Uuid::nil(),
+                syncing_since: None,
+            },
+            content: ContentWithModel {
+                content: Content {
+                    id: "movie".to_string(),
+                    parent_id: None,
+                    name: "Moonlit".to_string(),
+                    description: None,
+                    icon_url: None,
+                    series_name: None,
+                    season_name: None,
+                    media_streams: vec![],
+                    kind: ContentKind::Movie,
+                    media_sources: vec![],
+                    layout: VrLayout::default(),
+                    chapters: vec![],
+                    airs: None,
+                    metadata: Metadata {
+                        runtime_ms,
+                        ..Metadata::default()
+                    },
+                },
+                status: None,
+                variants: vec![],
+                clips: vec![],
+                resume_from: None,
+                progress: None,
+                played: false,
+                layout_overridden: false,
+                layout_label: String::new(),
+            },
+            library: None,
+            duration_ms,
+            downloads: vec![],
+        }
+    }
+
+    #[test]
+    fn prefers_playlist_durations_over_runtimes() {
+        assert_eq!(duration_ms(&entry(Some(1_000), Some(2_000))), Some(1_000));
+        assert_eq!(duration_ms(&entry(None, Some(2_000))), Some(2_000));
+        assert_eq!(duration_ms(&entry(None, Some(u64::MAX))), None);
+        assert_eq!(duration_ms(&entry(None, None)), None);
+    }
+}
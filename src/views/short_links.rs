@@ -0,0 +1,49 @@
+use loco_rs::prelude::*;
+use serde::Serialize;
+
+use crate::models::_entities::short_links;
+
+#[derive(Serialize)]
+struct LinkView<'a> {
+    #[serde(flatten)]
+    link: &'a short_links::Model,
+    url: String,
+    expired: bool,
+}
+
+impl<'a> LinkView<'a> {
+    fn new(host: &str, link: &'a short_links::Model) -> Self {
+        Self {
+            link,
+            url: format!("{host}/s/{}", link.code),
+            expired: link.is_expired(chrono::Utc::now().naive_utc()),
+        }
+    }
+}
+
+/// Render a single short link, with its QR code and counters.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn link(v: &impl ViewRenderer, host: &str, link: &short_links::Model) -> Result<Response> {
+    format::render().view(
+        v,
+        "short_links/link.html",
+        serde_json::json!({"item": LinkView::new(host, link)}),
+    )
+}
+
+/// Render the user's short links.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn list(v: &impl ViewRenderer, host: &str, links: &[short_links::Model]) -> Result<Response> {
+    let items: Vec<LinkView<'_>> = links.iter().map(|link| LinkView::new(host, link)).collect();
+    format::render().view(
+        v,
+        "short_links/index.html",
+        serde_json::json!({"items": items}),
+    )
+}
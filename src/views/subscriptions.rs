@@ -0,0 +1,71 @@
+use loco_rs::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    initializers::media_provider::ConnectedMediaProvider,
+    models::_entities::{player_connections, subscriptions},
+};
+
+#[derive(Serialize)]
+struct SubscriptionView<'a> {
+    #[serde(flatten)]
+    subscription: &'a subscriptions::Model,
+    /// Profiles of the connection's provider to pick from.
+    profiles: Vec<String>,
+}
+
+impl<'a> SubscriptionView<'a> {
+    fn new(subscription: &'a subscriptions::Model, connection: &player_connections::Model) -> Self {
+        let profiles = ConnectedMediaProvider::try_from(connection.clone())
+            .map(|provider| {
+                provider
+                    .provider
+                    .profiles
+                    .into_iter()
+                    .map(|profile| profile.name)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            subscription,
+            profiles,
+        }
+    }
+}
+
+/// Render a single subscription with its download rules.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn item(
+    v: &impl ViewRenderer,
+    subscription: &subscriptions::Model,
+    connection: &player_connections::Model,
+) -> Result<Response> {
+    format::render().view(
+        v,
+        "subscriptions/subscription.html",
+        serde_json::json!({"item": SubscriptionView::new(subscription, connection)}),
+    )
+}
+
+/// Render the user's subscriptions, for the dashboard.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn list(
+    v: &impl ViewRenderer,
+    subscriptions: &[(subscriptions::Model, player_connections::Model)],
+) -> Result<Response> {
+    let items: Vec<SubscriptionView<'_>> = subscriptions
+        .iter()
+        .map(|(subscription, connection)| SubscriptionView::new(subscription, connection))
+        .collect();
+    format::render().view(
+        v,
+        "subscriptions/list.html",
+        serde_json::json!({"items": items}),
+    )
+}
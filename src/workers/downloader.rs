@@ -3,8 +3,9 @@ use std::{path::PathBuf, str::FromStr};
 use axum::{body::Bytes, http::Uri};
 use futures_util::StreamExt;
 use loco_rs::prelude::*;
-use players::types::{Content, MediaStream, TranscodeJob};
+use players::types::{Content, DirectFile, M3U8Playlist, MediaStream, TranscodeJob};
 use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
 
 use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
 use reqwest_retry::{
@@ -14,12 +15,16 @@ use reqwest_retry::{
 use uuid::Uuid;
 
 use crate::{
-    common::notifications,
+    common::{notifications, outputs::Output, remux, settings::SETTINGS, skips},
     initializers::media_provider::ConnectedMediaProvider,
-    models::_entities::{
-        content_downloads,
-        player_connections::Model,
-        sea_orm_active_enums::StatusName::{Error as ErrorStatus, InProgress, Success},
+    models::{
+        _entities::{
+            content_downloads,
+            player_connections::Model,
+            sea_orm_active_enums::StatusName::{self, Error as ErrorStatus, InProgress, Success},
+            shared_transcodes,
+        },
+        shared_transcodes::storage_path as shared_storage_path,
     },
 };
 
@@ -42,6 +47,32 @@ pub struct DownloadWorkerArgs {
     pub profile: Option<String>,
     pub content: Content,
     pub preferred_mediastreams: Vec<MediaStream>,
+    /// Which version of the content to download, `None` picks the provider's default.
+    #[serde(default)]
+    pub media_source_id: Option<String>,
+    /// See [`crate::common::variants::Variant::id`], jobs queued before variants
+    /// existed don't have one and keep using the content dir.
+    #[serde(default)]
+    pub variant_id: Option<String>,
+    /// Key of the shared transcode the download fills, see
+    /// [`crate::common::variants::shared_key`].
+    #[serde(default)]
+    pub shared_key: Option<String>,
+}
+
+impl DownloadWorkerArgs {
+    /// Where the download is stored, relative to the transcoding dir.
+    #[must_use]
+    pub fn storage_path(&self) -> String {
+        if let Some(key) = &self.shared_key {
+            return shared_storage_path(key);
+        }
+        let base = format!("single/{}/{}", self.connection_id, self.content.id);
+        match &self.variant_id {
+            Some(variant_id) => format!("{base}/{variant_id}"),
+            None => base,
+        }
+    }
 }
 
 impl worker::AppWorker<DownloadWorkerArgs> for DownloadWorker {
@@ -52,177 +83,524 @@ impl worker::AppWorker<DownloadWorkerArgs> for DownloadWorker {
 
 const CONCURRENT_DOWNLOADS: usize = 4;
 const RETRY_DOWNLOADS: u32 = 15;
+const PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
 
 #[async_trait]
 impl worker::Worker<DownloadWorkerArgs> for DownloadWorker {
     #[tracing::instrument(skip_all, fields(user_id = args.user_id, connection_id = args.connection_id, content_download_id = ?args.content_download_id))]
     async fn perform(&self, args: DownloadWorkerArgs) -> worker::Result<()> {
+        let (provider, transcode) = match self.start(&args).await {
+            Ok(started) => started,
+            Err(e) => {
+                tracing::error!(error = ?e, "Failed to start transcode");
+                self.fail(&args, e.to_string()).await;
+                return Err(sidekiq::Error::Message(e.to_string()));
+            }
+        };
+
+        match transcode {
+            TranscodeJob::Direct(file) => self.download_direct(&args, &file).await,
+            TranscodeJob::M3U8(playlist) => self.download_hls(&args, &provider, playlist).await,
+        }
+        Ok(())
+    }
+}
+
+impl DownloadWorker {
+    /// Asks the provider for the transcode of the download's content.
+    async fn start(
+        &self,
+        args: &DownloadWorkerArgs,
+    ) -> eyre::Result<(ConnectedMediaProvider, TranscodeJob)> {
         let connection = Model::find_by_user_and_id(&self.ctx.db, args.user_id, args.connection_id)
             .await
-            .map_err(|_| sidekiq::Error::Message("Could not find player connection".to_string()))?;
-
+            .map_err(|_| eyre::eyre!("Could not find player connection"))?;
         let provider: ConnectedMediaProvider = connection
-            .clone()
             .try_into()
-            .map_err(|_| sidekiq::Error::Message("Could not create provider".to_string()))?;
+            .map_err(|_| eyre::eyre!("Could not create provider"))?;
         let transcode = provider
             .transcode(
                 &self.ctx,
                 &args.content,
                 args.profile.as_deref(),
                 &args.preferred_mediastreams,
+                args.media_source_id.as_deref(),
             )
-            .await
-            .map_err(|e| sidekiq::Error::Message(e.to_string()))?;
+            .await?;
+        Ok((provider, transcode))
+    }
 
-        let content_id = &args.content.id.clone();
+    /// Marks the download as failed, along with the downloads waiting on its
+    /// shared transcode, so none of them is left in progress.
+    async fn fail(&self, args: &DownloadWorkerArgs, error: String) {
+        let status = notifications::DownloaderStatus::Failed { error };
+        if let Err(e) = content_downloads::Model::notify_status(
+            &self.ctx.db,
+            args.content_download_id,
+            &args.content.id,
+            ErrorStatus,
+            &status,
+        )
+        .await
+        {
+            tracing::error!(error = ?e, "Failed to notify status");
+        }
+        self.complete_shared(args, ErrorStatus, &status).await;
+    }
 
-        match transcode {
-            TranscodeJob::M3U8(mut playlist) => {
+    /// Stores the playlists and fetches their segments into the variant's dir.
+    /// The download only succeeds when every segment made it, so partial ones
+    /// are never handed to the users sharing the transcode.
+    async fn download_hls(
+        &self,
+        args: &DownloadWorkerArgs,
+        provider: &ConnectedMediaProvider,
+        mut playlist: M3U8Playlist,
+    ) {
+        let content_id = &args.content.id;
+        let storage_path = args.storage_path();
+        let base_path = std::path::Path::new(&storage_path);
+
+        let mut paths = Vec::new();
+        let stored = async {
+            let mut v: Vec<u8> = Vec::new();
+            playlist.main.write_to(&mut v)?;
+            self.ctx
+                .storage
+                .upload(&base_path.join("main.m3u8"), &Bytes::from(v))
+                .await?;
+            for (name, media) in &mut playlist.media {
+                for segment in &mut media.segments {
+                    let uri = segment.uri.clone();
+                    segment.uri = local_uri(name, &uri)?;
+                    paths.push((uri, segment.uri.clone()));
+                    // fMP4 playlists carry an init segment next to the first media segment.
+                    if let Some(map) = segment.map.as_mut() {
+                        let uri = map.uri.clone();
+                        map.uri = local_uri(name, &uri)?;
+                        paths.push((uri, map.uri.clone()));
+                    }
+                }
                 let mut v: Vec<u8> = Vec::new();
-                playlist.main.write_to(&mut v).unwrap();
-                let base_path = std::path::Path::new(&format!("single/{}", &args.connection_id))
-                    .join(&args.content.id);
+                media.write_to(&mut v)?;
                 self.ctx
                     .storage
-                    .upload(&base_path.join("main.m3u8"), &Bytes::from(v))
-                    .await
-                    .unwrap();
-
-                let mut paths = Vec::new();
-                for (name, media) in &mut playlist.media {
-                    media.segments.iter_mut().for_each(|segment| {
-                        let uri = segment.uri.clone();
-                        segment.uri = format!(
-                            "{}/{}",
-                            name,
-                            Uri::from_str(&segment.uri)
-                                .unwrap()
-                                .path()
-                                .split('/')
-                                .last()
-                                .unwrap()
-                                .to_owned()
-                        );
-                        paths.push((uri, segment.uri.clone()));
-                    });
-                    let mut v: Vec<u8> = Vec::new();
-                    media.write_to(&mut v).unwrap();
-                    self.ctx
-                        .storage
-                        .upload(&base_path.join(format!("{name}.m3u8")), &Bytes::from(v))
-                        .await
-                        .unwrap();
-                }
+                    .upload(&base_path.join(format!("{name}.m3u8")), &Bytes::from(v))
+                    .await?;
+            }
+            Ok::<_, eyre::Error>(())
+        }
+        .await;
+        if let Err(e) = stored {
+            tracing::error!(error = ?e, "Failed to store playlists");
+            self.fail(args, e.to_string()).await;
+            return;
+        }
+        self.store_skip_ranges(provider, content_id, base_path)
+            .await;
 
-                let start_time = std::time::Instant::now();
-                let total = paths.len();
-                let mut eta = eta::Eta::new(total, eta::TimeAcc::SEC);
-                let (tx, mut rx) = tokio::sync::mpsc::channel(CONCURRENT_DOWNLOADS * 4);
-                let ctx: AppContext = self.ctx.clone();
-                tokio::spawn(async move {
-                    let fetches = futures_util::stream::iter(paths.into_iter().enumerate().map(
-                        move |(idx, (uri, filename))| {
-                            let base_path =
-                                std::path::Path::new(&format!("single/{}", &args.connection_id))
-                                    .join(&args.content.id);
-                            let tx = tx.clone();
-                            let ctx: AppContext = ctx.clone();
-                            async move {
-                                match Self::download_file(ctx, uri, filename, base_path).await {
-                                    Ok(_) => {
-                                        if let Err(_) = tx.send(Ok(idx)).await {
-                                            tracing::error!("receiver dropped");
-                                            return;
-                                        }
-                                    }
-                                    Err(e) => {
-                                        if let Err(_) = tx.send(Err((idx, e))).await {
-                                            tracing::error!("receiver dropped");
-                                            return;
-                                        }
-                                    }
+        let start_time = std::time::Instant::now();
+        let total = paths.len();
+        let mut eta = eta::Eta::new(total, eta::TimeAcc::SEC);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(CONCURRENT_DOWNLOADS * 4);
+        let ctx: AppContext = self.ctx.clone();
+        let download_path = storage_path.clone();
+        tokio::spawn(async move {
+            let fetches = futures_util::stream::iter(paths.into_iter().enumerate().map(
+                move |(idx, (uri, filename))| {
+                    let base_path = PathBuf::from(&download_path);
+                    let tx = tx.clone();
+                    let ctx: AppContext = ctx.clone();
+                    async move {
+                        match Self::download_file(ctx, uri, filename, base_path).await {
+                            Ok(_) => {
+                                if let Err(_) = tx.send(Ok(idx)).await {
+                                    tracing::error!("receiver dropped");
+                                    return;
+                                }
+                            }
+                            Err(e) => {
+                                if let Err(_) = tx.send(Err((idx, e))).await {
+                                    tracing::error!("receiver dropped");
+                                    return;
                                 }
                             }
-                        },
-                    ))
-                    .buffer_unordered(CONCURRENT_DOWNLOADS)
-                    .collect::<Vec<()>>();
-                    fetches.await;
-                });
-
-                let mut seen_idx = vec![];
-                while let Some(data) = rx.recv().await {
-                    let res = match data {
-                        Ok(i) => {
-                            seen_idx.push(i);
-                            eta.step();
-                            tracing::debug!(
-                                done = seen_idx.len(),
-                                total,
-                                idx = i,
-                                "Downloaded segment"
-                            );
-                            // if i % (CONCURRENT_DOWNLOADS * 4) == 1 {
-                            let var_name = notifications::DownloaderStatus::SegmentProgressReport {
-                                done: seen_idx.len(),
-                                total,
-                                eta: eta.to_string(),
-                                eta_seconds: eta.time_remaining(),
-                            };
-                            content_downloads::Model::notify_status(
-                                &self.ctx.db,
-                                args.content_download_id,
-                                content_id,
-                                InProgress,
-                                &var_name,
-                            )
-                            .await
-                            // } else {
-                            //     continue;
-                            // }
-                        }
-                        Err((i, e)) => {
-                            tracing::error!(error = ?e, idx = i, "Failed to download segment");
-                            let var_name = notifications::DownloaderStatus::SegmentFailed {
-                                segment_id: i,
-                                error: e.to_string(),
-                            };
-                            content_downloads::Model::notify_status(
-                                &self.ctx.db,
-                                args.content_download_id,
-                                content_id,
-                                ErrorStatus,
-                                &var_name,
-                            )
-                            .await
                         }
+                    }
+                },
+            ))
+            .buffer_unordered(CONCURRENT_DOWNLOADS)
+            .collect::<Vec<()>>();
+            fetches.await;
+        });
+
+        let mut seen_idx = vec![];
+        let mut failed = 0;
+        while let Some(data) = rx.recv().await {
+            let res = match data {
+                Ok(i) => {
+                    seen_idx.push(i);
+                    eta.step();
+                    tracing::debug!(done = seen_idx.len(), total, idx = i, "Downloaded segment");
+                    let var_name = notifications::DownloaderStatus::SegmentProgressReport {
+                        done: seen_idx.len(),
+                        total,
+                        eta: eta.to_string(),
+                        eta_seconds: eta.time_remaining(),
+                    };
+                    content_downloads::Model::notify_status(
+                        &self.ctx.db,
+                        args.content_download_id,
+                        content_id,
+                        InProgress,
+                        &var_name,
+                    )
+                    .await
+                }
+                Err((i, e)) => {
+                    failed += 1;
+                    tracing::error!(error = ?e, idx = i, "Failed to download segment");
+                    let var_name = notifications::DownloaderStatus::SegmentFailed {
+                        segment_id: i,
+                        error: e.to_string(),
                     };
-                    if let Err(e) = res {
-                        tracing::error!(error = ?e, "Failed to notify status");
+                    content_downloads::Model::notify_status(
+                        &self.ctx.db,
+                        args.content_download_id,
+                        content_id,
+                        ErrorStatus,
+                        &var_name,
+                    )
+                    .await
+                }
+            };
+            if let Err(e) = res {
+                tracing::error!(error = ?e, "Failed to notify status");
+            }
+        }
+        if failed > 0 || seen_idx.len() < total {
+            tracing::warn!(
+                failed,
+                total,
+                "Some segments failed, not finishing the download"
+            );
+            self.fail(args, format!("{failed} of {total} segments failed"))
+                .await;
+            return;
+        }
+
+        let elapsed = start_time.elapsed();
+        tracing::info!(?elapsed, "Downloaded all segments");
+        let var_name = notifications::DownloaderStatus::Finished { elapsed };
+        if let Err(e) = content_downloads::Model::notify_status(
+            &self.ctx.db,
+            args.content_download_id,
+            content_id,
+            Success,
+            &var_name,
+        )
+        .await
+        {
+            tracing::error!(error = ?e, "Failed to notify status");
+        }
+
+        let hls = Output::Hls {
+            path: format!("{storage_path}/main.m3u8"),
+        };
+        if let Err(e) =
+            content_downloads::Model::add_output(&self.ctx.db, args.content_download_id, hls).await
+        {
+            tracing::error!(error = ?e, "Failed to record HLS output");
+        }
+        self.export_mp4(&storage_path, args.content_download_id, content_id)
+            .await;
+        self.complete_shared(args, Success, &var_name).await;
+    }
+
+    /// Downloads the original file to `original.<container>` in the variant's dir
+    /// and records it as an output of the download.
+    async fn download_direct(&self, args: &DownloadWorkerArgs, file: &DirectFile) {
+        let start_time = std::time::Instant::now();
+        let content_id = &args.content.id;
+        let relative = args.storage_path();
+        let name = format!("original.{}", file.container);
+        let dir = SETTINGS.get().unwrap().transcoding_dir.join(&relative);
+        let partial = dir.join(format!("{name}.part"));
+
+        let result = async {
+            tokio::fs::create_dir_all(&dir).await?;
+            let size = self.fetch_resumable(args, file, &partial).await?;
+            tokio::fs::rename(&partial, dir.join(&name)).await?;
+            Ok::<_, eyre::Error>(size)
+        }
+        .await;
+
+        let size = match result {
+            Ok(size) => size,
+            Err(e) => {
+                tracing::error!(error = ?e, "Failed to download original file");
+                self.fail(args, e.to_string()).await;
+                return;
+            }
+        };
+        let elapsed = start_time.elapsed();
+        tracing::info!(?elapsed, size, "Downloaded original file");
+        let output = Output::File {
+            path: format!("{relative}/{name}"),
+            container: file.container.clone(),
+            size,
+        };
+        if let Err(e) =
+            content_downloads::Model::add_output(&self.ctx.db, args.content_download_id, output)
+                .await
+        {
+            tracing::error!(error = ?e, "Failed to record direct output");
+        }
+        let status_info = notifications::DownloaderStatus::Finished { elapsed };
+        if let Err(e) = content_downloads::Model::notify_status(
+            &self.ctx.db,
+            args.content_download_id,
+            content_id,
+            Success,
+            &status_info,
+        )
+        .await
+        {
+            tracing::error!(error = ?e, "Failed to notify status");
+        }
+        self.complete_shared(args, Success, &status_info).await;
+    }
+
+    /// Streams `file` into `partial`, picking up where an earlier attempt (or an earlier
+    /// run of the job) stopped with a range request. Returns the final size.
+    async fn fetch_resumable(
+        &self,
+        args: &DownloadWorkerArgs,
+        file: &DirectFile,
+        partial: &std::path::Path,
+    ) -> eyre::Result<u64> {
+        let client = reqwest::Client::new();
+        let started = std::time::Instant::now();
+        let mut last_report = started;
+        let mut attempts = 0;
+        loop {
+            let mut done = tokio::fs::metadata(partial).await.map_or(0, |m| m.len());
+            if file.size.is_some_and(|size| size > 0 && done >= size) {
+                return Ok(done);
+            }
+            let resumed_at = done;
+            let attempt = async {
+                let mut request = client.get(&file.url);
+                if done > 0 {
+                    request = request.header(reqwest::header::RANGE, format!("bytes={done}-"));
+                }
+                let mut response = request.send().await?.error_for_status()?;
+                let mut options = tokio::fs::OpenOptions::new();
+                options.create(true);
+                if response.status() == reqwest::StatusCode::PARTIAL_CONTENT {
+                    options.append(true);
+                } else {
+                    // The server ignored the range, start over.
+                    options.write(true).truncate(true);
+                    done = 0;
+                }
+                let total = file
+                    .size
+                    .or_else(|| response.content_length().map(|len| len + done));
+                let mut out = options.open(partial).await?;
+                while let Some(chunk) = response.chunk().await? {
+                    out.write_all(&chunk).await?;
+                    done += chunk.len() as u64;
+                    if last_report.elapsed() >= PROGRESS_INTERVAL {
+                        last_report = std::time::Instant::now();
+                        self.report_file_progress(args, done, resumed_at, total, started)
+                            .await;
                     }
                 }
-                let elapsed = start_time.elapsed();
-                tracing::info!(?elapsed, "Downloaded all segments");
-                let var_name = notifications::DownloaderStatus::Finished { elapsed };
-                if let Err(e) = content_downloads::Model::notify_status(
-                    &self.ctx.db,
-                    args.content_download_id,
-                    content_id,
-                    Success,
-                    &var_name,
-                )
+                out.flush().await?;
+                match total {
+                    Some(total) if done < total => {
+                        Err(eyre::eyre!("Connection closed at {done} of {total} bytes"))
+                    }
+                    _ => Ok(done),
+                }
+            }
+            .await;
+            match attempt {
+                Ok(size) => return Ok(size),
+                Err(e) if attempts < RETRY_DOWNLOADS => {
+                    attempts += 1;
+                    tracing::warn!(error = ?e, attempts, "Direct download interrupted, resuming");
+                    tokio::time::sleep(std::time::Duration::from_secs(u64::from(attempts.min(30))))
+                        .await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Keeps the content's intro/credits ranges with the download, so playlists can
+    /// leave them out later. Not having them only means nothing can be skipped.
+    async fn store_skip_ranges(
+        &self,
+        provider: &ConnectedMediaProvider,
+        content_id: &str,
+        base_path: &std::path::Path,
+    ) {
+        let ranges = match provider.skip_ranges(content_id).await {
+            Ok(ranges) if ranges.is_empty() => return,
+            Ok(ranges) => ranges,
+            Err(e) => {
+                tracing::warn!(error = ?e, "Failed to fetch skip ranges");
+                return;
+            }
+        };
+        let result = match serde_json::to_vec(&ranges) {
+            Ok(data) => self
+                .ctx
+                .storage
+                .upload(&base_path.join(skips::SKIP_RANGES_FILE), &Bytes::from(data))
                 .await
+                .map_err(|e| eyre::eyre!(e)),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            tracing::error!(error = ?e, "Failed to store skip ranges");
+        }
+    }
+
+    /// Hands the result over to the downloads of other users that waited on this
+    /// one to fill the shared transcode.
+    async fn complete_shared<T: Serialize + Sync>(
+        &self,
+        args: &DownloadWorkerArgs,
+        status: StatusName,
+        status_info: &T,
+    ) {
+        if args.shared_key.is_none() {
+            return;
+        }
+        let waiting = match shared_transcodes::Model::complete(
+            &self.ctx.db,
+            args.content_download_id,
+            status.clone(),
+        )
+        .await
+        {
+            Ok(waiting) => waiting,
+            Err(e) => {
+                tracing::error!(error = ?e, "Failed to complete shared transcode");
+                return;
+            }
+        };
+        for download in waiting {
+            if let Err(e) = content_downloads::Model::notify_status(
+                &self.ctx.db,
+                download.id,
+                &download.content_id,
+                status.clone(),
+                status_info,
+            )
+            .await
+            {
+                tracing::error!(error = ?e, "Failed to notify status");
+            }
+        }
+    }
+
+    async fn report_file_progress(
+        &self,
+        args: &DownloadWorkerArgs,
+        done: u64,
+        resumed_at: u64,
+        total: Option<u64>,
+        started: std::time::Instant,
+    ) {
+        #[allow(
+            clippy::cast_precision_loss,
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss
+        )]
+        let eta_seconds = total.map_or(0, |total| {
+            let rate = done.saturating_sub(resumed_at) as f64 / started.elapsed().as_secs_f64();
+            if rate > 0.0 {
+                (total.saturating_sub(done) as f64 / rate) as usize
+            } else {
+                0
+            }
+        });
+        let status = notifications::DownloaderStatus::FileProgressReport {
+            done_bytes: done,
+            total_bytes: total,
+            eta: format!("{eta_seconds}s"),
+            eta_seconds,
+        };
+        if let Err(e) = content_downloads::Model::notify_status(
+            &self.ctx.db,
+            args.content_download_id,
+            &args.content.id,
+            InProgress,
+            &status,
+        )
+        .await
+        {
+            tracing::error!(error = ?e, "Failed to notify status");
+        }
+    }
+
+    /// Remuxes a finished HLS download into `main.mp4` next to its playlist and
+    /// records it as an output of the download.
+    async fn export_mp4(&self, relative: &str, download_id: Uuid, content_id: &str) {
+        let start_time = std::time::Instant::now();
+        let base_dir = SETTINGS.get().unwrap().transcoding_dir.join(relative);
+        let output = base_dir.join("main.mp4");
+
+        let result =
+            tokio::task::spawn_blocking(move || remux::remux_hls_to_mp4(&base_dir, &output))
+                .await
+                .map_err(|e| eyre::eyre!(e))
+                .and_then(|r| r);
+
+        let status = match result {
+            Ok(summary) => {
+                let elapsed = start_time.elapsed();
+                tracing::info!(?elapsed, bytes = summary.bytes, "Exported MP4");
+                let mp4 = Output::Mp4 {
+                    path: format!("{relative}/main.mp4"),
+                    size: summary.bytes,
+                    duration_secs: summary.duration_secs,
+                };
+                if let Err(e) =
+                    content_downloads::Model::add_output(&self.ctx.db, download_id, mp4).await
                 {
-                    tracing::error!(error = ?e, "Failed to notify status");
+                    tracing::error!(error = ?e, "Failed to record MP4 output");
+                }
+                notifications::DownloaderStatus::ExportFinished {
+                    elapsed,
+                    bytes: summary.bytes,
+                }
+            }
+            Err(e) => {
+                tracing::error!(error = ?e, "Failed to export MP4");
+                notifications::DownloaderStatus::ExportFailed {
+                    error: e.to_string(),
                 }
-                Ok(())
             }
+        };
+        // The HLS download is playable either way, the export is best effort.
+        if let Err(e) = content_downloads::Model::notify_status(
+            &self.ctx.db,
+            download_id,
+            content_id,
+            Success,
+            &status,
+        )
+        .await
+        {
+            tracing::error!(error = ?e, "Failed to notify status");
         }
     }
-}
 
-impl DownloadWorker {
     async fn download_file(
         ctx: AppContext,
         url: String,
@@ -238,6 +616,16 @@ impl DownloadWorker {
     }
 }
 
+/// Maps a remote segment uri to `<variant>/<file name>` inside the download dir.
+fn local_uri(variant: &str, uri: &str) -> eyre::Result<String> {
+    let uri = Uri::from_str(uri)?;
+    let name = uri.path().rsplit('/').next().unwrap_or_default();
+    if name.is_empty() {
+        eyre::bail!("Segment uri {uri} has no file name");
+    }
+    Ok(format!("{variant}/{name}"))
+}
+
 fn http_client() -> ClientWithMiddleware {
     let client = reqwest::Client::builder().build().unwrap();
 
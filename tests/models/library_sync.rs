@@ -0,0 +1,218 @@
+use loco_rs::testing;
+use moonlit_binge::{
+    app::App,
+    common::library_sync,
+    initializers::media_provider::ConnectedMediaProvider,
+    models::{
+        _entities::{contents, libraries, player_connections},
+        player_connections::SYNC_LOCK_TIMEOUT,
+    },
+};
+use players::types::{Content, ContentKind, Item, Library, LibraryKind, Metadata, VrLayout};
+use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseConnection};
+use serial_test::serial;
+
+async fn connection(
+    db: &DatabaseConnection,
+    identity: Option<serde_json::Value>,
+) -> player_connections::Model {
+    player_connections::ActiveModel {
+        media_provider_id: ActiveValue::Set("test_jf".to_string()),
+        user_id: ActiveValue::Set(1),
+        identity: ActiveValue::Set(identity),
+        stream_token: ActiveValue::Set(uuid::Uuid::new_v4()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap()
+}
+
+async fn cache_library(db: &DatabaseConnection, connection_id: i32, id: &str, parent_id: &str) {
+    let library = Library {
+        id: id.to_string(),
+        parent_id: Some(parent_id.to_string()),
+        name: id.to_string(),
+        description: None,
+        icon_url: None,
+        kind: LibraryKind::Folder,
+        series_name: None,
+        metadata: Metadata::default(),
+    };
+    libraries::Model::upsert_cache_data(db, connection_id, &[&library], None)
+        .await
+        .unwrap();
+}
+
+async fn cache_content(db: &DatabaseConnection, connection_id: i32, id: &str, parent_id: &str) {
+    let content = Content {
+        id: id.to_string(),
+        parent_id: Some(parent_id.to_string()),
+        name: id.to_string(),
+        description: None,
+        icon_url: None,
+        series_name: None,
+        season_name: None,
+        media_streams: vec![],
+        kind: ContentKind::Movie,
+        media_sources: vec![],
+        layout: VrLayout::default(),
+        chapters: vec![],
+        airs: None,
+        metadata: Metadata::default(),
+    };
+    contents::Model::upsert_cache_data(db, connection_id, &[&content], None)
+        .await
+        .unwrap();
+}
+
+/// Ids of the items cached under a library, libraries first.
+async fn cached_children(
+    db: &DatabaseConnection,
+    connection_id: i32,
+    parent_id: &str,
+) -> Vec<String> {
+    let mut ids = libraries::Model::ids_by_connection_and_parent_id(db, connection_id, parent_id)
+        .await
+        .unwrap();
+    ids.sort();
+    let mut contents =
+        contents::Model::ids_by_connection_and_parent_id(db, connection_id, parent_id)
+            .await
+            .unwrap();
+    contents.sort();
+    ids.extend(contents);
+    ids
+}
+
+#[tokio::test]
+#[serial]
+async fn tombstones_everything_under_removed_libraries() {
+    crate::testing::boot_with_testcontainers::<App, _, _>(|boot| async move {
+        let db = &boot.app_context.db;
+        testing::seed::<App>(db).await.unwrap();
+        let connection = connection(db, None).await;
+        cache_library(db, connection.id, "show", "root").await;
+        cache_library(db, connection.id, "season", "show").await;
+        cache_content(db, connection.id, "episode", "season").await;
+        cache_library(db, connection.id, "kept", "root").await;
+        cache_content(db, connection.id, "movie", "kept").await;
+
+        let removed =
+            libraries::Model::tombstone_missing(db, connection.id, "root", &["kept".to_string()])
+                .await
+                .unwrap();
+        assert_eq!(removed, 3);
+        assert_eq!(cached_children(db, connection.id, "root").await, ["kept"]);
+        assert!(cached_children(db, connection.id, "show").await.is_empty());
+        assert!(cached_children(db, connection.id, "season")
+            .await
+            .is_empty());
+
+        // Like a webhook saying the library was deleted.
+        let removed = libraries::Model::tombstone(db, &[connection.id], "kept")
+            .await
+            .unwrap();
+        assert_eq!(removed, 2);
+        assert!(cached_children(db, connection.id, "kept").await.is_empty());
+
+        // Coming back restores the library, its children are synced again.
+        cache_library(db, connection.id, "show", "root").await;
+        assert_eq!(cached_children(db, connection.id, "root").await, ["show"]);
+        assert!(cached_children(db, connection.id, "show").await.is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn syncs_changes_since_the_last_sync() {
+    crate::testing::boot_with_testcontainers::<App, _, _>(|boot| async move {
+        let db = &boot.app_context.db;
+        testing::seed::<App>(db).await.unwrap();
+        let connection = connection(db, Some(crate::testing::jellyfin_identity().await)).await;
+        let provider = ConnectedMediaProvider::try_from(connection.clone()).unwrap();
+        let root = provider
+            .items(None)
+            .await
+            .unwrap()
+            .into_iter()
+            .find_map(|item| match item {
+                Item::Library(library) => Some(library.id),
+                Item::Content(_) => None,
+            })
+            .expect("the test library");
+
+        let first = library_sync::sync_connection(db, &connection)
+            .await
+            .unwrap();
+        assert_eq!(first.last_error, None);
+        assert!(first.synced_from.is_some());
+        let synced = cached_children(db, connection.id, &root).await;
+
+        // Gone from the provider since the first sync.
+        cache_library(db, connection.id, "gone", &root).await;
+        cache_content(db, connection.id, "gone-episode", "gone").await;
+
+        let connection = player_connections::Model::find_by_user_and_id(db, 1, connection.id)
+            .await
+            .unwrap();
+        let second = library_sync::sync_connection(db, &connection)
+            .await
+            .unwrap();
+        assert_eq!(second.last_error, None);
+        assert!(second.synced_from > first.synced_from);
+        assert_eq!(second.removed, 2);
+        assert_eq!(cached_children(db, connection.id, &root).await, synced);
+        assert!(cached_children(db, connection.id, "gone").await.is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn skips_connections_already_syncing() {
+    crate::testing::boot_with_testcontainers::<App, _, _>(|boot| async move {
+        let db = &boot.app_context.db;
+        testing::seed::<App>(db).await.unwrap();
+        let connection = connection(db, Some(crate::testing::jellyfin_identity().await)).await;
+        let now = chrono::Utc::now().naive_utc();
+
+        // Another process holds the connection.
+        assert!(
+            player_connections::Model::start_sync(db, connection.id, now)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !player_connections::Model::start_sync(db, connection.id, now)
+                .await
+                .unwrap()
+        );
+        let status = library_sync::sync_connection(db, &connection)
+            .await
+            .unwrap();
+        assert_eq!(status.last_sync_at, None);
+
+        // A claim left by a sync that died is taken over eventually.
+        let later = now + SYNC_LOCK_TIMEOUT + chrono::Duration::minutes(1);
+        assert!(
+            player_connections::Model::start_sync(db, connection.id, later)
+                .await
+                .unwrap()
+        );
+
+        player_connections::Model::end_sync(db, connection.id)
+            .await
+            .unwrap();
+        let status = library_sync::sync_connection(db, &connection)
+            .await
+            .unwrap();
+        assert!(status.last_sync_at.is_some());
+        let connection = player_connections::Model::find_by_user_and_id(db, 1, connection.id)
+            .await
+            .unwrap();
+        assert!(!connection.is_syncing(chrono::Utc::now().naive_utc()));
+    })
+    .await;
+}
@@ -1,6 +1,9 @@
 mod users;
 
+mod library_sync;
 mod player_connections;
+mod prefetch;
+mod shared_transcodes;
 
 // mod contents;
 // mod content_downloads;
@@ -1,5 +1,15 @@
 use loco_rs::testing;
-use moonlit_binge::app::App;
+use moonlit_binge::{
+    app::App,
+    common::{
+        pagination::{Cursor, Section, SortOrder, PAGE_SIZE},
+        search::{self, SEARCH_LIMIT},
+    },
+    models::_entities::{contents, libraries, player_connections},
+};
+use players::types::{Content, ContentKind, Library, LibraryKind, Metadata, VrLayout};
+use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseConnection};
+use serial_test::serial;
 
 macro_rules! configure_insta {
     ($($expr:expr),*) => {
@@ -29,3 +39,269 @@ async fn test_model() {
     // snapshot the result:
     // assert_debug_snapshot!(item);
 }
+
+const PARENT_ID: &str = "root";
+
+/// A connection with `libraries` folders and `contents` movies cached under
+/// [`PARENT_ID`].
+async fn cached_library(
+    db: &DatabaseConnection,
+    libraries: usize,
+    contents: usize,
+) -> player_connections::Model {
+    let connection = player_connections::ActiveModel {
+        media_provider_id: ActiveValue::Set("test_jf".to_string()),
+        user_id: ActiveValue::Set(1),
+        stream_token: ActiveValue::Set(uuid::Uuid::new_v4()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+    let folders: Vec<Library> = (0..libraries)
+        .map(|i| Library {
+            id: format!("library-{i:03}"),
+            parent_id: Some(PARENT_ID.to_string()),
+            name: format!("Folder {i:03}"),
+            description: None,
+            icon_url: None,
+            kind: LibraryKind::Folder,
+            series_name: None,
+            metadata: Metadata::default(),
+        })
+        .collect();
+    libraries::Model::upsert_cache_data(
+        db,
+        connection.id,
+        &folders.iter().collect::<Vec<_>>(),
+        None,
+    )
+    .await
+    .unwrap();
+    let movies: Vec<Content> = (0..contents).map(movie).collect();
+    contents::Model::upsert_cache_data(db, connection.id, &movies.iter().collect::<Vec<_>>(), None)
+        .await
+        .unwrap();
+    connection
+}
+
+/// Movies listed in the order of their number, except by date added which lists
+/// the newest first. Every ten share a year, runtime and date added, so pages
+/// also have to get past ties.
+fn movie(i: usize) -> Content {
+    let group = i32::try_from(i / 10).unwrap();
+    Content {
+        id: format!("movie-{i:03}"),
+        parent_id: Some(PARENT_ID.to_string()),
+        name: format!("Movie {i:03}"),
+        description: None,
+        icon_url: None,
+        series_name: None,
+        season_name: None,
+        media_streams: vec![],
+        kind: ContentKind::Movie,
+        media_sources: vec![],
+        layout: VrLayout::default(),
+        chapters: vec![],
+        airs: None,
+        metadata: Metadata {
+            date_added: Some(
+                chrono::DateTime::UNIX_EPOCH + chrono::Duration::days(i64::from(group)),
+            ),
+            runtime_ms: Some(60_000 * u64::try_from(group + 1).unwrap()),
+            production_year: Some(1950 + group),
+            ..Metadata::default()
+        },
+    }
+}
+
+/// Ids of every item of the library, fetched `limit` at a time.
+async fn all_pages(
+    db: &DatabaseConnection,
+    connection_id: i32,
+    order: SortOrder,
+    limit: u64,
+) -> Vec<Vec<String>> {
+    let mut pages = vec![];
+    let mut after: Option<Cursor> = None;
+    loop {
+        let page = player_connections::Model::items(
+            db,
+            connection_id,
+            PARENT_ID,
+            order,
+            after.as_ref(),
+            limit,
+        )
+        .await
+        .unwrap();
+        assert!(page.items.len() as u64 <= limit);
+        pages.push(
+            page.items
+                .iter()
+                .map(|item| item.id().to_string())
+                .collect(),
+        );
+        match page.next {
+            Some(next) => after = Some(next),
+            None => return pages,
+        }
+        assert!(pages.len() < 100, "Paging doesn't end");
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn pages_from_libraries_into_contents() {
+    crate::testing::boot_with_testcontainers::<App, _, _>(|boot| async move {
+        let db = &boot.app_context.db;
+        testing::seed::<App>(db).await.unwrap();
+        let connection = cached_library(db, 3, 2).await;
+
+        // Libraries exactly fill the first page.
+        let pages = all_pages(db, connection.id, SortOrder::Name, 3).await;
+        assert_eq!(
+            pages,
+            vec![
+                vec!["library-000", "library-001", "library-002"],
+                vec!["movie-000", "movie-001"],
+            ]
+        );
+
+        // A page holding the last libraries continues with the contents.
+        let pages = all_pages(db, connection.id, SortOrder::Name, 2).await;
+        assert_eq!(
+            pages,
+            vec![
+                vec!["library-000", "library-001"],
+                vec!["library-002", "movie-000"],
+                vec!["movie-001"],
+            ]
+        );
+
+        let first = player_connections::Model::items(
+            db,
+            connection.id,
+            PARENT_ID,
+            SortOrder::Name,
+            None,
+            2,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            first.next.map(|next| next.section),
+            Some(Section::Libraries)
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn finds_cached_items_by_prefix() {
+    crate::testing::boot_with_testcontainers::<App, _, _>(|boot| async move {
+        let db = &boot.app_context.db;
+        testing::seed::<App>(db).await.unwrap();
+        let connection = cached_library(db, 0, 0).await;
+        let contents = [
+            Content {
+                name: "The Expanse".to_string(),
+                ..movie(0)
+            },
+            Content {
+                name: "Leviathan Wakes".to_string(),
+                description: Some("Based on The Expanse novels".to_string()),
+                ..movie(1)
+            },
+            Content {
+                name: "Expedition".to_string(),
+                ..movie(2)
+            },
+        ];
+        contents::Model::upsert_cache_data(
+            db,
+            connection.id,
+            &contents.iter().collect::<Vec<_>>(),
+            None,
+        )
+        .await
+        .unwrap();
+        let folder = Library {
+            id: "library-expanse".to_string(),
+            parent_id: Some(PARENT_ID.to_string()),
+            name: "Expanse Extras".to_string(),
+            description: None,
+            icon_url: None,
+            kind: LibraryKind::Folder,
+            series_name: None,
+            metadata: Metadata::default(),
+        };
+        libraries::Model::upsert_cache_data(db, connection.id, &[&folder], None)
+            .await
+            .unwrap();
+
+        let search = |term: &str| {
+            let query = search::prefix_query(term).unwrap();
+            async move {
+                contents::Model::search(db, connection.id, &query, SEARCH_LIMIT)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|content| content.content_id)
+                    .collect::<Vec<_>>()
+            }
+        };
+        // Names outrank descriptions.
+        assert_eq!(search("expan").await, ["movie-000", "movie-001"]);
+        let found = search("EXP").await;
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[2], "movie-001");
+        assert_eq!(search("the exp").await, ["movie-000", "movie-001"]);
+        assert!(search("expanses").await.is_empty());
+
+        let libraries = libraries::Model::search(
+            db,
+            connection.id,
+            &search::prefix_query("extra").unwrap(),
+            SEARCH_LIMIT,
+        )
+        .await
+        .unwrap();
+        assert_eq!(libraries.len(), 1);
+        assert_eq!(libraries[0].library_id, "library-expanse");
+
+        contents::Model::tombstone(db, &[connection.id], "movie-000")
+            .await
+            .unwrap();
+        assert_eq!(search("expan").await, ["movie-001"]);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn pages_past_a_page_in_every_order() {
+    crate::testing::boot_with_testcontainers::<App, _, _>(|boot| async move {
+        let db = &boot.app_context.db;
+        testing::seed::<App>(db).await.unwrap();
+        let count = usize::try_from(PAGE_SIZE).unwrap() * 2 + 5;
+        let connection = cached_library(db, 2, count).await;
+
+        for order in SortOrder::ALL {
+            let pages = all_pages(db, connection.id, order, PAGE_SIZE).await;
+            assert_eq!(pages.len(), 3, "{order:?}");
+            let mut movies: Vec<String> = (0..count).map(|i| movie(i).id).collect();
+            if order == SortOrder::DateAdded {
+                movies.reverse();
+            }
+            let expected: Vec<String> = ["library-000", "library-001"]
+                .into_iter()
+                .map(String::from)
+                .chain(movies)
+                .collect();
+            assert_eq!(pages.concat(), expected, "{order:?}");
+        }
+    })
+    .await;
+}
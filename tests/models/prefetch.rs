@@ -0,0 +1,150 @@
+use loco_rs::testing;
+use moonlit_binge::{
+    app::App,
+    common::{
+        library_sync,
+        prefetch::{self, DEFAULT_DOWNLOAD_QUOTA},
+        settings::SETTINGS,
+        variants::Variant,
+    },
+    initializers::media_provider::ConnectedMediaProvider,
+    models::_entities::{
+        content_downloads, contents, libraries, player_connections,
+        sea_orm_active_enums::StatusName, users,
+    },
+};
+use players::types::{Content, ContentKind, Metadata, VrLayout};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder,
+};
+use serial_test::serial;
+
+/// Keeps `count` of the connection's downloads transcoding, of movies that
+/// aren't in the show.
+async fn transcoding(db: &DatabaseConnection, connection_id: i32, count: u64) {
+    for i in 0..count {
+        let movie = Content {
+            id: format!("movie-{i}"),
+            parent_id: None,
+            name: format!("Movie {i}"),
+            description: None,
+            icon_url: None,
+            series_name: None,
+            season_name: None,
+            media_streams: vec![],
+            kind: ContentKind::Movie,
+            media_sources: vec![],
+            layout: VrLayout::default(),
+            chapters: vec![],
+            airs: None,
+            metadata: Metadata::default(),
+        };
+        contents::Model::upsert_cache_data(db, connection_id, &[&movie], None)
+            .await
+            .unwrap();
+        contents::Model::start_download(db, connection_id, &movie.id, &Variant::default())
+            .await
+            .unwrap();
+    }
+}
+
+async fn status(
+    db: &DatabaseConnection,
+    connection_id: i32,
+    content_id: &str,
+) -> Option<StatusName> {
+    contents::Model::by_connection_and_id(db, connection_id, content_id)
+        .await
+        .unwrap()
+        .status
+}
+
+#[tokio::test]
+#[serial]
+async fn prefetches_within_the_season_and_quota() {
+    crate::testing::boot_with_testcontainers::<App, _, _>(|boot| async move {
+        let db = &boot.app_context.db;
+        testing::seed::<App>(db).await.unwrap();
+        let user = users::Entity::find_by_id(1).one(db).await.unwrap().unwrap();
+        users::ActiveModel {
+            prefetch_episodes: ActiveValue::Set(5),
+            ..user.into()
+        }
+        .update(db)
+        .await
+        .unwrap();
+        let connection = player_connections::ActiveModel {
+            media_provider_id: ActiveValue::Set("test_jf".to_string()),
+            user_id: ActiveValue::Set(1),
+            identity: ActiveValue::Set(Some(crate::testing::jellyfin_identity().await)),
+            stream_token: ActiveValue::Set(uuid::Uuid::new_v4()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        let provider = ConnectedMediaProvider::try_from(connection.clone()).unwrap();
+        let show = crate::testing::jellyfin_test_show(&provider).await;
+        library_sync::sync_subtree(db, &provider, connection.id, &show)
+            .await
+            .unwrap();
+        let season = libraries::Model::ids_by_connection_and_parent_id(db, connection.id, &show)
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+        let episodes: Vec<String> = contents::Entity::find()
+            .filter(contents::Column::PlayerConnectionId.eq(connection.id))
+            .filter(contents::Column::ParentId.eq(&season))
+            .order_by_asc(contents::Column::SortKey)
+            .all(db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|episode| episode.content_id)
+            .collect();
+        assert_eq!(episodes.len(), 3);
+
+        // Nothing follows the season finale.
+        assert_eq!(
+            prefetch::next_episodes(db, &connection, &episodes[2])
+                .await
+                .unwrap(),
+            0
+        );
+
+        // Room for one more download only fetches the very next episode.
+        let quota = SETTINGS
+            .get()
+            .and_then(|settings| settings.download_quota)
+            .unwrap_or(DEFAULT_DOWNLOAD_QUOTA);
+        transcoding(db, connection.id, quota - 1).await;
+        assert_eq!(
+            prefetch::next_episodes(db, &connection, &episodes[0])
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            status(db, connection.id, &episodes[1]).await,
+            Some(StatusName::InProgress)
+        );
+        assert_eq!(status(db, connection.id, &episodes[2]).await, None);
+
+        // And with the quota used up, nothing.
+        assert_eq!(
+            prefetch::next_episodes(db, &connection, &episodes[0])
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            content_downloads::Model::in_progress_by_user(db, 1)
+                .await
+                .unwrap(),
+            quota
+        );
+    })
+    .await;
+}
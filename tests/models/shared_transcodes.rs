@@ -0,0 +1,144 @@
+use loco_rs::testing;
+use moonlit_binge::{
+    app::App,
+    common::variants::Variant,
+    models::{
+        _entities::{
+            content_downloads, contents, player_connections, sea_orm_active_enums::StatusName,
+            shared_transcodes,
+        },
+        shared_transcodes::Lease,
+    },
+};
+use players::types::{Content, ContentKind, Metadata, VrLayout};
+use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseConnection, EntityTrait};
+use serial_test::serial;
+
+const CONTENT_ID: &str = "movie-1";
+
+/// A connection of the user with the movie cached, and a download of it.
+async fn download(db: &DatabaseConnection, user_id: i32) -> content_downloads::Model {
+    let connection = player_connections::ActiveModel {
+        media_provider_id: ActiveValue::Set("test_jf".to_string()),
+        user_id: ActiveValue::Set(user_id),
+        stream_token: ActiveValue::Set(uuid::Uuid::new_v4()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+    let movie = Content {
+        id: CONTENT_ID.to_string(),
+        parent_id: None,
+        name: "Moonlit".to_string(),
+        description: None,
+        icon_url: None,
+        series_name: None,
+        season_name: None,
+        media_streams: vec![],
+        kind: ContentKind::Movie,
+        media_sources: vec![],
+        layout: VrLayout::default(),
+        chapters: vec![],
+        airs: None,
+        metadata: Metadata::default(),
+    };
+    contents::Model::upsert_cache_data(db, connection.id, &[&movie], None)
+        .await
+        .unwrap();
+    let (_, download) =
+        contents::Model::start_download(db, connection.id, CONTENT_ID, &Variant::default())
+            .await
+            .unwrap();
+    download
+}
+
+async fn shared(db: &DatabaseConnection, key: &str) -> Option<shared_transcodes::Model> {
+    shared_transcodes::Entity::find_by_id(key.to_string())
+        .one(db)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+#[serial]
+async fn failed_transcodes_are_fetched_again() {
+    crate::testing::boot_with_testcontainers::<App, _, _>(|boot| async move {
+        let db = &boot.app_context.db;
+        testing::seed::<App>(db).await.unwrap();
+
+        let owner = download(db, 1).await;
+        let (_, lease) = shared_transcodes::Model::acquire(db, "test_jf", &owner)
+            .await
+            .unwrap();
+        assert_eq!(lease, Lease::Owner);
+        let waiting = download(db, 2).await;
+        let (entry, lease) = shared_transcodes::Model::acquire(db, "test_jf", &waiting)
+            .await
+            .unwrap();
+        assert_eq!(lease, Lease::Waiting);
+
+        let done = shared_transcodes::Model::complete(db, owner.id, StatusName::Error)
+            .await
+            .unwrap();
+        assert_eq!(
+            done.iter().map(|d| d.id).collect::<Vec<_>>(),
+            vec![waiting.id]
+        );
+        assert_eq!(
+            shared(db, &entry.key).await.unwrap().status,
+            StatusName::Error
+        );
+
+        let retry = download(db, 1).await;
+        let (_, lease) = shared_transcodes::Model::acquire(db, "test_jf", &retry)
+            .await
+            .unwrap();
+        assert_eq!(lease, Lease::Owner);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn removed_downloads_release_shared_transcodes() {
+    crate::testing::boot_with_testcontainers::<App, _, _>(|boot| async move {
+        let db = &boot.app_context.db;
+        testing::seed::<App>(db).await.unwrap();
+
+        let first = download(db, 1).await;
+        let (entry, _) = shared_transcodes::Model::acquire(db, "test_jf", &first)
+            .await
+            .unwrap();
+        let second = download(db, 2).await;
+        shared_transcodes::Model::acquire(db, "test_jf", &second)
+            .await
+            .unwrap();
+        shared_transcodes::Model::complete(db, first.id, StatusName::Success)
+            .await
+            .unwrap();
+        assert_eq!(shared(db, &entry.key).await.unwrap().ref_count, 2);
+
+        let dirs = content_downloads::Model::remove(db, first.player_connection_id, first.id)
+            .await
+            .unwrap();
+        assert!(dirs.is_empty());
+        assert_eq!(shared(db, &entry.key).await.unwrap().ref_count, 1);
+        assert!(shared_transcodes::Model::collect_garbage(db)
+            .await
+            .unwrap()
+            .is_empty());
+
+        content_downloads::Model::remove(db, second.player_connection_id, second.id)
+            .await
+            .unwrap();
+        assert_eq!(shared(db, &entry.key).await.unwrap().ref_count, 0);
+        let dropped = shared_transcodes::Model::collect_garbage(db).await.unwrap();
+        assert_eq!(
+            dropped.iter().map(|s| s.key.clone()).collect::<Vec<_>>(),
+            vec![entry.key.clone()]
+        );
+        assert!(shared(db, &entry.key).await.is_none());
+    })
+    .await;
+}
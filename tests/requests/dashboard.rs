@@ -0,0 +1,124 @@
+use moonlit_binge::{
+    app::App,
+    common::pagination::SortOrder,
+    models::_entities::{player_connections, users},
+};
+use sea_orm::{ActiveModelTrait, ActiveValue};
+use serial_test::serial;
+
+use super::prepare_data;
+
+#[tokio::test]
+#[serial]
+async fn shelves_of_unknown_connections_are_not_found() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let response = request
+            .get("/shelves/4242")
+            .add_header(auth_key, auth_value)
+            .await;
+
+        assert_eq!(response.status_code(), 404);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn prefetch_is_bounded() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let response = request
+            .post("/prefetch")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .form(&[("episodes", "100")])
+            .await;
+        assert_eq!(response.status_code(), 400);
+
+        let response = request
+            .post("/prefetch")
+            .add_header(auth_key, auth_value)
+            .form(&[("episodes", "4")])
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let user = users::Model::find_by_email(&ctx.db, &user.user.email)
+            .await
+            .unwrap();
+        assert_eq!(user.prefetch_episodes, 4);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn library_sort_is_saved() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let response = request
+            .post("/library_sort")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .form(&[("sort", "shuffle")])
+            .await;
+        assert!(response.status_code().is_client_error());
+
+        let response = request
+            .post("/library_sort")
+            .add_header(auth_key, auth_value)
+            .form(&[("sort", "date_added")])
+            .await;
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.header("hx-refresh"), "true");
+        let user = users::Model::find_by_email(&ctx.db, &user.user.email)
+            .await
+            .unwrap();
+        assert_eq!(user.library_sort(), SortOrder::DateAdded);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn renders_shelves() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let connection = player_connections::ActiveModel {
+            media_provider_id: ActiveValue::Set("test_jf".to_string()),
+            user_id: ActiveValue::Set(user.user.id),
+            identity: ActiveValue::Set(Some(crate::testing::jellyfin_identity().await)),
+            stream_token: ActiveValue::Set(uuid::Uuid::new_v4()),
+            ..Default::default()
+        }
+        .insert(&ctx.db)
+        .await
+        .unwrap();
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        // Jellyfin scans the test media in the background after starting up.
+        let mut body = String::new();
+        for _ in 0..30 {
+            let response = request
+                .get(&format!("/shelves/{}", connection.id))
+                .add_header(auth_key.clone(), auth_value.clone())
+                .await;
+            assert_eq!(response.status_code(), 200);
+            body = response.text();
+            if body.contains("Latest") {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        }
+        assert!(body.contains("Latest"), "{body}");
+        assert!(
+            body.contains(&format!("hx-post=\"/p/{}/transcode\"", connection.id)),
+            "{body}"
+        );
+        assert!(body.contains("Bunny"), "{body}");
+    })
+    .await;
+}
@@ -0,0 +1,40 @@
+use moonlit_binge::app::App;
+use serial_test::serial;
+
+use super::prepare_data;
+
+#[tokio::test]
+#[serial]
+async fn asks_deovr_to_log_in() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, _ctx| async move {
+        let response = request.get("/deovr").await;
+
+        assert_eq!(
+            response.json::<serde_json::Value>(),
+            serde_json::json!({"authorized": "0", "scenes": []})
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn lists_deovr_selection_with_api_key() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let response = request
+            .post("/deovr")
+            .form(&[
+                ("login", user.user.email.as_str()),
+                ("password", user.user.api_key.as_str()),
+            ])
+            .await;
+
+        assert_eq!(
+            response.json::<serde_json::Value>(),
+            serde_json::json!({"authorized": "1", "scenes": []})
+        );
+    })
+    .await;
+}
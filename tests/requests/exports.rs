@@ -0,0 +1,21 @@
+use moonlit_binge::app::App;
+use serial_test::serial;
+
+use super::prepare_data;
+
+#[tokio::test]
+#[serial]
+async fn exports_unknown_playlist_as_not_found() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let response = request
+            .get("/playlist/missing/export?format=m3u")
+            .add_header(auth_key, auth_value)
+            .await;
+
+        assert_eq!(response.status_code(), 404);
+    })
+    .await;
+}
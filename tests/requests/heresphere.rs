@@ -0,0 +1,41 @@
+use moonlit_binge::{app::App, views::heresphere::VERSION_HEADER};
+use serial_test::serial;
+
+use super::prepare_data;
+
+#[tokio::test]
+#[serial]
+async fn asks_heresphere_to_log_in() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, _ctx| async move {
+        let response = request.post("/heresphere").await;
+
+        assert_eq!(response.header(VERSION_HEADER), "1");
+        assert_eq!(
+            response.json::<serde_json::Value>(),
+            serde_json::json!({"access": -1})
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn lists_heresphere_library_with_api_key() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let response = request
+            .post("/heresphere")
+            .json(&serde_json::json!({
+                "username": user.user.email,
+                "password": user.user.api_key,
+            }))
+            .await;
+
+        assert_eq!(
+            response.json::<serde_json::Value>(),
+            serde_json::json!({"access": 1, "library": []})
+        );
+    })
+    .await;
+}
@@ -0,0 +1,62 @@
+use moonlit_binge::{app::App, models::_entities::player_connections};
+use sea_orm::{ActiveModelTrait, ActiveValue};
+use serial_test::serial;
+
+use super::prepare_data;
+
+#[tokio::test]
+#[serial]
+async fn images_need_a_user_or_stream_token() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, _ctx| async move {
+        let response = request.get("/images/1/some-item").await;
+
+        assert_eq!(response.status_code(), 401);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn stream_tokens_only_open_their_connection() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let connection = player_connections::ActiveModel {
+            media_provider_id: ActiveValue::Set("test_jf".to_string()),
+            user_id: ActiveValue::Set(user.user.id),
+            identity: ActiveValue::Set(Some(
+                serde_json::json!({"type": "auth", "id": "jellyfin-user", "token": "token"}),
+            )),
+            stream_token: ActiveValue::Set(uuid::Uuid::new_v4()),
+            ..Default::default()
+        }
+        .insert(&ctx.db)
+        .await
+        .unwrap();
+
+        let response = request
+            .get(&format!(
+                "/images/{}/some-item?token={}",
+                connection.id + 1,
+                connection.stream_token
+            ))
+            .await;
+        assert_eq!(response.status_code(), 401);
+
+        // Neither cached nor known to the provider.
+        let response = request
+            .get(&format!(
+                "/images/{}/some-item?token={}",
+                connection.id, connection.stream_token
+            ))
+            .await;
+        assert_eq!(response.status_code(), 404);
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let response = request
+            .get(&format!("/images/{}/some-item", connection.id + 1))
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(response.status_code(), 404);
+    })
+    .await;
+}
@@ -1,4 +1,12 @@
 mod auth;
+mod dashboard;
+mod deovr;
+mod exports;
+mod heresphere;
+mod images;
 mod player_connections;
 mod prepare_data;
+mod short_links;
+mod subscriptions;
 mod user;
+mod webhooks;
@@ -1,4 +1,4 @@
-use moonlit_binge::app::App;
+use moonlit_binge::{app::App, common::settings::SETTINGS};
 use serial_test::serial;
 
 use super::prepare_data;
@@ -36,3 +36,129 @@ async fn can_aaa() {
     })
     .await;
 }
+
+#[tokio::test]
+#[serial]
+async fn syncs_only_own_connections() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let response = request
+            .post("/p/4242/sync")
+            .add_header(auth_key, auth_value)
+            .await;
+
+        assert_eq!(response.status_code(), 404);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn searches_only_own_connections() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let response = request
+            .get("/p/4242/search?q=expanse")
+            .add_header(auth_key, auth_value)
+            .await;
+
+        assert_eq!(response.status_code(), 404);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn transcodes_only_own_connections() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let response = request.get("/p/4242/transcode?content=abc").await;
+        assert_eq!(response.status_code(), 401);
+
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let response = request
+            .get("/p/4242/transcode?content=abc")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(response.status_code(), 404);
+
+        let response = request
+            .post("/p/4242/transcode")
+            .add_header(auth_key, auth_value)
+            .form(&[
+                ("content", "abc"),
+                ("preferred_audio", "0"),
+                ("preferred_subtitle", "-1"),
+            ])
+            .await;
+        assert_eq!(response.status_code(), 404);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn streams_direct_files_but_not_sidecars() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, _ctx| async move {
+        let dir = SETTINGS
+            .get()
+            .unwrap()
+            .transcoding_dir
+            .join("single/1/direct/variant");
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        for name in ["original.mkv", "original.mkv.part", "skip_ranges.json"] {
+            tokio::fs::write(dir.join(name), name).await.unwrap();
+        }
+
+        let response = request
+            .get("/p/stream/single/1/direct/variant/original.mkv")
+            .await;
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.text(), "original.mkv");
+
+        for name in ["original.mkv.part", "skip_ranges.json"] {
+            let response = request
+                .get(&format!("/p/stream/single/1/direct/variant/{name}"))
+                .await;
+            assert_eq!(response.status_code(), 404, "{name}");
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn passes_only_parsed_options_to_variant_playlists() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, _ctx| async move {
+        let dir = SETTINGS
+            .get()
+            .unwrap()
+            .transcoding_dir
+            .join("single/1/skips/variant");
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        tokio::fs::write(
+            dir.join("main.m3u8"),
+            "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1000000\nv0.m3u8\n",
+        )
+        .await
+        .unwrap();
+
+        let response = request
+            .get("/p/stream/single/1/skips/variant/main.m3u8?skip=intro,%20outro&extra=%0A%23EXT-X-ENDLIST")
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let body = response.text();
+        assert!(body.contains("v0.m3u8?skip=intro,outro\n"), "{body}");
+        assert!(!body.contains("EXT-X-ENDLIST"), "{body}");
+
+        let response = request
+            .get("/p/stream/single/1/skips/variant/main.m3u8?skip=intro%0A%23EXT-X-ENDLIST")
+            .await;
+        assert_eq!(response.status_code(), 400);
+    })
+    .await;
+}
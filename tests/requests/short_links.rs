@@ -0,0 +1,89 @@
+use moonlit_binge::{app::App, models::_entities::short_links};
+use serial_test::serial;
+
+use super::prepare_data;
+
+#[tokio::test]
+#[serial]
+async fn follows_short_links_and_counts_uses() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let link =
+            short_links::Model::create(&ctx.db, user.user.id, "playlist/season-1/main.m3u8", None)
+                .await
+                .unwrap();
+
+        let response = request.get(&format!("/s/{}", link.code)).await;
+
+        assert_eq!(response.status_code(), 307);
+        assert_eq!(
+            response.header("location"),
+            "/p/stream/playlist/season-1/main.m3u8"
+        );
+        let link = short_links::Model::find_by_code(&ctx.db, &link.code)
+            .await
+            .unwrap();
+        assert_eq!(link.uses, 1);
+        assert!(link.last_used_at.is_some());
+
+        let response = request.get(&format!("/s/{}/qr.svg", link.code)).await;
+        assert_eq!(response.header("content-type"), "image/svg+xml");
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn expired_short_links_are_gone() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let yesterday = (chrono::Utc::now() - chrono::Duration::days(1)).naive_utc();
+        let link = short_links::Model::create(
+            &ctx.db,
+            user.user.id,
+            "playlist/season-1/main.m3u8",
+            Some(yesterday),
+        )
+        .await
+        .unwrap();
+
+        let response = request.get(&format!("/s/{}", link.code)).await;
+
+        assert_eq!(response.status_code(), 404);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn refuses_escaping_or_token_targets() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        for target in [
+            "single/1/%2e%2e/%2E%2E/secrets",
+            "single/1/..%2fsecrets",
+            "single/1/abc/default/main.m3u8?token=d2b7f1e0-3c4a-4b8e-9f6a-5c1d0e2f3a4b",
+            "single/1/abc/default/main.m3u8?t=1m&%74oken=x",
+        ] {
+            let response = request
+                .post("/s")
+                .add_header(auth_key.clone(), auth_value.clone())
+                .form(&[("target", target), ("expires", "")])
+                .await;
+            assert_eq!(response.status_code(), 400, "{target}");
+        }
+
+        let response = request
+            .post("/s")
+            .add_header(auth_key, auth_value)
+            .form(&[
+                ("target", "single/1/abc/default/main.m3u8"),
+                ("expires", "9223372036854775807"),
+            ])
+            .await;
+        assert_eq!(response.status_code(), 400);
+    })
+    .await;
+}
@@ -0,0 +1,141 @@
+use moonlit_binge::{
+    app::App,
+    common::{library_sync, subscriptions},
+    initializers::media_provider::ConnectedMediaProvider,
+    models::_entities::{
+        contents, libraries, player_connections, sea_orm_active_enums::StatusName,
+        subscriptions as subscription_models,
+    },
+};
+use players::types::Item;
+use sea_orm::{ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter};
+use serial_test::serial;
+
+use super::prepare_data;
+
+#[tokio::test]
+#[serial]
+async fn subscribes_only_on_own_connections() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let response = request
+            .post("/subscriptions")
+            .add_header(auth_key, auth_value)
+            .form(&[("connection", "4242"), ("library", "show")])
+            .await;
+
+        assert_eq!(response.status_code(), 404);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn edits_only_own_subscriptions() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let response = request
+            .post("/subscriptions/4242")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .form(&[("audio_language", "eng")])
+            .await;
+        assert_eq!(response.status_code(), 404);
+
+        let response = request
+            .delete("/subscriptions/4242")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(response.status_code(), 404);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn downloads_new_episodes() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let connection = player_connections::ActiveModel {
+            media_provider_id: ActiveValue::Set("test_jf".to_string()),
+            user_id: ActiveValue::Set(user.user.id),
+            identity: ActiveValue::Set(Some(crate::testing::jellyfin_identity().await)),
+            stream_token: ActiveValue::Set(uuid::Uuid::new_v4()),
+            ..Default::default()
+        }
+        .insert(&ctx.db)
+        .await
+        .unwrap();
+        let provider = ConnectedMediaProvider::try_from(connection.clone()).unwrap();
+        let show = crate::testing::jellyfin_test_show(&provider).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let response = request
+            .post("/subscriptions")
+            .add_header(auth_key, auth_value)
+            .form(&[
+                ("connection", connection.id.to_string()),
+                ("library", show.clone()),
+            ])
+            .await;
+        assert_eq!(response.status_code(), 200);
+        // Episodes there before subscribing are left alone.
+        assert_eq!(
+            subscriptions::download_new(&ctx.db, &connection)
+                .await
+                .unwrap(),
+            0
+        );
+
+        // The episode turns up again, along with one the provider doesn't know.
+        let season =
+            libraries::Model::ids_by_connection_and_parent_id(&ctx.db, connection.id, &show)
+                .await
+                .unwrap()
+                .pop()
+                .unwrap();
+        let episode =
+            contents::Model::ids_by_connection_and_parent_id(&ctx.db, connection.id, &season)
+                .await
+                .unwrap()
+                .pop()
+                .unwrap();
+        contents::Entity::delete_many()
+            .filter(contents::Column::ContentId.eq(&episode))
+            .exec(&ctx.db)
+            .await
+            .unwrap();
+        library_sync::sync_subtree(&ctx.db, &provider, connection.id, &show)
+            .await
+            .unwrap();
+        let Item::Content(mut unknown) = provider.item(&episode).await.unwrap() else {
+            panic!("not an episode");
+        };
+        unknown.id = "ffffffffffffffffffffffffffffffff".to_string();
+        contents::Model::upsert_cache_data(&ctx.db, connection.id, &[&unknown], Some(&season))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            subscriptions::download_new(&ctx.db, &connection)
+                .await
+                .unwrap(),
+            1
+        );
+        let downloaded = contents::Model::by_connection_and_id(&ctx.db, connection.id, &episode)
+            .await
+            .unwrap();
+        assert_eq!(downloaded.status, Some(StatusName::InProgress));
+        let subscription = subscription_models::Entity::find()
+            .one(&ctx.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(subscription.downloads, 1);
+        assert!(subscription.last_checked_at.is_some());
+    })
+    .await;
+}
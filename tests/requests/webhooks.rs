@@ -0,0 +1,124 @@
+use moonlit_binge::{
+    app::App,
+    models::_entities::{contents, player_connections},
+};
+use players::types::{Content, ContentKind, Metadata, VrLayout};
+use sea_orm::{ActiveModelTrait, ActiveValue};
+use serial_test::serial;
+
+use super::prepare_data;
+
+const TOKEN: &str = "test-webhook-token";
+/// How the API formats the fixtures' `ItemId`.
+const ITEM_ID: &str = "8f5a9d3c-2b1e-4f6a-9c7d-0e1f2a3b4c5d";
+const JELLYFIN_USER_ID: &str = "d2b7f1e0-3c4a-4b8e-9f6a-5c1d0e2f3a4b";
+
+async fn cached_episode(ctx: &loco_rs::app::AppContext, user_id: i32) -> player_connections::Model {
+    let connection = player_connections::ActiveModel {
+        media_provider_id: ActiveValue::Set("test_jf".to_string()),
+        user_id: ActiveValue::Set(user_id),
+        identity: ActiveValue::Set(Some(
+            serde_json::json!({"type": "auth", "id": JELLYFIN_USER_ID, "token": "token"}),
+        )),
+        stream_token: ActiveValue::Set(uuid::Uuid::new_v4()),
+        ..Default::default()
+    }
+    .insert(&ctx.db)
+    .await
+    .unwrap();
+    let episode = Content {
+        id: ITEM_ID.to_string(),
+        parent_id: Some("season-1".to_string()),
+        name: "The Long Way Round".to_string(),
+        description: None,
+        icon_url: None,
+        series_name: Some("Moonlit Tales".to_string()),
+        season_name: Some("Season 1".to_string()),
+        media_streams: vec![],
+        kind: ContentKind::Episode {
+            season: Some(1),
+            episode: 3,
+        },
+        media_sources: vec![],
+        layout: VrLayout::default(),
+        chapters: vec![],
+        airs: None,
+        metadata: Metadata::default(),
+    };
+    contents::Model::upsert_cache_data(&ctx.db, connection.id, &[&episode], Some("season-1"))
+        .await
+        .unwrap();
+    connection
+}
+
+#[tokio::test]
+#[serial]
+async fn refuses_webhooks_without_the_token() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, _ctx| async move {
+        let payload = include_str!("../fixtures/webhooks/jellyfin_item_deleted.json");
+
+        let response = request
+            .post("/webhooks/test_jf?token=wrong")
+            .text(payload)
+            .await;
+        assert_eq!(response.status_code(), 401);
+
+        let response = request
+            .post(&format!("/webhooks/unknown?token={TOKEN}"))
+            .text(payload)
+            .await;
+        assert_eq!(response.status_code(), 404);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn tombstones_deleted_items() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let connection = cached_episode(&ctx, user.user.id).await;
+
+        let response = request
+            .post("/webhooks/test_jf")
+            .add_header(
+                axum::http::HeaderName::from_static("x-webhook-token"),
+                axum::http::HeaderValue::from_static(TOKEN),
+            )
+            .text(include_str!(
+                "../fixtures/webhooks/jellyfin_item_deleted.json"
+            ))
+            .await;
+
+        assert_eq!(response.status_code(), 200);
+        let content = contents::Model::by_connection_and_id(&ctx.db, connection.id, ITEM_ID)
+            .await
+            .unwrap();
+        assert!(content.removed_at.is_some());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn takes_over_watch_progress() {
+    crate::testing::request_with_testcontainers::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let connection = cached_episode(&ctx, user.user.id).await;
+
+        let response = request
+            .post(&format!("/webhooks/test_jf?token={TOKEN}"))
+            .text(include_str!(
+                "../fixtures/webhooks/jellyfin_user_data_saved.json"
+            ))
+            .await;
+
+        assert_eq!(response.status_code(), 200);
+        let content = contents::Model::by_connection_and_id(&ctx.db, connection.id, ITEM_ID)
+            .await
+            .unwrap();
+        assert_eq!(content.resume_position_ms, Some(451_234));
+        assert!(content.played_at.is_none());
+    })
+    .await;
+}
@@ -8,9 +8,11 @@ use loco_rs::{
     environment::Environment,
     Result,
 };
+use moonlit_binge::initializers::media_provider::{ConnectedMediaProvider, CELL};
 use players::{
-    jellyfin::Jellyfin,
+    jellyfin::{Jellyfin, SetupStep},
     testcontainers::{Jellyfin as JellyfinContainer, JELLYFIN_HTTP_PORT},
+    types::{Item, LibraryKind},
 };
 use serde_json::json;
 use testcontainers::{runners::AsyncRunner, ContainerAsync, ImageExt};
@@ -149,3 +151,44 @@ where
     })
     .await;
 }
+
+/// Identity of the user the test Jellyfin was set up with, for connections that
+/// need to reach it. Only works once the app booted.
+///
+/// # Panics
+///
+/// Panics if the test provider isn't configured or login fails
+pub async fn jellyfin_identity() -> serde_json::Value {
+    let provider = CELL.get().unwrap().get("test_jf").unwrap();
+    let user = Jellyfin::new(&provider.url, &None)
+        .authenticate("root", "root")
+        .await
+        .unwrap();
+    serde_json::to_value(SetupStep::Auth {
+        id: user.id,
+        token: user.token,
+    })
+    .unwrap()
+}
+
+/// Id of the show in the test media, waiting for Jellyfin to scan it.
+///
+/// # Panics
+///
+/// Panics if the provider can't be reached or never lists the show
+pub async fn jellyfin_test_show(provider: &ConnectedMediaProvider) -> String {
+    for _ in 0..30 {
+        for view in provider.items(None).await.unwrap() {
+            let Item::Library(view) = view else { continue };
+            for item in provider.items(Some(view)).await.unwrap() {
+                if let Item::Library(library) = item {
+                    if library.kind == LibraryKind::Show {
+                        return library.id;
+                    }
+                }
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    }
+    panic!("Jellyfin didn't pick up the test show");
+}